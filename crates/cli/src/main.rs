@@ -0,0 +1,194 @@
+//! A command-line tool for working with the `webidl-bindings` custom section of
+//! Wasm binaries.
+//!
+//! * `embed` parses bindings written in the straw proposal text format and
+//!   attaches them to a Wasm binary, replacing any existing section.
+//!
+//! * `extract` prints a Wasm binary's section in the text format, or as JSON
+//!   with `--json`.
+//!
+//! * `validate` checks that a Wasm binary has a well-formed section, whose
+//!   references to Wasm functions and function types are all in bounds.
+//!
+//! * `strip` removes the section from a Wasm binary.
+//!
+//! * `dump` prints an annotated hex dump of the section's raw bytes, like
+//!   `wasm-objdump`, for inspecting sections that fail to decode.
+//!
+//! * `fmt` formats a text format bindings file canonically, or with `--check`
+//!   only checks that it already is.
+//!
+//! Exits with status 0 on success, 1 if anything goes wrong, including an
+//! invalid or missing section, and 2 if the arguments are invalid, so it can
+//! be used to gate CI.
+
+use clap::{Parser, Subcommand};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use wasm_webidl_bindings::spans::{render_snippet, Span};
+use wasm_webidl_bindings::{binary, embed, text};
+
+#[derive(Debug, Parser)]
+#[command(name = "wasm-webidl-bindings", version)]
+struct Options {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Attach text format bindings to a Wasm binary.
+    Embed {
+        /// The Wasm binary.
+        wasm: PathBuf,
+        /// The bindings, in the straw proposal text format.
+        bindings: PathBuf,
+        /// Where to write the new Wasm binary, instead of overwriting the input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print a Wasm binary's bindings in the text format.
+    Extract {
+        /// The Wasm binary.
+        wasm: PathBuf,
+        /// Where to write the bindings, instead of standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Print the bindings as JSON instead of in the text format.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check a Wasm binary's bindings against the rest of the binary.
+    Validate {
+        /// The Wasm binary.
+        wasm: PathBuf,
+    },
+    /// Remove the bindings from a Wasm binary.
+    Strip {
+        /// The Wasm binary.
+        wasm: PathBuf,
+        /// Where to write the new Wasm binary, instead of overwriting the input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print an annotated hex dump of a Wasm binary's bindings section.
+    Dump {
+        /// The Wasm binary.
+        wasm: PathBuf,
+    },
+    /// Format text format bindings canonically.
+    Fmt {
+        /// The bindings, in the straw proposal text format.
+        bindings: PathBuf,
+        /// Where to write the formatted bindings, instead of overwriting the
+        /// input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Fail if the bindings aren't formatted, instead of formatting them.
+        #[arg(long, conflicts_with = "output")]
+        check: bool,
+    },
+}
+
+fn main() {
+    let options = Options::parse();
+    if let Err(e) = run(options.command) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(command: Command) -> Result<(), failure::Error> {
+    match command {
+        Command::Embed {
+            wasm,
+            bindings,
+            output,
+        } => {
+            let output = output.as_ref().unwrap_or(&wasm);
+            embed::embed_file(&wasm, &bindings, output)
+        }
+        Command::Extract { wasm, output, json } => {
+            let extract = if json {
+                embed::extract_json
+            } else {
+                embed::extract
+            };
+            let mut text = extract_with(&wasm, extract)?;
+            if json {
+                text.push('\n');
+            }
+            match output {
+                Some(output) => write(&output, text.as_bytes()),
+                None => {
+                    print!("{}", text);
+                    Ok(())
+                }
+            }
+        }
+        Command::Validate { wasm } => {
+            extract_with(&wasm, embed::extract)?;
+            Ok(())
+        }
+        Command::Strip { wasm, output } => {
+            let stripped = embed::strip(&read(&wasm)?)?;
+            write(output.as_ref().unwrap_or(&wasm), &stripped)
+        }
+        Command::Dump { wasm } => {
+            let mut module = walrus::Module::from_buffer(&read(&wasm)?)
+                .map_err(|e| failure::format_err!("failed to parse the Wasm module: {}", e))?;
+            let section = match module.customs.remove_raw("webidl-bindings") {
+                Some(section) => section,
+                None => failure::bail!("`{}` has no `webidl-bindings` section", wasm.display()),
+            };
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let result = binary::dump(&section.data, &mut stdout);
+            stdout.flush()?;
+            result
+        }
+        Command::Fmt {
+            bindings,
+            output,
+            check,
+        } => {
+            let input = String::from_utf8(read(&bindings)?)
+                .map_err(|_| failure::format_err!("`{}` is not valid UTF-8", bindings.display()))?;
+            let formatted = text::format(&input).map_err(|e| {
+                let span = Span {
+                    start: e.start,
+                    end: e.end,
+                };
+                failure::format_err!("{}", render_snippet(&input, span, &e.message))
+            })?;
+            if !check {
+                return write(output.as_ref().unwrap_or(&bindings), formatted.as_bytes());
+            }
+            if formatted != input {
+                failure::bail!("`{}` is not formatted", bindings.display());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn extract_with(
+    wasm: &Path,
+    extract: fn(&[u8]) -> Result<Option<String>, failure::Error>,
+) -> Result<String, failure::Error> {
+    match extract(&read(wasm)?)? {
+        Some(text) => Ok(text),
+        None => failure::bail!("`{}` has no `webidl-bindings` section", wasm.display()),
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, failure::Error> {
+    fs::read(path).map_err(|e| failure::format_err!("failed to read `{}`: {}", path.display(), e))
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<(), failure::Error> {
+    fs::write(path, contents)
+        .map_err(|e| failure::format_err!("failed to write `{}`: {}", path.display(), e))
+}
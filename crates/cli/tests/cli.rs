@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn wasm_webidl_bindings(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wasm-webidl-bindings"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Create a fresh directory for a test's files.
+fn dir(test: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    drop(fs::remove_dir_all(&dir));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_wasm(dir: &Path) -> String {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[walrus::ValType::I32], &[]);
+    module.add_import_func("env", "log", ty);
+    let path = dir.join("module.wasm");
+    fs::write(&path, module.emit_wasm().unwrap()).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn embed_extract_validate_strip() {
+    let dir = dir("embed_extract_validate_strip");
+    let wasm = write_wasm(&dir);
+    let bindings = dir.join("bindings.webidl");
+    fs::write(
+        &bindings,
+        r#"
+        type $Log (func (param long))
+        func-binding $log import 0 $Log (param (as long 0)) (result)
+        bind (import "env" "log") $log
+        "#,
+    )
+    .unwrap();
+
+    // Without a section, validation fails.
+    let output = wasm_webidl_bindings(&["validate", &wasm]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = wasm_webidl_bindings(&["embed", &wasm, bindings.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = wasm_webidl_bindings(&["validate", &wasm]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = wasm_webidl_bindings(&["extract", &wasm]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "type $Log\n    (func (param long))\n\n\
+         func-binding $log import 0 $Log\n    (param (as long 0))\n    (result)\n\n\
+         bind\n    (import \"env\" \"log\")\n    $log\n"
+    );
+
+    let output = wasm_webidl_bindings(&["extract", "--json", &wasm]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"kind\": \"import\""), "{}", stdout);
+    assert!(stdout.ends_with("}\n"), "{}", stdout);
+
+    let output = wasm_webidl_bindings(&["dump", &wasm]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with(
+            "0000000: 01                      ; version: 1\n\
+             0000001: 80 80 04                ; features: 0x10000 (names)\n\
+             0000004: 00                      ; Web IDL type subsection (id 0, 6 bytes)\n"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(";         param: -5 long\n"), "{}", stdout);
+
+    let stripped = dir.join("stripped.wasm");
+    let stripped = stripped.to_str().unwrap();
+    let output = wasm_webidl_bindings(&["strip", &wasm, "-o", stripped]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = wasm_webidl_bindings(&["validate", stripped]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("has no `webidl-bindings` section"));
+}
+
+#[test]
+fn embed_reports_errors() {
+    let dir = dir("embed_reports_errors");
+    let wasm = write_wasm(&dir);
+    let bindings = dir.join("bindings.webidl");
+    fs::write(&bindings, "bind (import \"env\" \"nope\") 0\n").unwrap();
+
+    let output = wasm_webidl_bindings(&["embed", &wasm, bindings.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown Wasm import"), "{}", stderr);
+    assert!(stderr.contains("--> 1:6"), "{}", stderr);
+}
+
+#[test]
+fn dump_malformed_section() {
+    let dir = dir("dump_malformed_section");
+    let mut module = walrus::Module::default();
+    module.customs.add(walrus::RawCustomSection {
+        name: "webidl-bindings".to_string(),
+        data: vec![1, 0, 0, 1, 3, 2, 0x6f],
+    });
+    let wasm = dir.join("module.wasm");
+    fs::write(&wasm, module.emit_wasm().unwrap()).unwrap();
+
+    let output = wasm_webidl_bindings(&["dump", wasm.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with(";         member: -17 USVString\n"),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: unexpected end of section at offset 7\n"
+    );
+}
+
+#[test]
+fn fmt() {
+    let dir = dir("fmt");
+    let bindings = dir.join("bindings.webidl");
+    let bindings = bindings.to_str().unwrap();
+    fs::write(
+        bindings,
+        "type $Log (func (param long))\nbind (import \"env\" \"log\") $log\n",
+    )
+    .unwrap();
+
+    let output = wasm_webidl_bindings(&["fmt", "--check", bindings]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("is not formatted"));
+
+    let output = wasm_webidl_bindings(&["fmt", bindings]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::read_to_string(bindings).unwrap(),
+        "type $Log\n    (func (param long))\n\n\
+         bind\n    (import \"env\" \"log\")\n    $log\n"
+    );
+    let output = wasm_webidl_bindings(&["fmt", "--check", bindings]);
+    assert!(output.status.success(), "{:?}", output);
+
+    fs::write(bindings, "type $Log (func (param long)\n").unwrap();
+    let output = wasm_webidl_bindings(&["fmt", bindings]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--> 1:"), "{}", stderr);
+}
+
+#[test]
+fn invalid_arguments() {
+    let output = wasm_webidl_bindings(&["frobnicate"]);
+    assert_eq!(output.status.code(), Some(2));
+}
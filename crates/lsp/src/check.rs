@@ -0,0 +1,354 @@
+//! Checking that the Web IDL type and binding references in a binding text
+//! file resolve, without needing the Wasm module that it will be attached to.
+
+use std::collections::HashSet;
+use wasm_webidl_bindings_text_parser::{parse_with_actions_recovering, Actions, Error};
+
+/// Parse and check the given input, returning every error that was found.
+///
+/// Web IDL type and binding references must refer to a type or binding that is
+/// defined before them, just like when building an AST. References to Wasm
+/// functions and function types are not checked, since the Wasm module isn't
+/// available.
+pub fn check(input: &str) -> Vec<Error> {
+    let mut actions = CheckActions::default();
+    let ((), errors) = parse_with_actions_recovering(&mut actions, input);
+    errors
+}
+
+#[derive(Default)]
+struct CheckActions {
+    type_names: HashSet<String>,
+    alias_names: HashSet<String>,
+    type_count: u32,
+    binding_names: HashSet<String>,
+    binding_count: u32,
+}
+
+impl CheckActions {
+    fn binding(&mut self, name: Option<&str>) {
+        if let Some(name) = name {
+            self.binding_names.insert(name.to_string());
+        }
+        self.binding_count += 1;
+    }
+}
+
+impl Actions for CheckActions {
+    type WebidlBindingsSection = ();
+    fn webidl_bindings_section(&mut self, _types: (), _bindings: ()) {}
+
+    type WebidlTypeSubsection = ();
+    fn webidl_type_subsection(&mut self, _types: Vec<()>) {}
+
+    type WebidlType = ();
+    fn webidl_type(&mut self, name: Option<&str>, _ty: ()) -> Result<(), String> {
+        self.type_count += 1;
+        if let Some(name) = name {
+            if self.alias_names.contains(name) {
+                return Err(format!("Web IDL type name already defined: '{}'", name));
+            }
+            self.type_names.insert(name.to_string());
+        }
+        Ok(())
+    }
+    fn webidl_type_alias(&mut self, name: &str, _ty: ()) -> Result<(), String> {
+        if !self.type_names.insert(name.to_string()) {
+            return Err(format!("Web IDL type name already defined: '{}'", name));
+        }
+        self.alias_names.insert(name.to_string());
+        Ok(())
+    }
+
+    type WebidlCompoundType = ();
+
+    type WebidlFunction = ();
+    fn webidl_function(&mut self, _kind: Option<()>, _params: Option<()>, _result: Option<()>) {}
+
+    type WebidlFunctionKind = ();
+
+    type WebidlFunctionKindMethod = ();
+    fn webidl_function_kind_method(&mut self, _ty: ()) {}
+
+    type WebidlFunctionKindConstructor = ();
+    fn webidl_function_kind_constructor_default_new_target(&mut self) {}
+
+    type WebidlFunctionParams = ();
+    fn webidl_function_params(&mut self, _tys: Vec<()>) {}
+
+    type WebidlFunctionResult = ();
+    fn webidl_function_result(&mut self, _ty: ()) {}
+
+    type WebidlDictionary = ();
+    fn webidl_dictionary(&mut self, _fields: Vec<()>) {}
+
+    type WebidlDictionaryField = ();
+    fn webidl_dictionary_field(&mut self, _name: (), _ty: ()) {}
+
+    type WebidlDictionaryFieldName = ();
+    fn webidl_dictionary_field_name(&mut self, _name: &str) {}
+
+    type WebidlEnumeration = ();
+    fn webidl_enumeration(&mut self, _values: Vec<()>) {}
+
+    type WebidlEnumerationValue = ();
+    fn webidl_enumeration_value(&mut self, _value: &str) {}
+
+    type WebidlUnion = ();
+    fn webidl_union(&mut self, _members: Vec<()>) {}
+
+    type WebidlFunctionBindingsSubsection = ();
+    fn webidl_function_bindings_subsection(&mut self, _bindings: Vec<()>, _binds: Vec<()>) {}
+
+    type FunctionBinding = ();
+
+    type ImportBinding = ();
+    fn import_binding(
+        &mut self,
+        name: Option<&str>,
+        _wasm_ty: (),
+        _webidl_ty: (),
+        _params: (),
+        _result: (),
+    ) {
+        self.binding(name);
+    }
+
+    type ExportBinding = ();
+    fn export_binding(
+        &mut self,
+        name: Option<&str>,
+        _wasm_ty: (),
+        _webidl_ty: (),
+        _params: (),
+        _result: (),
+    ) {
+        self.binding(name);
+    }
+
+    type Bind = ();
+    fn bind(&mut self, _func: (), _binding: ()) {}
+
+    type OutgoingBindingMap = ();
+    fn outgoing_binding_map(&mut self, _bindings: Vec<()>) {}
+
+    type IncomingBindingMap = ();
+    fn incoming_binding_map(&mut self, _bindings: Vec<()>) {}
+
+    type OutgoingBindingExpression = ();
+
+    type OutgoingBindingExpressionAs = ();
+    fn outgoing_binding_expression_as(&mut self, _ty: (), _idx: u32) {}
+
+    type OutgoingBindingExpressionUtf8Str = ();
+    fn outgoing_binding_expression_utf8_str(&mut self, _ty: (), _offset: u32, _length: u32) {}
+
+    type OutgoingBindingExpressionUtf8CStr = ();
+    fn outgoing_binding_expression_utf8_c_str(&mut self, _ty: (), _offset: u32) {}
+
+    type OutgoingBindingExpressionI32ToEnum = ();
+    fn outgoing_binding_expression_i32_to_enum(&mut self, _ty: (), _idx: u32) {}
+
+    type OutgoingBindingExpressionView = ();
+    fn outgoing_binding_expression_view(&mut self, _ty: (), _offset: u32, _length: u32) {}
+
+    type OutgoingBindingExpressionCopy = ();
+    fn outgoing_binding_expression_copy(&mut self, _ty: (), _offset: u32, _length: u32) {}
+
+    type OutgoingBindingExpressionDict = ();
+    fn outgoing_binding_expression_dict(&mut self, _ty: (), _fields: Vec<()>) {}
+
+    type OutgoingBindingExpressionBindExport = ();
+    fn outgoing_binding_expression_bind_export(&mut self, _ty: (), _binding: (), _idx: u32) {}
+
+    type OutgoingBindingExpressionCustom = ();
+    fn outgoing_binding_expression_custom(&mut self, _opcode: u8, _payload: Vec<u8>) {}
+
+    type IncomingBindingExpression = ();
+
+    type IncomingBindingExpressionGet = ();
+    fn incoming_binding_expression_get(&mut self, _idx: u32) {}
+
+    type IncomingBindingExpressionAs = ();
+    fn incoming_binding_expression_as(&mut self, _ty: (), _expr: ()) {}
+
+    type IncomingBindingExpressionAllocUtf8Str = ();
+    fn incoming_binding_expression_alloc_utf8_str(&mut self, _alloc_func_name: &str, _expr: ()) {}
+
+    type IncomingBindingExpressionAllocCopy = ();
+    fn incoming_binding_expression_alloc_copy(&mut self, _alloc_func_name: &str, _expr: ()) {}
+
+    type IncomingBindingExpressionEnumToI32 = ();
+    fn incoming_binding_expression_enum_to_i32(&mut self, _ty: (), _expr: ()) {}
+
+    type IncomingBindingExpressionField = ();
+    fn incoming_binding_expression_field(&mut self, _idx: u32, _expr: ()) {}
+
+    type IncomingBindingExpressionBindImport = ();
+    fn incoming_binding_expression_bind_import(&mut self, _ty: (), _binding: (), _expr: ()) {}
+
+    type IncomingBindingExpressionCustom = ();
+    fn incoming_binding_expression_custom(&mut self, _opcode: u8, _payload: Vec<u8>) {}
+
+    type WebidlTypeRef = ();
+
+    type WebidlTypeRefNamed = ();
+    fn webidl_type_ref_named(&mut self, name: &str) -> Option<()> {
+        if self.type_names.contains(name) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    type WebidlTypeRefIndexed = ();
+    fn webidl_type_ref_indexed(&mut self, idx: u32) -> Option<()> {
+        if idx < self.type_count {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    type WebidlScalarType = ();
+    fn webidl_scalar_type_any(&mut self) {}
+    fn webidl_scalar_type_boolean(&mut self) {}
+    fn webidl_scalar_type_byte(&mut self) {}
+    fn webidl_scalar_type_octet(&mut self) {}
+    fn webidl_scalar_type_long(&mut self) {}
+    fn webidl_scalar_type_unsigned_long(&mut self) {}
+    fn webidl_scalar_type_short(&mut self) {}
+    fn webidl_scalar_type_unsigned_short(&mut self) {}
+    fn webidl_scalar_type_long_long(&mut self) {}
+    fn webidl_scalar_type_unsigned_long_long(&mut self) {}
+    fn webidl_scalar_type_float(&mut self) {}
+    fn webidl_scalar_type_unrestricted_float(&mut self) {}
+    fn webidl_scalar_type_double(&mut self) {}
+    fn webidl_scalar_type_unrestricted_double(&mut self) {}
+    fn webidl_scalar_type_dom_string(&mut self) {}
+    fn webidl_scalar_type_byte_string(&mut self) {}
+    fn webidl_scalar_type_usv_string(&mut self) {}
+    fn webidl_scalar_type_object(&mut self) {}
+    fn webidl_scalar_type_symbol(&mut self) {}
+    fn webidl_scalar_type_array_buffer(&mut self) {}
+    fn webidl_scalar_type_data_view(&mut self) {}
+    fn webidl_scalar_type_int8_array(&mut self) {}
+    fn webidl_scalar_type_int16_array(&mut self) {}
+    fn webidl_scalar_type_int32_array(&mut self) {}
+    fn webidl_scalar_type_uint8_array(&mut self) {}
+    fn webidl_scalar_type_uint16_array(&mut self) {}
+    fn webidl_scalar_type_uint32_array(&mut self) {}
+    fn webidl_scalar_type_uint8_clamped_array(&mut self) {}
+    fn webidl_scalar_type_float32_array(&mut self) {}
+    fn webidl_scalar_type_float64_array(&mut self) {}
+
+    type WasmValType = ();
+    fn wasm_val_type_i32(&mut self) {}
+    fn wasm_val_type_i64(&mut self) {}
+    fn wasm_val_type_f32(&mut self) {}
+    fn wasm_val_type_f64(&mut self) {}
+    fn wasm_val_type_v128(&mut self) {}
+    fn wasm_val_type_anyref(&mut self) {}
+
+    type WasmFuncTypeRef = ();
+
+    type WasmFuncTypeRefNamed = ();
+    fn wasm_func_type_ref_named(&mut self, _name: &str) -> Option<()> {
+        Some(())
+    }
+
+    type WasmFuncTypeRefIndexed = ();
+    fn wasm_func_type_ref_indexed(&mut self, _idx: u32) -> Option<()> {
+        Some(())
+    }
+
+    type WasmFuncRef = ();
+
+    type WasmFuncRefNamed = ();
+    fn wasm_func_ref_named(&mut self, _name: &str) -> Option<()> {
+        Some(())
+    }
+
+    type WasmFuncRefIndexed = ();
+    fn wasm_func_ref_indexed(&mut self, _idx: u32) -> Option<()> {
+        Some(())
+    }
+
+    type WasmFuncRefImport = ();
+    fn wasm_func_ref_import(&mut self, _module: &str, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    type WasmFuncRefExport = ();
+    fn wasm_func_ref_export(&mut self, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    type BindingRef = ();
+
+    type BindingRefNamed = ();
+    fn binding_ref_named(&mut self, name: &str) -> Option<()> {
+        if self.binding_names.contains(name) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    type BindingRefIndexed = ();
+    fn binding_ref_indexed(&mut self, idx: u32) -> Option<()> {
+        if idx < self.binding_count {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_ok() {
+        let errors = check(
+            r#"
+            type $Contact (dict (field "name" DOMString))
+            type $Person = $Contact
+            type (func (param $Person 0))
+            func-binding $b import $wasmTy 1 (param (as $Contact 0)) (result)
+            bind $f $b
+            bind $f 0
+            bind (import "env" "f") $b
+            bind (export "g") 0
+            "#,
+        );
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn check_unknown_references() {
+        let input = r#"
+            type $A (dict (field "a" $B))
+            type $B (union 5)
+            func-binding import 0 0 (param) (result)
+            bind $f $nope
+            bind $f 1
+        "#;
+        let errors = check(input);
+        let found: Vec<_> = errors.iter().map(|e| &input[e.start..e.end]).collect();
+        assert_eq!(found, ["$B", "5", "0", "$nope", "1"]);
+    }
+
+    #[test]
+    fn check_alias_name_collisions() {
+        for input in &[
+            "type $A (dict) type $A = long",
+            "type $A = long type $A (dict)",
+        ] {
+            let errors = check(input);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Web IDL type name already defined: '$A'");
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! An index of the definitions and references in a binding text file.
+//!
+//! The index is built from the lossless concrete syntax tree rather than by
+//! parsing with `Actions`, so that it has the source positions of everything,
+//! and so that it is still available while the file has syntax errors.
+
+use wasm_webidl_bindings_text_parser::cst::{self, List, Node, Token, TokenKind};
+
+/// The scalar Web IDL types.
+pub const SCALAR_TYPES: &[&str] = &[
+    "any",
+    "boolean",
+    "byte",
+    "octet",
+    "long",
+    "unsigned long",
+    "short",
+    "unsigned short",
+    "long long",
+    "unsigned long long",
+    "float",
+    "unrestricted float",
+    "double",
+    "unrestricted double",
+    "DOMString",
+    "ByteString",
+    "USVString",
+    "object",
+    "symbol",
+    "ArrayBuffer",
+    "DataView",
+    "Int8Array",
+    "Int16Array",
+    "Int32Array",
+    "Uint8Array",
+    "Uint16Array",
+    "Uint32Array",
+    "Uint8ClampedArray",
+    "Float32Array",
+    "Float64Array",
+];
+
+/// The keywords that can come right after a `(`.
+pub const EXPRESSION_HEADS: &[&str] = &[
+    // Web IDL types.
+    "func",
+    "method",
+    "constructor",
+    "param",
+    "result",
+    "dict",
+    "field",
+    "enum",
+    "union",
+    // Outgoing binding expressions.
+    "as",
+    "utf8-str",
+    "utf8-cstr",
+    "i32-to-enum",
+    "view",
+    "copy",
+    "bind-export",
+    // Incoming binding expressions.
+    "get",
+    "alloc-utf8-str",
+    "alloc-copy",
+    "enum-to-i32",
+    "bind-import",
+    // Custom binding expressions, in either direction.
+    "custom",
+    // Wasm function references.
+    "import",
+    "export",
+];
+
+const WASM_VAL_TYPES: &[&str] = &["i32", "i64", "f32", "f64", "v128", "anyref"];
+
+/// What kind of thing a definition defines or a reference refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A Web IDL type, defined by a `type` item.
+    Type,
+    /// A function binding, defined by a `func-binding` item.
+    Binding,
+}
+
+/// A `type` or `func-binding` item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Definition {
+    pub kind: SymbolKind,
+    pub name: Option<String>,
+    /// The byte range of the definition's name, or of its keyword if it
+    /// doesn't have a name.
+    pub name_span: (usize, usize),
+    /// The byte range of the whole item.
+    pub item_span: (usize, usize),
+    /// If this is a `type $A = $B` alias, then the reference to `$B`.
+    pub alias_of: Option<Reference>,
+    /// If this is a `type $A = ...` alias, then the byte range of the aliased
+    /// type, which may be a scalar type rather than a reference.
+    pub aliased: Option<(usize, usize)>,
+}
+
+/// A reference to a Web IDL type or function binding, by name or by index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+    pub kind: SymbolKind,
+    pub text: String,
+    pub span: (usize, usize),
+}
+
+/// The definitions and references in a binding text file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Index {
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+}
+
+impl Index {
+    /// Build the index for the given input.
+    pub fn new(input: &str) -> Index {
+        let tree = cst::parse(input);
+        let mut index = Index::default();
+
+        let mut items: Vec<Vec<&Node>> = vec![];
+        for node in significant(&tree.nodes) {
+            match node {
+                Node::Token(t) if is_item_keyword(t.text) => items.push(vec![node]),
+                _ => {
+                    if let Some(item) = items.last_mut() {
+                        item.push(node);
+                    }
+                }
+            }
+        }
+
+        for item in items {
+            index.item(&item);
+        }
+        index
+    }
+
+    fn item(&mut self, item: &[&Node]) {
+        let keyword = match item[0] {
+            Node::Token(t) => t,
+            Node::List(_) => return,
+        };
+        let item_span = (item[0].start(), item[item.len() - 1].end());
+        let name = match item.get(1) {
+            Some(Node::Token(t))
+                if t.kind == TokenKind::Atom && t.text != "import" && t.text != "export" =>
+            {
+                Some(t)
+            }
+            _ => None,
+        };
+        let definition = |kind, alias_of, aliased| Definition {
+            kind,
+            name: name.map(|t| t.text.to_string()),
+            name_span: name.map_or((keyword.start, keyword.end()), |t| (t.start, t.end())),
+            item_span,
+            alias_of,
+            aliased,
+        };
+
+        match keyword.text {
+            "type" => {
+                let (mut alias_of, mut aliased) = (None, None);
+                if let (Some(Node::Token(eq)), Some(target)) = (item.get(2), item.get(3)) {
+                    if eq.text == "=" {
+                        alias_of = reference(SymbolKind::Type, target);
+                        aliased = Some((target.start(), item_span.1));
+                    }
+                }
+                self.references.extend(alias_of.clone());
+                self.definitions
+                    .push(definition(SymbolKind::Type, alias_of, aliased));
+                for node in item {
+                    if let Node::List(l) = node {
+                        self.type_references(l);
+                    }
+                }
+            }
+            "func-binding" => {
+                self.definitions
+                    .push(definition(SymbolKind::Binding, None, None));
+                // Skip the `import` or `export` keyword and the Wasm function
+                // type to get to the Web IDL function type.
+                let rest = if name.is_some() {
+                    &item[2..]
+                } else {
+                    &item[1..]
+                };
+                if let Some(ty) = rest.get(2) {
+                    self.references.extend(reference(SymbolKind::Type, ty));
+                }
+                for node in item {
+                    if let Node::List(l) = node {
+                        self.expression_references(l);
+                    }
+                }
+            }
+            "bind" => {
+                if let Some(binding) = item.get(2) {
+                    self.references
+                        .extend(reference(SymbolKind::Binding, binding));
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Find the references in a Web IDL type.
+    fn type_references(&mut self, list: &List) {
+        let children = significant(&list.children);
+        let head = head(&children);
+        for (i, child) in children.iter().enumerate().skip(1) {
+            match child {
+                Node::List(l) => self.type_references(l),
+                Node::Token(_) => {
+                    let is_type = match head {
+                        "method" | "param" | "result" | "union" => true,
+                        "field" => i == 2,
+                        _ => false,
+                    };
+                    if is_type {
+                        self.references.extend(reference(SymbolKind::Type, child));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Find the references in a binding expression.
+    fn expression_references(&mut self, list: &List) {
+        let children = significant(&list.children);
+        let head = head(&children);
+        for (i, child) in children.iter().enumerate().skip(1) {
+            match child {
+                Node::List(l) => self.expression_references(l),
+                Node::Token(_) => {
+                    let kind = match (head, i) {
+                        ("as", 1)
+                        | ("utf8-str", 1)
+                        | ("utf8-cstr", 1)
+                        | ("i32-to-enum", 1)
+                        | ("view", 1)
+                        | ("copy", 1)
+                        | ("dict", 1)
+                        | ("bind-export", 1)
+                        | ("enum-to-i32", 1) => SymbolKind::Type,
+                        ("bind-export", 2) | ("bind-import", 2) => SymbolKind::Binding,
+                        _ => continue,
+                    };
+                    self.references.extend(reference(kind, child));
+                }
+            }
+        }
+    }
+
+    /// Get the definition with the given name or index.
+    pub fn resolve(&self, kind: SymbolKind, text: &str) -> Option<&Definition> {
+        let mut definitions = self.definitions.iter().filter(|d| d.kind == kind);
+        match text.parse::<usize>() {
+            // Aliases don't get an index of their own.
+            Ok(idx) => definitions.filter(|d| d.alias_of.is_none()).nth(idx),
+            Err(_) => definitions.find(|d| d.name.as_deref() == Some(text)),
+        }
+    }
+
+    /// Get the definition that is referenced or defined at the given byte
+    /// offset, along with the byte range of the reference or name there.
+    pub fn definition_at(&self, offset: usize) -> Option<(&Definition, (usize, usize))> {
+        let contains = |(start, end): (usize, usize)| start <= offset && offset <= end;
+        if let Some(r) = self.references.iter().find(|r| contains(r.span)) {
+            return self.resolve(r.kind, &r.text).map(|d| (d, r.span));
+        }
+        self.definitions
+            .iter()
+            .find(|d| d.name.is_some() && contains(d.name_span))
+            .map(|d| (d, d.name_span))
+    }
+
+    /// Follow a chain of aliases to the type that it ends at.
+    pub fn resolve_aliases<'a>(&'a self, mut definition: &'a Definition) -> Vec<&'a Definition> {
+        let mut chain = vec![definition];
+        while let Some(target) = &definition.alias_of {
+            definition = match self.resolve(target.kind, &target.text) {
+                Some(d) if !chain.contains(&d) => d,
+                _ => break,
+            };
+            chain.push(definition);
+        }
+        chain
+    }
+
+    /// Get the byte range of the type that the given definition resolves to.
+    ///
+    /// This is the whole item of the compound type at the end of its chain of
+    /// aliases, or the aliased type itself if the chain ends at an alias of a
+    /// scalar type or of a type that doesn't exist.
+    pub fn resolve_type(&self, definition: &Definition) -> (usize, usize) {
+        let last = self.resolve_aliases(definition).pop().unwrap();
+        last.aliased.unwrap_or(last.item_span)
+    }
+}
+
+fn significant<'a, 'b>(nodes: &'b [Node<'a>]) -> Vec<&'b Node<'a>> {
+    nodes
+        .iter()
+        .filter(|n| match n {
+            Node::Token(t) => !t.kind.is_trivia(),
+            Node::List(_) => true,
+        })
+        .collect()
+}
+
+fn head<'a>(children: &[&Node<'a>]) -> &'a str {
+    match children.first() {
+        Some(Node::Token(t)) => t.text,
+        _ => "",
+    }
+}
+
+fn is_item_keyword(word: &str) -> bool {
+    matches!(word, "type" | "func-binding" | "bind")
+}
+
+/// Is this word part of the name of a scalar Web IDL type?
+fn is_scalar_type_word(word: &str) -> bool {
+    SCALAR_TYPES
+        .iter()
+        .any(|ty| ty.split(' ').any(|w| w == word))
+}
+
+/// Is the given character allowed in an atom?
+pub fn is_atom_char(c: char) -> bool {
+    !c.is_whitespace() && c != '(' && c != ')' && c != '"' && c != ';'
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '$' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '$' || c == '_')
+}
+
+/// Get the reference at the given node, if it is a name or index rather than
+/// a keyword or an expression.
+fn reference(kind: SymbolKind, node: &Node) -> Option<Reference> {
+    let token: &Token = match node {
+        Node::Token(t) if t.kind == TokenKind::Atom => t,
+        _ => return None,
+    };
+    let is_index = token.text.bytes().all(|b| b.is_ascii_digit());
+    let is_name = is_identifier(token.text)
+        && !is_scalar_type_word(token.text)
+        && !WASM_VAL_TYPES.contains(&token.text);
+    if !is_index && !is_name {
+        return None;
+    }
+    Some(Reference {
+        kind,
+        text: token.text.to_string(),
+        span: (token.start, token.end()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"
+type $Contact (dict (field "name" DOMString) (field "age" unsigned long))
+type $Person = $Contact
+type (func (param $Person 0) (result long))
+func-binding $b import $wasmTy 2 ;; not $Contact
+    (param (as $Contact 0) (as 0 1) (bind-export any $b 2))
+    (result (as i32 (get 0)))
+bind $f $b
+bind 0 0
+"#;
+
+    fn references(index: &Index) -> Vec<(SymbolKind, &str)> {
+        index
+            .references
+            .iter()
+            .map(|r| {
+                assert_eq!(&INPUT[r.span.0..r.span.1], r.text);
+                (r.kind, r.text.as_str())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn index_definitions() {
+        let index = Index::new(INPUT);
+        let names: Vec<_> = index
+            .definitions
+            .iter()
+            .map(|d| (d.kind, &INPUT[d.name_span.0..d.name_span.1]))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (SymbolKind::Type, "$Contact"),
+                (SymbolKind::Type, "$Person"),
+                (SymbolKind::Type, "type"),
+                (SymbolKind::Binding, "$b"),
+            ]
+        );
+        let b = &index.definitions[3];
+        assert!(INPUT[b.item_span.0..b.item_span.1].starts_with("func-binding $b"));
+        assert!(INPUT[b.item_span.0..b.item_span.1].ends_with("(get 0)))"));
+    }
+
+    #[test]
+    fn index_references() {
+        let index = Index::new(INPUT);
+        assert_eq!(
+            references(&index),
+            [
+                (SymbolKind::Type, "$Contact"),
+                (SymbolKind::Type, "$Person"),
+                (SymbolKind::Type, "0"),
+                (SymbolKind::Type, "2"),
+                (SymbolKind::Type, "$Contact"),
+                (SymbolKind::Type, "0"),
+                (SymbolKind::Binding, "$b"),
+                (SymbolKind::Binding, "$b"),
+                (SymbolKind::Binding, "0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn index_resolve() {
+        let index = Index::new(INPUT);
+        let name = |d: Option<&Definition>| d.and_then(|d| d.name.clone());
+        assert_eq!(
+            name(index.resolve(SymbolKind::Type, "0")),
+            Some("$Contact".into())
+        );
+        assert_eq!(name(index.resolve(SymbolKind::Type, "1")), None);
+        assert!(index.resolve(SymbolKind::Type, "1").is_some());
+        assert!(index.resolve(SymbolKind::Type, "2").is_none());
+        assert!(index.resolve(SymbolKind::Type, "$b").is_none());
+        assert_eq!(
+            name(index.resolve(SymbolKind::Binding, "$b")),
+            Some("$b".into())
+        );
+
+        let person = index.resolve(SymbolKind::Type, "$Person").unwrap();
+        let chain: Vec<_> = index
+            .resolve_aliases(person)
+            .into_iter()
+            .map(|d| name(Some(d)))
+            .collect();
+        assert_eq!(chain, [Some("$Person".into()), Some("$Contact".into())]);
+    }
+
+    #[test]
+    fn index_resolve_type() {
+        let input = r#"
+type $Contact (dict (field "name" DOMString))
+type $Person = $Contact
+type $Human = $Person
+type $Count = unsigned long
+type $Other = $Count
+type $Nope = $Missing
+"#;
+        let index = Index::new(input);
+        let resolved = |name: &str| {
+            let definition = index.resolve(SymbolKind::Type, name).unwrap();
+            let (start, end) = index.resolve_type(definition);
+            &input[start..end]
+        };
+        let contact = r#"type $Contact (dict (field "name" DOMString))"#;
+        assert_eq!(resolved("$Contact"), contact);
+        assert_eq!(resolved("$Person"), contact);
+        assert_eq!(resolved("$Human"), contact);
+        assert_eq!(resolved("$Count"), "unsigned long");
+        assert_eq!(resolved("$Other"), "unsigned long");
+        assert_eq!(resolved("$Nope"), "$Missing");
+    }
+
+    #[test]
+    fn index_definition_at() {
+        let index = Index::new(INPUT);
+        let at = |needle: &str, nth: usize| {
+            let offset = INPUT.match_indices(needle).nth(nth).unwrap().0 + 1;
+            index
+                .definition_at(offset)
+                .map(|(d, _)| &INPUT[d.name_span.0..d.name_span.1])
+        };
+        assert_eq!(at("$Contact", 0), Some("$Contact"));
+        assert_eq!(at("$Contact", 1), Some("$Contact"));
+        assert_eq!(at("$Person", 1), Some("$Person"));
+        assert_eq!(at("$b", 2), Some("$b"));
+        assert_eq!(at("$wasmTy", 0), None);
+        assert_eq!(at("DOMString", 0), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! A Language Server Protocol server for the straw proposal text format,
+//! which talks to its client over stdio.
+//!
+//! It provides:
+//!
+//! * Diagnostics for syntax errors and for references to Web IDL types and
+//!   function bindings that don't exist.
+//!
+//! * Go to definition from a reference to a Web IDL type or function binding
+//!   to the `type` or `func-binding` that defines it.
+//!
+//! * Hover over a Web IDL type reference to see the type it resolves to.
+//!
+//! * Completion for scalar Web IDL types, expression keywords, and the names of
+//!   defined types and bindings.
+
+mod check;
+mod index;
+mod position;
+
+use crate::index::{Index, SymbolKind};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::error::Error;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["(".to_string(), "$".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    Server::default().run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    /// The text of every open document.
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn run(&mut self, connection: Connection) -> Result<()> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.request(request);
+                    connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => {
+                    for diagnostics in self.notification(notification)? {
+                        let notification =
+                            Notification::new(PublishDiagnostics::METHOD.to_string(), diagnostics);
+                        connection.sender.send(notification.into())?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Response {
+        fn respond<T: serde::Serialize>(id: RequestId, result: Result<T>) -> Response {
+            match result {
+                Ok(result) => Response::new_ok(id, result),
+                Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+            }
+        }
+
+        let id = request.id.clone();
+        match request.method.as_str() {
+            GotoDefinition::METHOD => respond(id, self.goto_definition(request.params)),
+            HoverRequest::METHOD => respond(id, self.hover(request.params)),
+            Completion::METHOD => respond(id, self.completion(request.params)),
+            method => Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {}", method),
+            ),
+        }
+    }
+
+    /// Handle a notification, returning the diagnostics to publish because of
+    /// it.
+    fn notification(
+        &mut self,
+        notification: Notification,
+    ) -> Result<Vec<PublishDiagnosticsParams>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let doc = params.text_document;
+                let diagnostics = diagnostics(&doc.text);
+                self.documents.insert(doc.uri.clone(), doc.text);
+                Ok(vec![PublishDiagnosticsParams::new(
+                    doc.uri,
+                    diagnostics,
+                    Some(doc.version),
+                )])
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // We only ask for full text syncing, so the last change has
+                // the whole new text.
+                let text = match params.content_changes.into_iter().last() {
+                    Some(change) => change.text,
+                    None => return Ok(vec![]),
+                };
+                let doc = params.text_document;
+                let diagnostics = diagnostics(&text);
+                self.documents.insert(doc.uri.clone(), text);
+                Ok(vec![PublishDiagnosticsParams::new(
+                    doc.uri,
+                    diagnostics,
+                    Some(doc.version),
+                )])
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                Ok(vec![PublishDiagnosticsParams::new(uri, vec![], None)])
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Get the text of the document at the given position, and the byte offset
+    /// of the position.
+    fn document(&self, params: &TextDocumentPositionParams) -> Result<(&str, usize)> {
+        let text = self
+            .documents
+            .get(&params.text_document.uri)
+            .ok_or_else(|| format!("unknown document: {}", params.text_document.uri))?;
+        Ok((text, position::offset(text, params.position)))
+    }
+
+    fn goto_definition(&self, params: serde_json::Value) -> Result<Option<GotoDefinitionResponse>> {
+        let params: GotoDefinitionParams = serde_json::from_value(params)?;
+        let params = params.text_document_position_params;
+        let (text, offset) = self.document(&params)?;
+
+        let index = Index::new(text);
+        Ok(index.definition_at(offset).map(|(definition, _)| {
+            GotoDefinitionResponse::Scalar(Location {
+                uri: params.text_document.uri.clone(),
+                range: position::range(text, definition.name_span),
+            })
+        }))
+    }
+
+    fn hover(&self, params: serde_json::Value) -> Result<Option<Hover>> {
+        let params: HoverParams = serde_json::from_value(params)?;
+        let (text, offset) = self.document(&params.text_document_position_params)?;
+
+        let index = Index::new(text);
+        let (definition, span) = match index.definition_at(offset) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        // Show the type that the definition resolves to, rather than the
+        // aliases along the way.
+        let (start, end) = index.resolve_type(definition);
+        let ty = &text[start..end];
+        let ty =
+            wasm_webidl_bindings_text_parser::format(ty).unwrap_or_else(|_| format!("{}\n", ty));
+        let value = format!("```\n{}```\n", ty);
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(position::range(text, span)),
+        }))
+    }
+
+    fn completion(&self, params: serde_json::Value) -> Result<Option<CompletionResponse>> {
+        let params: CompletionParams = serde_json::from_value(params)?;
+        let (text, offset) = self.document(&params.text_document_position)?;
+
+        // Find the start of the word being completed, and check whether it
+        // comes right after a `(`.
+        let before = &text[..offset];
+        let word_start = before.trim_end_matches(index::is_atom_char).len();
+        let after_paren = before[..word_start].ends_with('(');
+
+        let keyword = |label: &str| CompletionItem {
+            label: label.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            ..CompletionItem::default()
+        };
+
+        let items = if after_paren {
+            index::EXPRESSION_HEADS.iter().map(|k| keyword(k)).collect()
+        } else {
+            let index = Index::new(text);
+            let names = index.definitions.iter().filter_map(|d| {
+                let kind = match d.kind {
+                    SymbolKind::Type => CompletionItemKind::STRUCT,
+                    SymbolKind::Binding => CompletionItemKind::FUNCTION,
+                };
+                d.name.as_ref().map(|name| CompletionItem {
+                    label: name.clone(),
+                    kind: Some(kind),
+                    ..CompletionItem::default()
+                })
+            });
+            index::SCALAR_TYPES
+                .iter()
+                .map(|ty| keyword(ty))
+                .chain(names)
+                .collect()
+        };
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+}
+
+fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    check::check(text)
+        .into_iter()
+        .map(|e| Diagnostic {
+            range: position::range(text, (e.start, e.end)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("webidl-bindings".to_string()),
+            message: e.message,
+            ..Diagnostic::default()
+        })
+        .collect()
+}
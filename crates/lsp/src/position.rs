@@ -0,0 +1,78 @@
+//! Converting between byte offsets and LSP positions, which count lines and
+//! UTF-16 code units.
+
+use lsp_types::{Position, Range};
+
+/// Convert a byte offset in the text into a position.
+pub fn position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Convert a byte range in the text into a range.
+pub fn range(text: &str, (start, end): (usize, usize)) -> Range {
+    Range {
+        start: position(text, start),
+        end: position(text, end),
+    }
+}
+
+/// Convert a position into a byte offset in the text.
+///
+/// Positions past the end of a line are clamped to the end of that line, and
+/// positions past the end of the text are clamped to the end of the text.
+pub fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+
+    let mut utf16 = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if c == '\n' || utf16 >= position.character as usize {
+            return line_start + i;
+        }
+        utf16 += c.len_utf16();
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "ab\n😀c\n\nd";
+        let positions = [
+            (0, 0, 0),
+            (2, 0, 2),
+            (3, 1, 0),
+            (7, 1, 2),
+            (8, 1, 3),
+            (9, 2, 0),
+            (10, 3, 0),
+            (11, 3, 1),
+        ];
+        for &(offset, line, character) in positions.iter() {
+            let p = Position { line, character };
+            assert_eq!(position(text, offset), p);
+            assert_eq!(super::offset(text, p), offset);
+        }
+    }
+
+    #[test]
+    fn offset_clamps() {
+        let text = "ab\ncd";
+        assert_eq!(offset(text, Position::new(0, 10)), 2);
+        assert_eq!(offset(text, Position::new(5, 0)), 5);
+    }
+}
@@ -0,0 +1,205 @@
+//! Drive the language server over stdio with a scripted client.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_wasm-webidl-bindings-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+        };
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(result["capabilities"]["hoverProvider"], true);
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(n) = header.strip_prefix("Content-Length: ") {
+                length = Some(n.parse().unwrap());
+            }
+        }
+        let mut body = vec![0; length.unwrap()];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                assert_eq!(message.get("error"), None);
+                return message["result"].clone();
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn diagnostics(&mut self) -> Value {
+        loop {
+            let message = self.receive();
+            if message["method"] == "textDocument/publishDiagnostics" {
+                return message["params"]["diagnostics"].clone();
+            }
+        }
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+const URI: &str = "file:///bindings.webidl";
+
+fn position(line: u32, character: u32) -> Value {
+    json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": line, "character": character },
+    })
+}
+
+#[test]
+fn scripted_session() {
+    let mut client = Client::start();
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": URI,
+                "languageId": "webidl-bindings",
+                "version": 1,
+                "text": "type $Contact (dict (field \"name\" DOMString))\n\
+                         type $Person = $Contact\n\
+                         type $Broken (dict (field \"age\" $Nope))\n",
+            }
+        }),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "unknown Web IDL type name: '$Nope'"
+    );
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({
+            "start": { "line": 2, "character": 32 },
+            "end": { "line": 2, "character": 37 },
+        })
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{
+                "text": "type $Contact (dict (field \"name\" DOMString))\n\
+                         type $Person = $Contact\n\
+                         type $Human = $Person\n\
+                         type $Count = unsigned long\n\
+                         func-binding $b import 0 0 (param (as $Person 0)) (result)\n\
+                         bind 0 $b\n\
+                         type $T (func (param ))\n",
+            }],
+        }),
+    );
+    assert_eq!(client.diagnostics().as_array().unwrap().len(), 1);
+
+    let definition = client.request("textDocument/definition", position(4, 40));
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"],
+        json!({
+            "start": { "line": 1, "character": 5 },
+            "end": { "line": 1, "character": 12 },
+        })
+    );
+
+    let definition = client.request("textDocument/definition", position(5, 8));
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 4, "character": 13 })
+    );
+
+    // Hovering over an alias shows the type at the end of its chain of
+    // aliases.
+    let contact = "```\ntype $Contact\n    (dict (field \"name\" DOMString))\n```\n";
+    let hover = client.request("textDocument/hover", position(4, 40));
+    assert_eq!(hover["contents"]["value"], contact);
+    let hover = client.request("textDocument/hover", position(2, 7));
+    assert_eq!(hover["contents"]["value"], contact);
+    let hover = client.request("textDocument/hover", position(3, 7));
+    assert_eq!(hover["contents"]["value"], "```\nunsigned long\n```\n");
+
+    let hover = client.request("textDocument/hover", position(0, 0));
+    assert_eq!(hover, Value::Null);
+
+    let labels = |completion: Value| -> Vec<String> {
+        completion
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let heads = labels(client.request("textDocument/completion", position(6, 15)));
+    assert!(heads.contains(&"dict".to_string()));
+    assert!(heads.contains(&"bind-import".to_string()));
+    assert!(!heads.contains(&"DOMString".to_string()));
+
+    let types = labels(client.request("textDocument/completion", position(6, 21)));
+    assert!(types.contains(&"unsigned long long".to_string()));
+    assert!(types.contains(&"$Person".to_string()));
+    assert!(types.contains(&"$b".to_string()));
+    assert!(!types.contains(&"dict".to_string()));
+
+    client.notify(
+        "textDocument/didClose",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    assert_eq!(client.diagnostics(), json!([]));
+
+    client.shutdown();
+}
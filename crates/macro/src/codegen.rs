@@ -0,0 +1,716 @@
+//! Parse actions that generate the code to build an unresolved AST, rather than
+//! building it themselves.
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+use std::collections::{HashMap, HashSet};
+use wasm_webidl_bindings_text_parser::Actions;
+
+/// Generates a statement for each type and function binding, in order. Names
+/// of Web IDL types and function bindings are resolved while parsing, so that
+/// unknown names are compile errors, but references to Wasm functions and
+/// function types are left symbolic.
+#[derive(Debug, Default)]
+pub(crate) struct CodegenActions {
+    stmts: Vec<TokenStream>,
+    /// The number of compound types defined so far.
+    types: u32,
+    /// The expression for each named Web IDL type.
+    type_names: HashMap<String, TokenStream>,
+    /// The names of the Web IDL type aliases.
+    alias_names: HashSet<String>,
+    /// The number of function bindings defined so far.
+    bindings: u32,
+    binding_names: HashMap<String, u32>,
+}
+
+impl CodegenActions {
+    /// Get the expression that builds the whole section.
+    pub(crate) fn finish(self) -> TokenStream {
+        let stmts = self.stmts;
+        quote!({
+            #[allow(unused_imports)]
+            use ::wasm_webidl_bindings::{ast, unresolved};
+            #[allow(unused_mut)]
+            let mut __section = unresolved::WebidlBindings::default();
+            #[allow(unused_mut)]
+            let mut __types: ::std::vec::Vec<ast::WebidlTypeRef> = ::std::vec::Vec::new();
+            #(#stmts)*
+            __section
+        })
+    }
+
+    fn type_ref(&self, idx: u32) -> TokenStream {
+        let idx = Literal::usize_unsuffixed(idx as usize);
+        quote!(__types[#idx])
+    }
+
+    fn push_binding(&mut self, name: Option<&str>, binding: TokenStream) -> TokenStream {
+        let idx = self.bindings;
+        self.bindings += 1;
+        if let Some(name) = name {
+            self.binding_names.insert(name.to_string(), idx);
+        }
+        self.stmts.push(quote!(__section.bindings.push(#binding);));
+        Literal::u32_unsuffixed(idx).into_token_stream()
+    }
+}
+
+fn string(s: &str) -> TokenStream {
+    quote!(::std::string::String::from(#s))
+}
+
+fn name(name: Option<&str>) -> TokenStream {
+    match name {
+        Some(name) => {
+            let name = string(name);
+            quote!(::std::option::Option::Some(#name))
+        }
+        None => quote!(::std::option::Option::None),
+    }
+}
+
+fn u32(x: u32) -> Literal {
+    Literal::u32_unsuffixed(x)
+}
+
+fn scalar(ty: TokenStream) -> TokenStream {
+    quote!(ast::WebidlTypeRef::Scalar(ast::WebidlScalarType::#ty))
+}
+
+fn val_type(ty: TokenStream) -> TokenStream {
+    quote!(::wasm_webidl_bindings::walrus::ValType::#ty)
+}
+
+impl Actions for CodegenActions {
+    type WebidlBindingsSection = ();
+    fn webidl_bindings_section(&mut self, _types: (), _bindings: ()) {}
+
+    type WebidlTypeSubsection = ();
+    fn webidl_type_subsection(&mut self, _types: Vec<()>) {}
+
+    type WebidlType = ();
+    fn webidl_type(&mut self, name: Option<&str>, ty: TokenStream) -> Result<(), String> {
+        if let Some(name) = name {
+            if self.alias_names.contains(name) {
+                return Err(format!("Web IDL type name already defined: '{}'", name));
+            }
+        }
+        // The check above means that this can't fail.
+        let set_name = name.map(|name| {
+            let name = string(name);
+            quote!(__section.types.set_name(#name, __id).unwrap();)
+        });
+        self.stmts.push(quote!({
+            let __id = __section.types.insert(#ty);
+            #set_name
+            __types.push(ast::WebidlTypeRef::Id(__id.into()));
+        }));
+        if let Some(name) = name {
+            let ty = self.type_ref(self.types);
+            self.type_names.insert(name.to_string(), ty);
+        }
+        self.types += 1;
+        Ok(())
+    }
+
+    fn webidl_type_alias(&mut self, name: &str, ty: TokenStream) -> Result<(), String> {
+        if self.type_names.contains_key(name) {
+            return Err(format!("Web IDL type name already defined: '{}'", name));
+        }
+        // The check above means that this can't fail.
+        let alias = string(name);
+        self.stmts
+            .push(quote!(__section.types.alias(#alias, #ty).unwrap();));
+        self.type_names.insert(name.to_string(), ty);
+        self.alias_names.insert(name.to_string());
+        Ok(())
+    }
+
+    type WebidlCompoundType = TokenStream;
+
+    type WebidlFunction = TokenStream;
+    fn webidl_function(
+        &mut self,
+        kind: Option<TokenStream>,
+        params: Option<TokenStream>,
+        result: Option<TokenStream>,
+    ) -> TokenStream {
+        let kind = kind.unwrap_or_else(|| quote!(ast::WebidlFunctionKind::Static));
+        let params = params.unwrap_or_else(|| quote!(::std::vec::Vec::new()));
+        let result = match result {
+            Some(ty) => quote!(::std::option::Option::Some(#ty)),
+            None => quote!(::std::option::Option::None),
+        };
+        quote!(ast::WebidlFunction {
+            kind: #kind,
+            params: #params,
+            result: #result,
+        })
+    }
+
+    type WebidlFunctionKind = TokenStream;
+
+    type WebidlFunctionKindMethod = TokenStream;
+    fn webidl_function_kind_method(&mut self, ty: TokenStream) -> TokenStream {
+        quote!(ast::WebidlFunctionKind::Method(ast::WebidlFunctionKindMethod { ty: #ty }))
+    }
+
+    type WebidlFunctionKindConstructor = TokenStream;
+    fn webidl_function_kind_constructor_default_new_target(&mut self) -> TokenStream {
+        quote!(ast::WebidlFunctionKind::Constructor)
+    }
+
+    type WebidlFunctionParams = TokenStream;
+    fn webidl_function_params(&mut self, tys: Vec<TokenStream>) -> TokenStream {
+        quote!(::std::vec![#(#tys),*])
+    }
+
+    type WebidlFunctionResult = TokenStream;
+    fn webidl_function_result(&mut self, ty: TokenStream) -> TokenStream {
+        ty
+    }
+
+    type WebidlDictionary = TokenStream;
+    fn webidl_dictionary(&mut self, fields: Vec<TokenStream>) -> TokenStream {
+        quote!(ast::WebidlDictionary {
+            fields: ::std::vec![#(#fields),*],
+        })
+    }
+
+    type WebidlDictionaryField = TokenStream;
+    fn webidl_dictionary_field(&mut self, name: TokenStream, ty: TokenStream) -> TokenStream {
+        quote!(ast::WebidlDictionaryField {
+            name: #name,
+            ty: #ty,
+        })
+    }
+
+    type WebidlDictionaryFieldName = TokenStream;
+    fn webidl_dictionary_field_name(&mut self, name: &str) -> TokenStream {
+        string(name)
+    }
+
+    type WebidlEnumeration = TokenStream;
+    fn webidl_enumeration(&mut self, values: Vec<TokenStream>) -> TokenStream {
+        quote!(ast::WebidlEnumeration {
+            values: ::std::vec![#(#values),*],
+        })
+    }
+
+    type WebidlEnumerationValue = TokenStream;
+    fn webidl_enumeration_value(&mut self, value: &str) -> TokenStream {
+        string(value)
+    }
+
+    type WebidlUnion = TokenStream;
+    fn webidl_union(&mut self, members: Vec<TokenStream>) -> TokenStream {
+        quote!(ast::WebidlUnion {
+            members: ::std::vec![#(#members),*],
+        })
+    }
+
+    type WebidlFunctionBindingsSubsection = ();
+    fn webidl_function_bindings_subsection(
+        &mut self,
+        _bindings: Vec<TokenStream>,
+        _binds: Vec<()>,
+    ) {
+    }
+
+    type FunctionBinding = TokenStream;
+
+    type ImportBinding = TokenStream;
+    fn import_binding(
+        &mut self,
+        name: Option<&str>,
+        wasm_ty: TokenStream,
+        webidl_ty: TokenStream,
+        params: TokenStream,
+        result: TokenStream,
+    ) -> TokenStream {
+        let binding_name = self::name(name);
+        self.push_binding(
+            name,
+            quote!(unresolved::FunctionBinding::Import(unresolved::ImportBinding {
+                name: #binding_name,
+                wasm_ty: #wasm_ty,
+                webidl_ty: #webidl_ty,
+                params: #params,
+                result: #result,
+            })),
+        )
+    }
+
+    type ExportBinding = TokenStream;
+    fn export_binding(
+        &mut self,
+        name: Option<&str>,
+        wasm_ty: TokenStream,
+        webidl_ty: TokenStream,
+        params: TokenStream,
+        result: TokenStream,
+    ) -> TokenStream {
+        let binding_name = self::name(name);
+        self.push_binding(
+            name,
+            quote!(unresolved::FunctionBinding::Export(unresolved::ExportBinding {
+                name: #binding_name,
+                wasm_ty: #wasm_ty,
+                webidl_ty: #webidl_ty,
+                params: #params,
+                result: #result,
+            })),
+        )
+    }
+
+    type Bind = ();
+    fn bind(&mut self, func: TokenStream, binding: TokenStream) {
+        self.stmts
+            .push(quote!(__section.binds.push(unresolved::Bind {
+            func: #func,
+            binding: #binding,
+        });));
+    }
+
+    type OutgoingBindingMap = TokenStream;
+    fn outgoing_binding_map(&mut self, bindings: Vec<TokenStream>) -> TokenStream {
+        quote!(unresolved::OutgoingBindingMap {
+            bindings: ::std::vec![#(#bindings),*],
+        })
+    }
+
+    type IncomingBindingMap = TokenStream;
+    fn incoming_binding_map(&mut self, bindings: Vec<TokenStream>) -> TokenStream {
+        quote!(unresolved::IncomingBindingMap {
+            bindings: ::std::vec![#(#bindings),*],
+        })
+    }
+
+    type OutgoingBindingExpression = TokenStream;
+
+    type OutgoingBindingExpressionAs = TokenStream;
+    fn outgoing_binding_expression_as(&mut self, ty: TokenStream, idx: u32) -> TokenStream {
+        let idx = u32(idx);
+        quote!(unresolved::OutgoingBindingExpression::As(
+            ast::OutgoingBindingExpressionAs { ty: #ty, idx: #idx }
+        ))
+    }
+
+    type OutgoingBindingExpressionUtf8Str = TokenStream;
+    fn outgoing_binding_expression_utf8_str(
+        &mut self,
+        ty: TokenStream,
+        offset: u32,
+        length: u32,
+    ) -> TokenStream {
+        let (offset, length) = (u32(offset), u32(length));
+        quote!(unresolved::OutgoingBindingExpression::Utf8Str(
+            ast::OutgoingBindingExpressionUtf8Str {
+                ty: #ty,
+                offset: #offset,
+                length: #length,
+            }
+        ))
+    }
+
+    type OutgoingBindingExpressionUtf8CStr = TokenStream;
+    fn outgoing_binding_expression_utf8_c_str(
+        &mut self,
+        ty: TokenStream,
+        offset: u32,
+    ) -> TokenStream {
+        let offset = u32(offset);
+        quote!(unresolved::OutgoingBindingExpression::Utf8CStr(
+            ast::OutgoingBindingExpressionUtf8CStr {
+                ty: #ty,
+                offset: #offset,
+            }
+        ))
+    }
+
+    type OutgoingBindingExpressionI32ToEnum = TokenStream;
+    fn outgoing_binding_expression_i32_to_enum(
+        &mut self,
+        ty: TokenStream,
+        idx: u32,
+    ) -> TokenStream {
+        let idx = u32(idx);
+        quote!(unresolved::OutgoingBindingExpression::I32ToEnum(
+            ast::OutgoingBindingExpressionI32ToEnum { ty: #ty, idx: #idx }
+        ))
+    }
+
+    type OutgoingBindingExpressionView = TokenStream;
+    fn outgoing_binding_expression_view(
+        &mut self,
+        ty: TokenStream,
+        offset: u32,
+        length: u32,
+    ) -> TokenStream {
+        let (offset, length) = (u32(offset), u32(length));
+        quote!(unresolved::OutgoingBindingExpression::View(
+            ast::OutgoingBindingExpressionView {
+                ty: #ty,
+                offset: #offset,
+                length: #length,
+            }
+        ))
+    }
+
+    type OutgoingBindingExpressionCopy = TokenStream;
+    fn outgoing_binding_expression_copy(
+        &mut self,
+        ty: TokenStream,
+        offset: u32,
+        length: u32,
+    ) -> TokenStream {
+        let (offset, length) = (u32(offset), u32(length));
+        quote!(unresolved::OutgoingBindingExpression::Copy(
+            ast::OutgoingBindingExpressionCopy {
+                ty: #ty,
+                offset: #offset,
+                length: #length,
+            }
+        ))
+    }
+
+    type OutgoingBindingExpressionDict = TokenStream;
+    fn outgoing_binding_expression_dict(
+        &mut self,
+        ty: TokenStream,
+        fields: Vec<TokenStream>,
+    ) -> TokenStream {
+        quote!(unresolved::OutgoingBindingExpression::Dict(
+            unresolved::OutgoingBindingExpressionDict {
+                ty: #ty,
+                fields: ::std::vec![#(#fields),*],
+            }
+        ))
+    }
+
+    type OutgoingBindingExpressionBindExport = TokenStream;
+    fn outgoing_binding_expression_bind_export(
+        &mut self,
+        ty: TokenStream,
+        binding: TokenStream,
+        idx: u32,
+    ) -> TokenStream {
+        let idx = u32(idx);
+        quote!(unresolved::OutgoingBindingExpression::BindExport(
+            unresolved::OutgoingBindingExpressionBindExport {
+                ty: #ty,
+                binding: #binding,
+                idx: #idx,
+            }
+        ))
+    }
+
+    type OutgoingBindingExpressionCustom = TokenStream;
+    fn outgoing_binding_expression_custom(&mut self, opcode: u8, payload: Vec<u8>) -> TokenStream {
+        let opcode = Literal::u8_unsuffixed(opcode);
+        let payload = payload.into_iter().map(Literal::u8_unsuffixed);
+        quote!(unresolved::OutgoingBindingExpression::Custom(
+            ast::OutgoingBindingExpressionCustom {
+                opcode: #opcode,
+                payload: ::std::vec![#(#payload),*],
+            }
+        ))
+    }
+
+    type IncomingBindingExpression = TokenStream;
+
+    type IncomingBindingExpressionGet = TokenStream;
+    fn incoming_binding_expression_get(&mut self, idx: u32) -> TokenStream {
+        let idx = u32(idx);
+        quote!(unresolved::IncomingBindingExpression::Get(
+            ast::IncomingBindingExpressionGet { idx: #idx }
+        ))
+    }
+
+    type IncomingBindingExpressionAs = TokenStream;
+    fn incoming_binding_expression_as(
+        &mut self,
+        ty: TokenStream,
+        expr: TokenStream,
+    ) -> TokenStream {
+        quote!(unresolved::IncomingBindingExpression::As(
+            unresolved::IncomingBindingExpressionAs {
+                ty: #ty,
+                expr: ::std::boxed::Box::new(#expr),
+            }
+        ))
+    }
+
+    type IncomingBindingExpressionAllocUtf8Str = TokenStream;
+    fn incoming_binding_expression_alloc_utf8_str(
+        &mut self,
+        alloc_func_name: &str,
+        expr: TokenStream,
+    ) -> TokenStream {
+        let alloc_func_name = string(alloc_func_name);
+        quote!(unresolved::IncomingBindingExpression::AllocUtf8Str(
+            unresolved::IncomingBindingExpressionAllocUtf8Str {
+                alloc_func_name: #alloc_func_name,
+                expr: ::std::boxed::Box::new(#expr),
+            }
+        ))
+    }
+
+    type IncomingBindingExpressionAllocCopy = TokenStream;
+    fn incoming_binding_expression_alloc_copy(
+        &mut self,
+        alloc_func_name: &str,
+        expr: TokenStream,
+    ) -> TokenStream {
+        let alloc_func_name = string(alloc_func_name);
+        quote!(unresolved::IncomingBindingExpression::AllocCopy(
+            unresolved::IncomingBindingExpressionAllocCopy {
+                alloc_func_name: #alloc_func_name,
+                expr: ::std::boxed::Box::new(#expr),
+            }
+        ))
+    }
+
+    type IncomingBindingExpressionEnumToI32 = TokenStream;
+    fn incoming_binding_expression_enum_to_i32(
+        &mut self,
+        ty: TokenStream,
+        expr: TokenStream,
+    ) -> TokenStream {
+        quote!(unresolved::IncomingBindingExpression::EnumToI32(
+            unresolved::IncomingBindingExpressionEnumToI32 {
+                ty: #ty,
+                expr: ::std::boxed::Box::new(#expr),
+            }
+        ))
+    }
+
+    type IncomingBindingExpressionField = TokenStream;
+    fn incoming_binding_expression_field(&mut self, idx: u32, expr: TokenStream) -> TokenStream {
+        let idx = u32(idx);
+        quote!(unresolved::IncomingBindingExpression::Field(
+            unresolved::IncomingBindingExpressionField {
+                idx: #idx,
+                expr: ::std::boxed::Box::new(#expr),
+            }
+        ))
+    }
+
+    type IncomingBindingExpressionBindImport = TokenStream;
+    fn incoming_binding_expression_bind_import(
+        &mut self,
+        ty: TokenStream,
+        binding: TokenStream,
+        expr: TokenStream,
+    ) -> TokenStream {
+        quote!(unresolved::IncomingBindingExpression::BindImport(
+            unresolved::IncomingBindingExpressionBindImport {
+                ty: #ty,
+                binding: #binding,
+                expr: ::std::boxed::Box::new(#expr),
+            }
+        ))
+    }
+
+    type IncomingBindingExpressionCustom = TokenStream;
+    fn incoming_binding_expression_custom(&mut self, opcode: u8, payload: Vec<u8>) -> TokenStream {
+        let opcode = Literal::u8_unsuffixed(opcode);
+        let payload = payload.into_iter().map(Literal::u8_unsuffixed);
+        quote!(unresolved::IncomingBindingExpression::Custom(
+            ast::IncomingBindingExpressionCustom {
+                opcode: #opcode,
+                payload: ::std::vec![#(#payload),*],
+            }
+        ))
+    }
+
+    type WebidlTypeRef = TokenStream;
+
+    type WebidlTypeRefNamed = TokenStream;
+    fn webidl_type_ref_named(&mut self, name: &str) -> Option<TokenStream> {
+        self.type_names.get(name).cloned()
+    }
+
+    type WebidlTypeRefIndexed = TokenStream;
+    fn webidl_type_ref_indexed(&mut self, idx: u32) -> Option<TokenStream> {
+        if idx < self.types {
+            Some(self.type_ref(idx))
+        } else {
+            None
+        }
+    }
+
+    type WebidlScalarType = TokenStream;
+    fn webidl_scalar_type_any(&mut self) -> TokenStream {
+        scalar(quote!(Any))
+    }
+    fn webidl_scalar_type_boolean(&mut self) -> TokenStream {
+        scalar(quote!(Boolean))
+    }
+    fn webidl_scalar_type_byte(&mut self) -> TokenStream {
+        scalar(quote!(Byte))
+    }
+    fn webidl_scalar_type_octet(&mut self) -> TokenStream {
+        scalar(quote!(Octet))
+    }
+    fn webidl_scalar_type_long(&mut self) -> TokenStream {
+        scalar(quote!(Long))
+    }
+    fn webidl_scalar_type_unsigned_long(&mut self) -> TokenStream {
+        scalar(quote!(UnsignedLong))
+    }
+    fn webidl_scalar_type_short(&mut self) -> TokenStream {
+        scalar(quote!(Short))
+    }
+    fn webidl_scalar_type_unsigned_short(&mut self) -> TokenStream {
+        scalar(quote!(UnsignedShort))
+    }
+    fn webidl_scalar_type_long_long(&mut self) -> TokenStream {
+        scalar(quote!(LongLong))
+    }
+    fn webidl_scalar_type_unsigned_long_long(&mut self) -> TokenStream {
+        scalar(quote!(UnsignedLongLong))
+    }
+    fn webidl_scalar_type_float(&mut self) -> TokenStream {
+        scalar(quote!(Float))
+    }
+    fn webidl_scalar_type_unrestricted_float(&mut self) -> TokenStream {
+        scalar(quote!(UnrestrictedFloat))
+    }
+    fn webidl_scalar_type_double(&mut self) -> TokenStream {
+        scalar(quote!(Double))
+    }
+    fn webidl_scalar_type_unrestricted_double(&mut self) -> TokenStream {
+        scalar(quote!(UnrestrictedDouble))
+    }
+    fn webidl_scalar_type_dom_string(&mut self) -> TokenStream {
+        scalar(quote!(DomString))
+    }
+    fn webidl_scalar_type_byte_string(&mut self) -> TokenStream {
+        scalar(quote!(ByteString))
+    }
+    fn webidl_scalar_type_usv_string(&mut self) -> TokenStream {
+        scalar(quote!(UsvString))
+    }
+    fn webidl_scalar_type_object(&mut self) -> TokenStream {
+        scalar(quote!(Object))
+    }
+    fn webidl_scalar_type_symbol(&mut self) -> TokenStream {
+        scalar(quote!(Symbol))
+    }
+    fn webidl_scalar_type_array_buffer(&mut self) -> TokenStream {
+        scalar(quote!(ArrayBuffer))
+    }
+    fn webidl_scalar_type_data_view(&mut self) -> TokenStream {
+        scalar(quote!(DataView))
+    }
+    fn webidl_scalar_type_int8_array(&mut self) -> TokenStream {
+        scalar(quote!(Int8Array))
+    }
+    fn webidl_scalar_type_int16_array(&mut self) -> TokenStream {
+        scalar(quote!(Int16Array))
+    }
+    fn webidl_scalar_type_int32_array(&mut self) -> TokenStream {
+        scalar(quote!(Int32Array))
+    }
+    fn webidl_scalar_type_uint8_array(&mut self) -> TokenStream {
+        scalar(quote!(Uint8Array))
+    }
+    fn webidl_scalar_type_uint16_array(&mut self) -> TokenStream {
+        scalar(quote!(Uint16Array))
+    }
+    fn webidl_scalar_type_uint32_array(&mut self) -> TokenStream {
+        scalar(quote!(Uint32Array))
+    }
+    fn webidl_scalar_type_uint8_clamped_array(&mut self) -> TokenStream {
+        scalar(quote!(Uint8ClampedArray))
+    }
+    fn webidl_scalar_type_float32_array(&mut self) -> TokenStream {
+        scalar(quote!(Float32Array))
+    }
+    fn webidl_scalar_type_float64_array(&mut self) -> TokenStream {
+        scalar(quote!(Float64Array))
+    }
+
+    type WasmValType = TokenStream;
+    fn wasm_val_type_i32(&mut self) -> TokenStream {
+        val_type(quote!(I32))
+    }
+    fn wasm_val_type_i64(&mut self) -> TokenStream {
+        val_type(quote!(I64))
+    }
+    fn wasm_val_type_f32(&mut self) -> TokenStream {
+        val_type(quote!(F32))
+    }
+    fn wasm_val_type_f64(&mut self) -> TokenStream {
+        val_type(quote!(F64))
+    }
+    fn wasm_val_type_v128(&mut self) -> TokenStream {
+        val_type(quote!(V128))
+    }
+    fn wasm_val_type_anyref(&mut self) -> TokenStream {
+        val_type(quote!(Anyref))
+    }
+
+    type WasmFuncTypeRef = TokenStream;
+
+    type WasmFuncTypeRefNamed = TokenStream;
+    fn wasm_func_type_ref_named(&mut self, name: &str) -> Option<TokenStream> {
+        let name = string(name);
+        Some(quote!(unresolved::WasmRef::Named(#name)))
+    }
+
+    type WasmFuncTypeRefIndexed = TokenStream;
+    fn wasm_func_type_ref_indexed(&mut self, idx: u32) -> Option<TokenStream> {
+        let idx = u32(idx);
+        Some(quote!(unresolved::WasmRef::Indexed(#idx)))
+    }
+
+    type WasmFuncRef = TokenStream;
+
+    type WasmFuncRefNamed = TokenStream;
+    fn wasm_func_ref_named(&mut self, name: &str) -> Option<TokenStream> {
+        let name = string(name);
+        Some(quote!(unresolved::WasmFuncRef::Named(#name)))
+    }
+
+    type WasmFuncRefIndexed = TokenStream;
+    fn wasm_func_ref_indexed(&mut self, idx: u32) -> Option<TokenStream> {
+        let idx = u32(idx);
+        Some(quote!(unresolved::WasmFuncRef::Indexed(#idx)))
+    }
+
+    type WasmFuncRefImport = TokenStream;
+    fn wasm_func_ref_import(&mut self, module: &str, name: &str) -> Result<TokenStream, String> {
+        let (module, name) = (string(module), string(name));
+        Ok(quote!(unresolved::WasmFuncRef::Import {
+            module: #module,
+            name: #name,
+        }))
+    }
+
+    type WasmFuncRefExport = TokenStream;
+    fn wasm_func_ref_export(&mut self, name: &str) -> Result<TokenStream, String> {
+        let name = string(name);
+        Ok(quote!(unresolved::WasmFuncRef::Export(#name)))
+    }
+
+    type BindingRef = TokenStream;
+
+    type BindingRefNamed = TokenStream;
+    fn binding_ref_named(&mut self, name: &str) -> Option<TokenStream> {
+        self.binding_names
+            .get(name)
+            .map(|idx| u32(*idx).into_token_stream())
+    }
+
+    type BindingRefIndexed = TokenStream;
+    fn binding_ref_indexed(&mut self, idx: u32) -> Option<TokenStream> {
+        if idx < self.bindings {
+            Some(u32(idx).into_token_stream())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! The `webidl_bindings!` macro, which checks Web IDL bindings written in the
+//! straw proposal text format at compile time.
+//!
+//! The bindings are parsed when the crate using the macro is compiled, and
+//! syntax errors and references to Web IDL types or function bindings that
+//! don't exist are reported as compile errors at the offending token. The
+//! macro expands to an expression that builds a
+//! `wasm_webidl_bindings::unresolved::WebidlBindings`, whose references to Wasm
+//! functions and function types are resolved against a module at runtime:
+//!
+//! ```
+//! use wasm_webidl_bindings_macro::webidl_bindings;
+//!
+//! let bindings = webidl_bindings! {
+//!     type $Greeting (func (param DOMString))
+//!
+//!     func-binding $greet import $GreetWasm $Greeting
+//!         (param (utf8-str DOMString 0 1))
+//!         (result)
+//!
+//!     bind (import "env" "greet") $greet
+//! };
+//! # let _ = bindings;
+//! ```
+//!
+//! Since Rust's tokenizer drops comments and whitespace, the text is rebuilt
+//! from the macro's tokens before it is parsed: `$` is joined to the name that
+//! follows it, and `-` to the words on either side of it, so that `$Greeting`
+//! and `func-binding` are written just as they are in the text format. Only
+//! parentheses may be used to delimit lists.
+
+extern crate proc_macro;
+
+mod codegen;
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+/// Parse Web IDL bindings in the straw proposal text format at compile time.
+///
+/// See the crate documentation for details.
+#[proc_macro]
+pub fn webidl_bindings(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input.into()).into()
+}
+
+/// The text format input rebuilt from the macro's tokens, along with the
+/// offset in `text` that each token starts at and its span.
+#[derive(Debug, Default)]
+struct Source {
+    text: String,
+    tokens: Vec<(usize, Span)>,
+    /// Whether the next token is joined to the previous one, rather than being
+    /// separated from it by a space.
+    join_next: bool,
+}
+
+impl Source {
+    fn push(&mut self, text: &str, span: Span) {
+        if !self.text.is_empty() && !self.join_next {
+            self.text.push(' ');
+        }
+        self.tokens.push((self.text.len(), span));
+        self.text.push_str(text);
+        self.join_next = false;
+    }
+
+    fn push_stream(&mut self, stream: TokenStream) -> Result<(), TokenStream> {
+        for tree in stream {
+            match tree {
+                TokenTree::Group(group) => match group.delimiter() {
+                    Delimiter::Parenthesis => {
+                        self.push("(", group.span_open());
+                        self.push_stream(group.stream())?;
+                        self.push(")", group.span_close());
+                    }
+                    // Invisible groups come from fragments passed through
+                    // `macro_rules!` macros.
+                    Delimiter::None => self.push_stream(group.stream())?,
+                    Delimiter::Brace | Delimiter::Bracket => {
+                        return Err(compile_error(
+                            group.span_open(),
+                            "only parentheses may be used to delimit lists",
+                        ));
+                    }
+                },
+                TokenTree::Punct(punct) => match punct.as_char() {
+                    '$' => {
+                        self.push("$", punct.span());
+                        self.join_next = true;
+                    }
+                    '-' => {
+                        self.join_next = true;
+                        self.push("-", punct.span());
+                        self.join_next = true;
+                    }
+                    c => self.push(&c.to_string(), punct.span()),
+                },
+                TokenTree::Ident(ident) => self.push(&ident.to_string(), ident.span()),
+                TokenTree::Literal(literal) => self.push(&literal.to_string(), literal.span()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the span of the token that contains the given offset in `text`.
+    fn span_at(&self, offset: usize) -> Span {
+        self.tokens
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= offset)
+            .map_or_else(Span::call_site, |(_, span)| *span)
+    }
+}
+
+fn compile_error(span: Span, message: &str) -> TokenStream {
+    quote_spanned!(span=> compile_error!(#message);)
+}
+
+fn expand(input: TokenStream) -> TokenStream {
+    let mut source = Source::default();
+    if let Err(error) = source.push_stream(input) {
+        return quote!({ #error });
+    }
+
+    let mut actions = codegen::CodegenActions::default();
+    let ((), errors) =
+        wasm_webidl_bindings_text_parser::parse_with_actions_recovering(&mut actions, &source.text);
+    if !errors.is_empty() {
+        let errors = errors
+            .iter()
+            .map(|e| compile_error(source.span_at(e.start), &e.message));
+        return quote!({ #(#errors)* });
+    }
+
+    actions.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(input: TokenStream) -> String {
+        let mut source = Source::default();
+        source.push_stream(input).unwrap();
+        source.text
+    }
+
+    #[test]
+    fn rebuild_text() {
+        assert_eq!(
+            source(quote! {
+                type $T (dict (field "a" any))
+                func-binding $b import 0 $T (param (utf8-cstr DOMString 0)) (result)
+                bind (import "env" "f") $b
+            }),
+            r#"type $T ( dict ( field "a" any ) ) func-binding $b import 0 $T ( param ( utf8-cstr DOMString 0 ) ) ( result ) bind ( import "env" "f" ) $b"#
+        );
+    }
+
+    #[test]
+    fn other_delimiters_are_errors() {
+        let output = expand(quote!(type $T [dict]));
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let output = expand(quote! {
+            type $T (dict (field "a" $Nope))
+            bind $f $missing
+        });
+        let output = output.to_string();
+        assert!(output.contains("unknown"), "{}", output);
+        assert_eq!(output.matches("compile_error").count(), 2, "{}", output);
+    }
+}
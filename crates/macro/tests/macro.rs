@@ -0,0 +1,78 @@
+use wasm_webidl_bindings::ast;
+use wasm_webidl_bindings_macro::webidl_bindings;
+
+#[test]
+fn builds_unresolved_ast() {
+    let bindings = webidl_bindings! {
+        type $Alias = any
+        type $Dict (dict (field "a" $Alias) (field "b" DOMString))
+        type $Func (func (param $Dict) (result 0))
+        type (enum "x" "y")
+
+        func-binding $f import $FuncWasm 1
+            (param (dict $Dict (as any 0) (utf8-str DOMString 1 2)))
+            (result (as i32 (enum-to-i32 $Alias (get 0))))
+        func-binding export 0 $Func (param) (result (bind-export $Dict $f 0))
+
+        bind $f $f
+        bind (export "g") 1
+    };
+
+    assert_eq!(
+        bindings.types.resolve("$Alias"),
+        Some(ast::WebidlScalarType::Any.into())
+    );
+    assert!(bindings.types.by_name("$Dict").is_some());
+    assert_eq!(bindings.types.by_index(3), None);
+    assert_eq!(bindings.bindings.len(), 2);
+    assert_eq!(bindings.binding_by_name("$f"), Some(0));
+    assert_eq!(bindings.binds.len(), 2);
+
+    let expected = wasm_webidl_bindings::text::parse_unresolved(
+        r#"
+        type $Alias = any
+        type $Dict (dict (field "a" $Alias) (field "b" DOMString))
+        type $Func (func (param $Dict) (result 0))
+        type (enum "x" "y")
+
+        func-binding $f import $FuncWasm 1
+            (param (dict $Dict (as any 0) (utf8-str DOMString 1 2)))
+            (result (as i32 (enum-to-i32 $Alias (get 0))))
+        func-binding export 0 $Func (param) (result (bind-export $Dict $f 0))
+
+        bind $f $f
+        bind (export "g") 1
+        "#,
+    )
+    .unwrap();
+    // Type IDs from different sections never compare equal, so compare how
+    // they are printed instead.
+    assert_eq!(
+        format!("{:?}", bindings.bindings),
+        format!("{:?}", expected.bindings)
+    );
+    assert_eq!(bindings.binds, expected.binds);
+}
+
+#[test]
+fn resolves_against_a_module() {
+    let mut module = walrus::Module::default();
+    let ty = module
+        .types
+        .add(&[walrus::ValType::I32, walrus::ValType::I32], &[]);
+    module.add_import_func("env", "greet", ty);
+    let wasm = module.emit_wasm().unwrap();
+
+    let mut config = walrus::ModuleConfig::default();
+    config.on_parse(|module, ids| {
+        let bindings = webidl_bindings! {
+            type $Greeting (func (param DOMString))
+            func-binding $greet import 0 $Greeting (param (utf8-str DOMString 0 1)) (result)
+            bind (import "env" "greet") $greet
+        };
+        let section = bindings.resolve(module, ids)?;
+        assert_eq!(section.binds.iter().count(), 1);
+        Ok(())
+    });
+    config.parse(&wasm).unwrap();
+}
@@ -13,14 +13,34 @@ pub trait Actions {
     ) -> Self::WebidlTypeSubsection;
 
     type WebidlType;
-    fn webidl_type(&mut self, name: Option<&str>, ty: Self::WebidlCompoundType)
-        -> Self::WebidlType;
+    /// Fails with an error message if `name` is already the name of an alias.
+    fn webidl_type(
+        &mut self,
+        name: Option<&str>,
+        ty: Self::WebidlCompoundType,
+    ) -> Result<Self::WebidlType, String>;
+    /// Fails with an error message if `name` is already taken.
+    fn webidl_type_alias(
+        &mut self,
+        name: &str,
+        ty: Self::WebidlTypeRef,
+    ) -> Result<Self::WebidlType, String>;
 
     type WebidlCompoundType: From<Self::WebidlFunction>
         + From<Self::WebidlDictionary>
         + From<Self::WebidlEnumeration>
         + From<Self::WebidlUnion>;
 
+    /// Called with the byte range of the input that each compound type was
+    /// parsed from, after it is built. Does nothing by default.
+    fn webidl_compound_type_span(
+        &mut self,
+        _ty: &Self::WebidlCompoundType,
+        _start: usize,
+        _end: usize,
+    ) {
+    }
+
     type WebidlFunction;
     fn webidl_function(
         &mut self,
@@ -89,6 +109,16 @@ pub trait Actions {
 
     type FunctionBinding: From<Self::ImportBinding> + From<Self::ExportBinding>;
 
+    /// Called with the byte range of the input that each function binding was
+    /// parsed from, after it is built. Does nothing by default.
+    fn function_binding_span(
+        &mut self,
+        _binding: &Self::FunctionBinding,
+        _start: usize,
+        _end: usize,
+    ) {
+    }
+
     type ImportBinding;
     fn import_binding(
         &mut self,
@@ -112,6 +142,10 @@ pub trait Actions {
     type Bind;
     fn bind(&mut self, func: Self::WasmFuncRef, binding: Self::BindingRef) -> Self::Bind;
 
+    /// Called with the byte range of the input that each bind was parsed from,
+    /// after it is built. Does nothing by default.
+    fn bind_span(&mut self, _bind: &Self::Bind, _start: usize, _end: usize) {}
+
     type OutgoingBindingMap;
     fn outgoing_binding_map(
         &mut self,
@@ -131,7 +165,19 @@ pub trait Actions {
         + From<Self::OutgoingBindingExpressionView>
         + From<Self::OutgoingBindingExpressionCopy>
         + From<Self::OutgoingBindingExpressionDict>
-        + From<Self::OutgoingBindingExpressionBindExport>;
+        + From<Self::OutgoingBindingExpressionBindExport>
+        + From<Self::OutgoingBindingExpressionCustom>;
+
+    /// Called with the byte range of the input that each outgoing binding
+    /// expression was parsed from, after it is built. An expression's nested
+    /// expressions are reported before it is. Does nothing by default.
+    fn outgoing_binding_expression_span(
+        &mut self,
+        _expr: &Self::OutgoingBindingExpression,
+        _start: usize,
+        _end: usize,
+    ) {
+    }
 
     type OutgoingBindingExpressionAs;
     fn outgoing_binding_expression_as(
@@ -193,13 +239,35 @@ pub trait Actions {
         idx: u32,
     ) -> Self::OutgoingBindingExpressionBindExport;
 
+    /// An experimental expression that isn't part of the proposal, e.g.
+    /// `(custom 128 1 2 3)`, whose `opcode` is in the range reserved for
+    /// custom expressions, and whose `payload` is opaque.
+    type OutgoingBindingExpressionCustom;
+    fn outgoing_binding_expression_custom(
+        &mut self,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> Self::OutgoingBindingExpressionCustom;
+
     type IncomingBindingExpression: From<Self::IncomingBindingExpressionGet>
         + From<Self::IncomingBindingExpressionAs>
         + From<Self::IncomingBindingExpressionAllocUtf8Str>
         + From<Self::IncomingBindingExpressionAllocCopy>
         + From<Self::IncomingBindingExpressionEnumToI32>
         + From<Self::IncomingBindingExpressionField>
-        + From<Self::IncomingBindingExpressionBindImport>;
+        + From<Self::IncomingBindingExpressionBindImport>
+        + From<Self::IncomingBindingExpressionCustom>;
+
+    /// Called with the byte range of the input that each incoming binding
+    /// expression was parsed from, after it is built. An expression's nested
+    /// expression is reported before it is. Does nothing by default.
+    fn incoming_binding_expression_span(
+        &mut self,
+        _expr: &Self::IncomingBindingExpression,
+        _start: usize,
+        _end: usize,
+    ) {
+    }
 
     type IncomingBindingExpressionGet;
     fn incoming_binding_expression_get(&mut self, idx: u32) -> Self::IncomingBindingExpressionGet;
@@ -247,6 +315,15 @@ pub trait Actions {
         expr: Self::IncomingBindingExpression,
     ) -> Self::IncomingBindingExpressionBindImport;
 
+    /// Like `outgoing_binding_expression_custom`, but for an incoming binding
+    /// expression.
+    type IncomingBindingExpressionCustom;
+    fn incoming_binding_expression_custom(
+        &mut self,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> Self::IncomingBindingExpressionCustom;
+
     type WebidlTypeRef: From<Self::WebidlTypeRefNamed>
         + From<Self::WebidlTypeRefIndexed>
         + From<Self::WebidlScalarType>;
@@ -317,7 +394,10 @@ pub trait Actions {
         idx: u32,
     ) -> Option<Self::WasmFuncTypeRefIndexed>;
 
-    type WasmFuncRef: From<Self::WasmFuncRefNamed> + From<Self::WasmFuncRefIndexed>;
+    type WasmFuncRef: From<Self::WasmFuncRefNamed>
+        + From<Self::WasmFuncRefIndexed>
+        + From<Self::WasmFuncRefImport>
+        + From<Self::WasmFuncRefExport>;
 
     type WasmFuncRefNamed;
     fn wasm_func_ref_named(&mut self, name: &str) -> Option<Self::WasmFuncRefNamed>;
@@ -326,6 +406,26 @@ pub trait Actions {
     fn wasm_func_ref_indexed(&mut self, idx: u32)
         -> Option<Self::WasmFuncRefIndexed>;
 
+    /// Resolve a reference to the function imported as `name` from `module`,
+    /// e.g. `(import "TextEncoder" "encodeInto")`.
+    ///
+    /// Returns the error message to report if there is no such import, or if
+    /// the import isn't a function.
+    type WasmFuncRefImport;
+    fn wasm_func_ref_import(
+        &mut self,
+        module: &str,
+        name: &str,
+    ) -> Result<Self::WasmFuncRefImport, String>;
+
+    /// Resolve a reference to the function exported as `name`, e.g.
+    /// `(export "greet")`.
+    ///
+    /// Returns the error message to report if there is no such export, or if
+    /// the export isn't a function.
+    type WasmFuncRefExport;
+    fn wasm_func_ref_export(&mut self, name: &str) -> Result<Self::WasmFuncRefExport, String>;
+
     type BindingRef: From<Self::BindingRefNamed> + From<Self::BindingRefIndexed>;
 
     type BindingRefNamed;
@@ -0,0 +1,417 @@
+//! A lossless concrete syntax tree for the text format.
+//!
+//! Parsing with `Actions` throws away whitespace and comments. The concrete
+//! syntax tree keeps every byte of the input instead, so that tools like the
+//! formatter can work with the author's text without losing anything. It only
+//! knows about parentheses and tokens, not about the grammar of the text format,
+//! so it can be built for any input, even one with syntax errors.
+
+use crate::error::Error;
+use std::fmt;
+
+/// The kind of a token in the concrete syntax tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A `(`.
+    LParen,
+    /// A `)`.
+    RParen,
+    /// A keyword, identifier, or number.
+    Atom,
+    /// A quoted string, including its quotes.
+    String,
+    /// A quoted string that is missing its closing quote.
+    UnterminatedString,
+    /// A run of whitespace.
+    Whitespace,
+    /// A `;;` line comment, not including the newline that ends it.
+    Comment,
+}
+
+impl TokenKind {
+    /// Is this whitespace or a comment?
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+/// A token and where it is in the input text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub start: usize,
+}
+
+impl Token<'_> {
+    /// The byte offset just past the end of this token.
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+}
+
+/// Split the input into tokens, including whitespace and comments.
+///
+/// Concatenating the text of every token gives back the input.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let kind = match c {
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '"' => {
+                let mut kind = TokenKind::UnterminatedString;
+                while let Some((i, c)) = chars.next() {
+                    end = i + c.len_utf8();
+                    match c {
+                        '\\' => {
+                            if let Some((i, c)) = chars.next() {
+                                end = i + c.len_utf8();
+                            }
+                        }
+                        '"' => {
+                            kind = TokenKind::String;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                kind
+            }
+            ';' if input[end..].starts_with(';') => {
+                while let Some(&(i, c)) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                TokenKind::Comment
+            }
+            c if c.is_whitespace() => {
+                while let Some(&(i, c)) = chars.peek() {
+                    if !c.is_whitespace() {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                TokenKind::Whitespace
+            }
+            _ => {
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace()
+                        || c == '('
+                        || c == ')'
+                        || c == '"'
+                        || input[i..].starts_with(";;")
+                    {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                TokenKind::Atom
+            }
+        };
+        tokens.push(Token {
+            kind,
+            text: &input[start..end],
+            start,
+        });
+    }
+
+    tokens
+}
+
+/// A node in the concrete syntax tree: either a single token or a
+/// parenthesized list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node<'a> {
+    Token(Token<'a>),
+    List(List<'a>),
+}
+
+impl Node<'_> {
+    /// The byte offset of the start of this node.
+    pub fn start(&self) -> usize {
+        match self {
+            Node::Token(t) => t.start,
+            Node::List(l) => l.open.start,
+        }
+    }
+
+    /// The byte offset just past the end of this node.
+    pub fn end(&self) -> usize {
+        match self {
+            Node::Token(t) => t.end(),
+            Node::List(l) => l.end(),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Token(t) => f.write_str(t.text),
+            Node::List(l) => {
+                f.write_str(l.open.text)?;
+                for child in &l.children {
+                    child.write(f)?;
+                }
+                if let Some(close) = &l.close {
+                    f.write_str(close.text)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn errors(&self, errors: &mut Vec<Error>) {
+        match self {
+            Node::Token(t) if t.kind == TokenKind::UnterminatedString => errors.push(Error {
+                start: t.start,
+                end: t.end(),
+                message: "unterminated string".into(),
+            }),
+            Node::Token(t) if t.kind == TokenKind::RParen => errors.push(Error {
+                start: t.start,
+                end: t.end(),
+                message: "unmatched `)`".into(),
+            }),
+            Node::Token(_) => {}
+            Node::List(l) => {
+                for child in &l.children {
+                    child.errors(errors);
+                }
+                if l.close.is_none() {
+                    errors.push(Error {
+                        start: l.open.start,
+                        end: l.open.end(),
+                        message: "unclosed `(`".into(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A parenthesized list of nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct List<'a> {
+    pub open: Token<'a>,
+    pub children: Vec<Node<'a>>,
+    /// The closing `)`, or `None` if the input ended before the list was
+    /// closed.
+    pub close: Option<Token<'a>>,
+}
+
+impl List<'_> {
+    /// The byte offset just past the end of this list.
+    pub fn end(&self) -> usize {
+        match &self.close {
+            Some(close) => close.end(),
+            None => self
+                .children
+                .last()
+                .map_or(self.open.end(), |child| child.end()),
+        }
+    }
+}
+
+/// A lossless concrete syntax tree for some text format input.
+///
+/// Displaying the tree gives back exactly the input it was parsed from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxTree<'a> {
+    pub nodes: Vec<Node<'a>>,
+}
+
+impl SyntaxTree<'_> {
+    /// Get any unbalanced parentheses or unterminated strings in this tree.
+    pub fn errors(&self) -> Vec<Error> {
+        let mut errors = vec![];
+        for node in &self.nodes {
+            node.errors(&mut errors);
+        }
+        errors.sort_by_key(|e| e.start);
+        errors
+    }
+}
+
+impl fmt::Display for SyntaxTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in &self.nodes {
+            node.write(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the input into a lossless concrete syntax tree.
+///
+/// This never fails: unbalanced parentheses and unterminated strings are kept
+/// in the tree and reported by `SyntaxTree::errors`.
+pub fn parse(input: &str) -> SyntaxTree<'_> {
+    // The stack of lists that are still open, innermost last.
+    let mut open: Vec<List> = vec![];
+    let mut nodes = vec![];
+
+    for token in tokenize(input) {
+        let node = match token.kind {
+            TokenKind::LParen => {
+                open.push(List {
+                    open: token,
+                    children: vec![],
+                    close: None,
+                });
+                continue;
+            }
+            TokenKind::RParen => match open.pop() {
+                Some(mut list) => {
+                    list.close = Some(token);
+                    Node::List(list)
+                }
+                None => Node::Token(token),
+            },
+            _ => Node::Token(token),
+        };
+        match open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => nodes.push(node),
+        }
+    }
+
+    // Close any lists that are still open at the end of the input.
+    while let Some(list) = open.pop() {
+        let node = Node::List(list);
+        match open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => nodes.push(node),
+        }
+    }
+
+    SyntaxTree { nodes }
+}
+
+/// Replace every comment in the input with spaces.
+///
+/// The grammar doesn't know about comments, but blanking them out this way
+/// keeps the byte offsets of everything else the same.
+pub(crate) fn blank_comments(input: &str) -> std::borrow::Cow<'_, str> {
+    let tokens = tokenize(input);
+    if tokens.iter().all(|t| t.kind != TokenKind::Comment) {
+        return input.into();
+    }
+    let mut blanked = String::with_capacity(input.len());
+    for t in tokens {
+        if t.kind == TokenKind::Comment {
+            blanked.extend(t.text.bytes().map(|_| ' '));
+        } else {
+            blanked.push_str(t.text);
+        }
+    }
+    blanked.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input).iter().map(|t| (t.kind, t.text)).collect()
+    }
+
+    #[test]
+    fn tokenize_everything() {
+        use self::TokenKind::*;
+        assert_eq!(
+            kinds("type $A (enum \"a\\\"b\") ;; hi\n(x;;y"),
+            vec![
+                (Atom, "type"),
+                (Whitespace, " "),
+                (Atom, "$A"),
+                (Whitespace, " "),
+                (LParen, "("),
+                (Atom, "enum"),
+                (Whitespace, " "),
+                (String, "\"a\\\"b\""),
+                (RParen, ")"),
+                (Whitespace, " "),
+                (Comment, ";; hi"),
+                (Whitespace, "\n"),
+                (LParen, "("),
+                (Atom, "x"),
+                (Comment, ";;y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_unterminated_string() {
+        assert_eq!(
+            kinds("(enum \"a"),
+            vec![
+                (TokenKind::LParen, "("),
+                (TokenKind::Atom, "enum"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::UnterminatedString, "\"a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_is_lossless() {
+        let inputs = [
+            "",
+            "  \n\t ",
+            r#"type $Contact
+                 ;; A person's contact details.
+                 (dict (field "name" DOMString)   (field "age" long)) ;; trailing
+               bind 0 1"#,
+            "(((",
+            ")))",
+            "(a (b \"unterminated",
+            "ünïcödé (😀 ;; ☃\n)",
+        ];
+        for input in inputs.iter() {
+            let tree = parse(input);
+            assert_eq!(tree.to_string(), *input);
+        }
+    }
+
+    #[test]
+    fn parse_nesting_and_spans() {
+        let tree = parse("(a (b) c)");
+        assert_eq!(tree.nodes.len(), 1);
+        let list = match &tree.nodes[0] {
+            Node::List(l) => l,
+            n => panic!("expected a list, found {:?}", n),
+        };
+        assert_eq!(list.children.len(), 5);
+        assert_eq!(list.children[2].start(), 3);
+        assert_eq!(list.children[2].end(), 6);
+        assert_eq!(tree.nodes[0].end(), 9);
+        assert!(tree.errors().is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let errors = parse(") (a \"b").errors();
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["unmatched `)`", "unclosed `(`", "unterminated string"]
+        );
+        assert_eq!((errors[1].start, errors[1].end), (2, 3));
+    }
+
+    #[test]
+    fn blank_comments_keeps_offsets() {
+        let input = "(a ;; ☃\n\"s;;\") ;;x";
+        let blanked = blank_comments(input);
+        assert_eq!(blanked.len(), input.len());
+        assert_eq!(blanked, "(a       \n\"s;;\")    ");
+    }
+}
@@ -1,7 +1,36 @@
-pub(crate) fn error<L, T, S>(error: S) -> lalrpop_util::ParseError<L, T, String>
+use std::fmt;
+
+/// An error found while parsing the text format, along with the byte offsets
+/// of the input text that it applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub(crate) fn error<T, S>(
+    start: usize,
+    end: usize,
+    message: S,
+) -> lalrpop_util::ParseError<usize, T, Error>
 where
     S: Into<String>,
 {
-    let error = error.into();
-    lalrpop_util::ParseError::User { error }
+    let message = message.into();
+    lalrpop_util::ParseError::User {
+        error: Error {
+            start,
+            end,
+            message,
+        },
+    }
 }
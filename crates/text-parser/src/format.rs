@@ -0,0 +1,391 @@
+use crate::cst::{self, List, Node, TokenKind};
+use crate::error::Error;
+
+const INDENT: usize = 4;
+
+/// Format the given straw proposal text format input canonically.
+///
+/// Each top-level `type`, `func-binding`, and `bind` item starts on a new line,
+/// with a blank line between items. The names and references at the start of
+/// an item stay on its first line, and each expression after them goes on its
+/// own line, indented one level. An expression stays on a single line unless it
+/// contains a comment or more than one nested expression, in which case each of
+/// its nested expressions goes on its own line, indented one more level.
+///
+/// Comments are kept, either at the end of the line they were written at the
+/// end of, or on their own line.
+///
+/// Formatting already formatted input doesn't change it. Returns an error if
+/// the input has unbalanced parentheses or an unterminated string.
+pub fn format(input: &str) -> Result<String, Error> {
+    let tree = cst::parse(input);
+    if let Some(e) = tree.errors().into_iter().next() {
+        return Err(e);
+    }
+
+    let mut f = Formatter { out: String::new() };
+    f.top_level(&elements(&tree.nodes, false));
+    if !f.out.is_empty() {
+        f.out.push('\n');
+    }
+    Ok(f.out)
+}
+
+/// A non-whitespace node, along with the whitespace that came before it.
+enum Element<'a, 'b> {
+    /// An atom or a string.
+    Word {
+        text: &'a str,
+        blank_line_before: bool,
+    },
+    List(&'b List<'a>),
+    Comment {
+        text: &'a str,
+        /// Is this comment at the end of a line with something else on it?
+        trailing: bool,
+        blank_line_before: bool,
+    },
+}
+
+/// Get the elements of a sequence of nodes. If `after_open` is true, then the
+/// nodes are the children of a list, and come after its `(`.
+fn elements<'a, 'b>(nodes: &'b [Node<'a>], after_open: bool) -> Vec<Element<'a, 'b>> {
+    let mut elements = vec![];
+    let mut has_prev = after_open;
+    let mut newlines = 0;
+
+    for node in nodes {
+        let element = match node {
+            Node::List(l) => Element::List(l),
+            Node::Token(t) => match t.kind {
+                TokenKind::Whitespace => {
+                    newlines += t.text.matches('\n').count();
+                    continue;
+                }
+                TokenKind::Comment => Element::Comment {
+                    text: t.text.trim_end(),
+                    trailing: has_prev && newlines == 0,
+                    blank_line_before: newlines > 1,
+                },
+                _ => Element::Word {
+                    text: t.text,
+                    blank_line_before: newlines > 1,
+                },
+            },
+        };
+        elements.push(element);
+        has_prev = true;
+        newlines = 0;
+    }
+
+    elements
+}
+
+/// Does a list with these elements need to be split across multiple lines?
+fn is_multiline(elements: &[Element]) -> bool {
+    let mut lists = 0;
+    for e in elements {
+        match e {
+            Element::Word { .. } => {}
+            Element::Comment { .. } => return true,
+            Element::List(l) => {
+                if is_multiline(&self::elements(&l.children, true)) {
+                    return true;
+                }
+                lists += 1;
+            }
+        }
+    }
+    lists > 1
+}
+
+fn is_item_keyword(word: &str) -> bool {
+    matches!(word, "type" | "func-binding" | "bind")
+}
+
+/// Where the formatter is, relative to the top-level items.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Nothing has been written yet.
+    Start,
+    /// On the first line of an item.
+    ItemHead,
+    /// After the first line of an item.
+    ItemBody,
+    /// Just after a comment, which is either part of the preceding item or
+    /// stands on its own between items.
+    Comment { in_item: bool },
+}
+
+struct Formatter {
+    out: String,
+}
+
+impl Formatter {
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat(' ').take(indent));
+    }
+
+    fn top_level(&mut self, elements: &[Element]) {
+        let mut state = State::Start;
+
+        for e in elements {
+            state = match *e {
+                Element::Word {
+                    text,
+                    blank_line_before,
+                } if is_item_keyword(text) => {
+                    match state {
+                        State::Start => {}
+                        State::Comment { in_item: false } if !blank_line_before => self.newline(0),
+                        _ => self.out.push_str("\n\n"),
+                    }
+                    self.out.push_str(text);
+                    State::ItemHead
+                }
+                Element::Word { text, .. } => {
+                    match state {
+                        State::Start => {}
+                        State::ItemHead => self.out.push(' '),
+                        State::ItemBody | State::Comment { in_item: true } => self.newline(INDENT),
+                        State::Comment { in_item: false } => self.newline(0),
+                    }
+                    self.out.push_str(text);
+                    if state == State::ItemHead || state == State::Start {
+                        State::ItemHead
+                    } else {
+                        State::ItemBody
+                    }
+                }
+                Element::List(l) => {
+                    let indent = match state {
+                        State::Start => 0,
+                        State::Comment { in_item: false } => {
+                            self.newline(0);
+                            0
+                        }
+                        _ => {
+                            self.newline(INDENT);
+                            INDENT
+                        }
+                    };
+                    self.list(l, indent);
+                    State::ItemBody
+                }
+                Element::Comment {
+                    text,
+                    trailing: true,
+                    ..
+                } if state != State::Start => {
+                    self.out.push(' ');
+                    self.out.push_str(text);
+                    State::Comment {
+                        in_item: match state {
+                            State::Comment { in_item } => in_item,
+                            _ => true,
+                        },
+                    }
+                }
+                Element::Comment {
+                    text,
+                    blank_line_before,
+                    ..
+                } => {
+                    let in_item = match state {
+                        State::Start => false,
+                        State::ItemHead | State::ItemBody => !blank_line_before,
+                        State::Comment { in_item } => in_item && !blank_line_before,
+                    };
+                    if state != State::Start {
+                        if blank_line_before {
+                            self.out.push('\n');
+                        }
+                        self.newline(if in_item { INDENT } else { 0 });
+                    }
+                    self.out.push_str(text);
+                    State::Comment { in_item }
+                }
+            };
+        }
+    }
+
+    fn list(&mut self, list: &List, indent: usize) {
+        let elements = elements(&list.children, true);
+        self.out.push('(');
+
+        if !is_multiline(&elements) {
+            for (i, e) in elements.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(' ');
+                }
+                match e {
+                    Element::Word { text, .. } => self.out.push_str(text),
+                    Element::List(l) => self.list(l, indent),
+                    Element::Comment { .. } => unreachable!(),
+                }
+            }
+            self.out.push(')');
+            return;
+        }
+
+        let inner = indent + INDENT;
+        let mut on_first_line = true;
+        let mut after_comment = false;
+        for (i, e) in elements.iter().enumerate() {
+            after_comment = false;
+            match *e {
+                Element::Word { text, .. } if on_first_line => {
+                    if i > 0 {
+                        self.out.push(' ');
+                    }
+                    self.out.push_str(text);
+                }
+                Element::Word { text, .. } => {
+                    self.newline(inner);
+                    self.out.push_str(text);
+                }
+                Element::List(l) => {
+                    self.newline(inner);
+                    self.list(l, inner);
+                    on_first_line = false;
+                }
+                Element::Comment { text, trailing, .. } => {
+                    if trailing {
+                        self.out.push(' ');
+                    } else {
+                        self.newline(inner);
+                    }
+                    self.out.push_str(text);
+                    on_first_line = false;
+                    after_comment = true;
+                }
+            }
+        }
+
+        // A comment runs to the end of its line, so the `)` can't go after it.
+        if after_comment {
+            self.newline(indent);
+        }
+        self.out.push(')');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_formats_to(input: &str, expected: &str) {
+        let actual = format(input).unwrap();
+        println!("actual =\n{}", actual);
+        assert_eq!(actual, expected);
+        assert_eq!(format(&actual).unwrap(), actual, "formatting is idempotent");
+    }
+
+    #[test]
+    fn format_explainer_example() {
+        assert_formats_to(
+            r#"
+   type $TextEncoderEncodeIntoResult (dict (field "read" unsigned long long)
+      (field "written" unsigned long long))
+type $EncodeIntoFuncWebIDL
+           (func (method any)
+              (param USVString Uint8Array)    (result $TextEncoderEncodeIntoResult))
+        func-binding $encodeIntoBinding import $EncodeIntoFuncWasm $EncodeIntoFuncWebIDL
+          (param (as any 0) (as any 1) (view Uint8Array 2 3))
+          (result (as i64 (field 0 (get 0))) (as i64 (field 1 (get 0))))
+        bind $encodeInto $encodeIntoBinding"#,
+            r#"type $TextEncoderEncodeIntoResult
+    (dict
+        (field "read" unsigned long long)
+        (field "written" unsigned long long))
+
+type $EncodeIntoFuncWebIDL
+    (func
+        (method any)
+        (param USVString Uint8Array)
+        (result $TextEncoderEncodeIntoResult))
+
+func-binding $encodeIntoBinding import $EncodeIntoFuncWasm $EncodeIntoFuncWebIDL
+    (param
+        (as any 0)
+        (as any 1)
+        (view Uint8Array 2 3))
+    (result
+        (as i64 (field 0 (get 0)))
+        (as i64 (field 1 (get 0))))
+
+bind $encodeInto $encodeIntoBinding
+"#,
+        );
+    }
+
+    #[test]
+    fn format_nested_dicts() {
+        assert_formats_to(
+            "func-binding $b export 0 1 (param) (result (dict $A (dict $B (as long 0) (as long 1)) (as long 2)))",
+            r#"func-binding $b export 0 1
+    (param)
+    (result
+        (dict $A
+            (dict $B
+                (as long 0)
+                (as long 1))
+            (as long 2)))
+"#,
+        );
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        assert_formats_to(
+            r#";; Types.
+
+;; A contact.
+type $Contact ;; trailing after the name
+  (dict (field "name" DOMString) ;; the name
+    ;; the age
+    (field "age" long)) ;; trailing after the type
+type $Bytes = Uint8Array
+
+
+;; Binds.
+bind 0 1"#,
+            r#";; Types.
+
+;; A contact.
+type $Contact ;; trailing after the name
+    (dict
+        (field "name" DOMString) ;; the name
+        ;; the age
+        (field "age" long)) ;; trailing after the type
+
+type $Bytes = Uint8Array
+
+;; Binds.
+bind 0 1
+"#,
+        );
+    }
+
+    #[test]
+    fn format_comment_before_close_paren() {
+        assert_formats_to(
+            "type (enum \"a\" ;; a\n)",
+            "type\n    (enum \"a\" ;; a\n    )\n",
+        );
+    }
+
+    #[test]
+    fn format_empty() {
+        assert_formats_to("", "");
+        assert_formats_to(" \n\t\n", "");
+    }
+
+    #[test]
+    fn format_errors() {
+        assert!(format("type (dict").is_err());
+        assert!(format("type (enum \"a)").is_err());
+        assert!(format("type (enum))").is_err());
+    }
+}
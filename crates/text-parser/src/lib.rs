@@ -1,8 +1,12 @@
 //! Working with the text format.
 
 mod actions;
+pub mod cst;
 mod error;
+mod format;
 mod parser;
 
 pub use actions::Actions;
-pub use parser::parse_with_actions;
+pub use error::Error;
+pub use format::format;
+pub use parser::{parse_with_actions, parse_with_actions_recovering};
@@ -1,8 +1,16 @@
 #![allow(unused_imports, dead_code, missing_debug_implementations)]
 
 use crate::actions::Actions;
+use crate::error::Error;
+use std::borrow::Cow;
 
-include!(concat!(env!("OUT_DIR"), "/grammar.rs"));
+// The generated parser doesn't follow every lint, so keep it in its own module.
+#[allow(clippy::all, unused_parens)]
+mod grammar {
+    include!(concat!(env!("OUT_DIR"), "/grammar.rs"));
+}
+
+pub(crate) use self::grammar::*;
 
 /// Parse the given straw proposal text format input with custom parse actions.
 ///
@@ -16,12 +24,273 @@ pub fn parse_with_actions<A>(
 where
     A: Actions,
 {
+    let input = crate::cst::blank_comments(input);
     let ast = WebidlBindingsSectionParser::new()
-        .parse(actions, input)
+        .parse(actions, 0, &mut Indices::default(), &input)
         .map_err(|e| failure::format_err!("{}", e))?;
     Ok(ast)
 }
 
+/// Parse the given straw proposal text format input with custom parse actions,
+/// recovering from errors at top-level item boundaries.
+///
+/// Rather than stopping at the first error, each `type`, `func-binding` and
+/// `bind` item is parsed on its own, and the section is assembled from the
+/// items that parsed successfully. Every syntax and name resolution error is
+/// returned alongside it, in the order it appears in the input. The items are
+/// split apart by hand rather than with LALRPOP's `!` error recovery, so an
+/// error never spreads into the items after it.
+///
+/// An item that fails to parse still takes up its index, so references by
+/// index to the types and function bindings after it refer to the same items
+/// as they would if it had parsed, and references to it are errors.
+pub fn parse_with_actions_recovering<A>(
+    actions: &mut A,
+    input: &str,
+) -> (A::WebidlBindingsSection, Vec<Error>)
+where
+    A: Actions,
+{
+    let input = &crate::cst::blank_comments(input);
+    let mut errors = vec![];
+    let mut types = vec![];
+    let mut bindings = vec![];
+    let mut binds = vec![];
+    let mut indices = Indices::default();
+
+    // Items must appear in the order that the subsections do: all `type`s,
+    // then all `func-binding`s, then all `bind`s.
+    let mut last_kind = ItemKind::Type;
+
+    for item in top_level_items(input) {
+        let text = &input[item.start..item.end];
+        let kind = match item.kind {
+            Some(kind) => kind,
+            None => {
+                errors.push(Error {
+                    start: item.start,
+                    end: item.end,
+                    message: "expected `type`, `func-binding`, or `bind`".into(),
+                });
+                continue;
+            }
+        };
+        if kind < last_kind {
+            errors.push(Error {
+                start: item.start,
+                end: item.end,
+                message: format!(
+                    "`{}` must come before every `{}`",
+                    kind.keyword(),
+                    last_kind.keyword()
+                ),
+            });
+            indices.skip(kind, text);
+            continue;
+        }
+        last_kind = kind;
+
+        let offset = item.start;
+        let located = |e| recovered_error(e, offset);
+        let defined = indices.defined(kind);
+        let parsed = match kind {
+            ItemKind::Type => WebidlTypeParser::new()
+                .parse(actions, offset, &mut indices, text)
+                .map(|ty| types.push(ty)),
+            ItemKind::FunctionBinding => FunctionBindingParser::new()
+                .parse(actions, offset, &mut indices, text)
+                .map(|binding| bindings.push(binding)),
+            ItemKind::Bind => BindParser::new()
+                .parse(actions, offset, &mut indices, text)
+                .map(|bind| binds.push(bind)),
+        };
+        if let Err(e) = parsed {
+            errors.push(located(e));
+            // The item may have failed after it defined its type or binding,
+            // which then keeps its index.
+            if indices.defined(kind) == defined {
+                indices.skip(kind, text);
+            }
+        }
+    }
+
+    let types = actions.webidl_type_subsection(types);
+    let bindings = actions.webidl_function_bindings_subsection(bindings, binds);
+    (actions.webidl_bindings_section(types, bindings), errors)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ItemKind {
+    Type,
+    FunctionBinding,
+    Bind,
+}
+
+impl ItemKind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            ItemKind::Type => "type",
+            ItemKind::FunctionBinding => "func-binding",
+            ItemKind::Bind => "bind",
+        }
+    }
+}
+
+/// Maps the indices that references to Web IDL types and function bindings use
+/// in the text to the indices that `Actions` gave those items.
+///
+/// They are the same, unless the recovering parser skipped an item that would
+/// have defined a type or binding.
+#[derive(Debug, Default)]
+pub(crate) struct Indices {
+    types: IndexSpace,
+    bindings: IndexSpace,
+}
+
+impl Indices {
+    fn defined(&self, kind: ItemKind) -> u32 {
+        match kind {
+            ItemKind::Type => self.types.defined,
+            ItemKind::FunctionBinding => self.bindings.defined,
+            ItemKind::Bind => 0,
+        }
+    }
+
+    /// Reserve the index of the skipped item of the given kind, whose text is
+    /// `text`, if it would have defined a type or binding.
+    fn skip(&mut self, kind: ItemKind, text: &str) {
+        match kind {
+            // Type aliases don't have indices.
+            ItemKind::Type if !is_type_alias(text) => self.types.skip(),
+            ItemKind::Type => {}
+            ItemKind::FunctionBinding => self.bindings.skip(),
+            ItemKind::Bind => {}
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct IndexSpace {
+    /// How many items the actions have defined.
+    defined: u32,
+    /// The indices in the text of the items that were skipped, in increasing
+    /// order.
+    skipped: Vec<u32>,
+}
+
+impl IndexSpace {
+    fn define(&mut self) {
+        self.defined += 1;
+    }
+
+    fn skip(&mut self) {
+        self.skipped.push(self.defined + self.skipped.len() as u32);
+    }
+
+    /// Get the index that the actions gave to the item at index `idx` in the
+    /// text, or `None` if that item was skipped.
+    fn get(&self, idx: u32) -> Option<u32> {
+        match self.skipped.binary_search(&idx) {
+            Ok(_) => None,
+            Err(before) => Some(idx - before as u32),
+        }
+    }
+}
+
+/// Is the `type` item whose text is `text` a type alias, like `type $A = long`?
+///
+/// Unlike the parser's lexer, the CST tokenizer reads `$A=long` as one atom, so
+/// this looks for an `=` inside the atoms before the compound type's `(`.
+fn is_type_alias(text: &str) -> bool {
+    crate::cst::tokenize(text)
+        .iter()
+        .filter(|t| !t.kind.is_trivia())
+        .skip(1)
+        .take(2)
+        .take_while(|t| t.kind == crate::cst::TokenKind::Atom)
+        .any(|t| t.text.contains('='))
+}
+
+/// Strip the quotes from a quoted string and undo its escapes.
+///
+/// A backslash escapes whatever character follows it, which is how a `"` or a
+/// `\` gets into a string.
+pub(crate) fn unquote(s: &str) -> Cow<'_, str> {
+    let s = &s[1..(s.len() - 1)];
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut unquoted = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    Cow::Owned(unquoted)
+}
+
+struct Item {
+    kind: Option<ItemKind>,
+    start: usize,
+    end: usize,
+}
+
+/// Split the input into top-level items at each `type`, `func-binding`, and
+/// `bind` keyword.
+///
+/// None of these keywords may appear nested inside an item, so we split on them
+/// even inside unbalanced parentheses. This way a missing `)` only breaks the
+/// item it is missing from. Any leading text that isn't an item becomes an item
+/// without a kind.
+fn top_level_items(input: &str) -> Vec<Item> {
+    let mut items: Vec<Item> = vec![];
+    let tokens = crate::cst::tokenize(input);
+    for token in tokens.iter().filter(|t| !t.kind.is_trivia()) {
+        let (start, end) = (token.start, token.end());
+        let kind = match token.text {
+            "type" => Some(ItemKind::Type),
+            "func-binding" => Some(ItemKind::FunctionBinding),
+            "bind" => Some(ItemKind::Bind),
+            _ => None,
+        };
+        match items.last_mut() {
+            Some(last) if kind.is_none() => last.end = end,
+            _ => items.push(Item { kind, start, end }),
+        }
+    }
+    items
+}
+
+/// Convert an error from parsing the item starting at `start` into an `Error`
+/// whose offsets are relative to the whole input.
+///
+/// Our own errors already have offsets relative to the whole input, since the
+/// grammar adds the item's offset to them, but LALRPOP's don't.
+fn recovered_error(e: lalrpop_util::ParseError<usize, Token, Error>, start: usize) -> Error {
+    use lalrpop_util::ParseError::*;
+
+    let e = e.map_location(|l| l + start);
+    let (start, end) = match &e {
+        InvalidToken { location } | UnrecognizedEOF { location, .. } => (*location, *location),
+        UnrecognizedToken {
+            token: (l, _, r), ..
+        }
+        | ExtraToken { token: (l, _, r) } => (*l, *r),
+        User { error } => (error.start, error.end),
+    };
+    let message = match e {
+        User { error } => error.message,
+        e => e.to_string(),
+    };
+    Error {
+        start,
+        end,
+        message,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,8 +366,15 @@ mod tests {
             &mut self,
             name: Option<&str>,
             ty: Self::WebidlCompoundType,
-        ) -> Self::WebidlType {
-            t!("WebidlType" name ty)
+        ) -> Result<Self::WebidlType, String> {
+            Ok(t!("WebidlType" name ty))
+        }
+        fn webidl_type_alias(
+            &mut self,
+            name: &str,
+            ty: Self::WebidlTypeRef,
+        ) -> Result<Self::WebidlType, String> {
+            Ok(t!("WebidlTypeAlias" name ty))
         }
 
         type WebidlCompoundType = ParseTree;
@@ -320,6 +596,17 @@ mod tests {
             t!("OutgoingBindingExpressionBindExport" ty binding idx)
         }
 
+        type OutgoingBindingExpressionCustom = ParseTree;
+        fn outgoing_binding_expression_custom(
+            &mut self,
+            opcode: u8,
+            payload: Vec<u8>,
+        ) -> Self::OutgoingBindingExpressionCustom {
+            let payload: Vec<ParseTree> =
+                payload.into_iter().map(|b| u32::from(b).into()).collect();
+            t!("OutgoingBindingExpressionCustom" u32::from(opcode) payload)
+        }
+
         type IncomingBindingExpression = ParseTree;
 
         type IncomingBindingExpressionGet = ParseTree;
@@ -385,6 +672,17 @@ mod tests {
             t!("IncomingBindingExpressionBindImport" ty binding expr)
         }
 
+        type IncomingBindingExpressionCustom = ParseTree;
+        fn incoming_binding_expression_custom(
+            &mut self,
+            opcode: u8,
+            payload: Vec<u8>,
+        ) -> Self::IncomingBindingExpressionCustom {
+            let payload: Vec<ParseTree> =
+                payload.into_iter().map(|b| u32::from(b).into()).collect();
+            t!("IncomingBindingExpressionCustom" u32::from(opcode) payload)
+        }
+
         type WebidlTypeRef = ParseTree;
 
         type WebidlTypeRefNamed = ParseTree;
@@ -533,6 +831,20 @@ mod tests {
             Some(t!("WasmFuncRefIndexed" idx))
         }
 
+        type WasmFuncRefImport = ParseTree;
+        fn wasm_func_ref_import(
+            &mut self,
+            module: &str,
+            name: &str,
+        ) -> Result<Self::WasmFuncRefImport, String> {
+            Ok(t!("WasmFuncRefImport" module name))
+        }
+
+        type WasmFuncRefExport = ParseTree;
+        fn wasm_func_ref_export(&mut self, name: &str) -> Result<Self::WasmFuncRefExport, String> {
+            Ok(t!("WasmFuncRefExport" name))
+        }
+
         type BindingRef = ParseTree;
 
         type BindingRefNamed = ParseTree;
@@ -551,7 +863,9 @@ mod tests {
             #[test]
             fn $name() {
                 let actions = &mut BuildParseTree;
-                let actual = $parser::new().parse(actions, $input).unwrap();
+                let actual = $parser::new()
+                    .parse(actions, 0, &mut Indices::default(), $input)
+                    .unwrap();
                 let expected = $output;
                 println!("actual = {:#?}", actual);
                 println!("expected = {:#?}", expected);
@@ -565,7 +879,9 @@ mod tests {
             #[test]
             fn $name() {
                 let actions = &mut BuildParseTree;
-                assert!($parser::new().parse(actions, $input).is_err());
+                assert!($parser::new()
+                    .parse(actions, 0, &mut Indices::default(), $input)
+                    .is_err());
             }
         };
     }
@@ -847,6 +1163,38 @@ mod tests {
         r#"type (union "hello")"#
     );
 
+    ok!(
+        webidl_type_alias_ok_1,
+        WebidlTypeParser,
+        "type $Bytes = Uint8Array",
+        t!("WebidlTypeAlias"
+           "$Bytes"
+           t!("WebidlScalarType" "Uint8Array"))
+    );
+    ok!(
+        webidl_type_alias_ok_2,
+        WebidlTypeParser,
+        "type $Person = $Contact",
+        t!("WebidlTypeAlias"
+           "$Person"
+           t!("WebidlTypeRefNamed" "$Contact"))
+    );
+    err!(
+        webidl_type_alias_err_1,
+        WebidlTypeParser,
+        "type = Uint8Array"
+    );
+    err!(
+        webidl_type_alias_err_2,
+        WebidlTypeParser,
+        "type $Bytes ="
+    );
+    err!(
+        webidl_type_alias_err_3,
+        WebidlTypeParser,
+        "type $Contact = (dict)"
+    );
+
     ok!(
         import_binding_ok_1,
         ImportBindingParser,
@@ -1063,7 +1411,24 @@ mod tests {
         "42",
         t!("WasmFuncRefIndexed" 42)
     );
+    ok!(
+        wasm_func_ref_ok_3,
+        WasmFuncRefParser,
+        r#"(import "TextEncoder" "encodeInto")"#,
+        t!("WasmFuncRefImport" "TextEncoder" "encodeInto")
+    );
+    ok!(
+        wasm_func_ref_ok_4,
+        WasmFuncRefParser,
+        r#"(export "greet")"#,
+        t!("WasmFuncRefExport" "greet")
+    );
     err!(wasm_func_ref_err, WasmFuncRefParser, "1abc");
+    err!(
+        wasm_func_ref_err_2,
+        WasmFuncRefParser,
+        r#"(import "greet")"#
+    );
 
     ok!(
         outgoing_binding_expression_as_ok_1,
@@ -1255,6 +1620,29 @@ mod tests {
         "(bind-export SomeCallback SomeBinding)"
     );
 
+    ok!(
+        outgoing_binding_expression_custom_ok_1,
+        OutgoingBindingExpressionParser,
+        "(custom 128 1 2 255)",
+        t!("OutgoingBindingExpressionCustom" 128 t!(1 2 255))
+    );
+    ok!(
+        outgoing_binding_expression_custom_ok_2,
+        OutgoingBindingExpressionParser,
+        "(custom 255)",
+        t!("OutgoingBindingExpressionCustom" 255 t!())
+    );
+    err!(
+        outgoing_binding_expression_custom_err_1,
+        OutgoingBindingExpressionParser,
+        "(custom 7 1 2)"
+    );
+    err!(
+        outgoing_binding_expression_custom_err_2,
+        OutgoingBindingExpressionParser,
+        "(custom 128 256)"
+    );
+
     ok!(
         incoming_binding_expression_get_ok_1,
         IncomingBindingExpressionParser,
@@ -1372,6 +1760,18 @@ mod tests {
         "(field 0)"
     );
 
+    ok!(
+        incoming_binding_expression_custom_ok_1,
+        IncomingBindingExpressionParser,
+        "(custom 200 0)",
+        t!("IncomingBindingExpressionCustom" 200 t!(0))
+    );
+    err!(
+        incoming_binding_expression_custom_err_1,
+        IncomingBindingExpressionParser,
+        "(custom 0 (get 0))"
+    );
+
     ok!(
         incoming_binding_expression_bind_import_ok_1,
         IncomingBindingExpressionParser,
@@ -1397,4 +1797,101 @@ mod tests {
         IncomingBindingExpressionParser,
         "(bind-import hello (get 1))"
     );
+
+    #[test]
+    fn recovering_without_errors_matches_strict_parse() {
+        let input = r#"
+            type $Contact (dict (field "name" DOMString))
+            func-binding $b import $ty $Contact (param) (result)
+            bind $f $b
+        "#;
+        let strict = WebidlBindingsSectionParser::new()
+            .parse(&mut BuildParseTree, 0, &mut Indices::default(), input)
+            .unwrap();
+        let (recovered, errors) = parse_with_actions_recovering(&mut BuildParseTree, input);
+        assert_eq!(errors, vec![]);
+        assert_eq!(recovered, strict);
+    }
+
+    #[test]
+    fn recovering_reports_every_syntax_error() {
+        let input = r#"
+            type $A (dict (field "a"))
+            type $B (enum "b")
+            type $C (union (get 0))
+            bind $f $g $h
+            bind $f $g
+        "#;
+        let (section, errors) = parse_with_actions_recovering(&mut BuildParseTree, input);
+        println!("errors = {:#?}", errors);
+        assert_eq!(errors.len(), 3);
+        assert!(input[errors[0].start..].starts_with(")"));
+        assert!(input[errors[1].start..].starts_with("(get 0)"));
+        assert!(input[errors[2].start..].starts_with("$h"));
+        assert_eq!(
+            section,
+            t!("WebidlBindingsSection"
+               t!("WebidlTypeSubsection"
+                  t!(t!("WebidlType"
+                        t!("Some" "$B")
+                        t!("WebidlEnumeration" t!(t!("WebidlEnumerationValue" "b"))))))
+               t!("WebidlFunctionBindingsSubsection"
+                  t!()
+                  t!(t!("Bind"
+                        t!("WasmFuncRefNamed" "$f")
+                        t!("BindingRefNamed" "$g")))))
+        );
+    }
+
+    #[test]
+    fn recovering_from_unbalanced_parens() {
+        let input = r#"
+            type $A (dict (field "a" long)
+            type $B (enum "b")
+        "#;
+        let (_, errors) = parse_with_actions_recovering(&mut BuildParseTree, input);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("EOF"));
+    }
+
+    #[test]
+    fn recovering_from_misplaced_items() {
+        let input = r#"
+            (dict)
+            bind $f $g
+            type $A (enum "a")
+        "#;
+        let (_, errors) = parse_with_actions_recovering(&mut BuildParseTree, input);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(&input[errors[0].start..errors[0].end], "(dict)");
+        assert_eq!(
+            &input[errors[1].start..errors[1].end],
+            r#"type $A (enum "a")"#
+        );
+        assert_eq!(errors[1].message, "`type` must come before every `bind`");
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        let input = r#"
+            ;; A contact.
+            type $Contact ;; the name of the type
+              (dict (field "name;;" DOMString)) ;; the type itself
+            ;; bind $f $g
+        "#;
+        let expected = t!("WebidlBindingsSection"
+            t!("WebidlTypeSubsection"
+               t!(t!("WebidlType"
+                     t!("Some" "$Contact")
+                     t!("WebidlDictionary"
+                        t!(t!("WebidlDictionaryField"
+                              t!("WebidlDictionaryFieldName" "name;;")
+                              t!("WebidlScalarType" "DOMString")))))))
+            t!("WebidlFunctionBindingsSubsection" t!() t!()));
+        let actual = parse_with_actions(&mut BuildParseTree, input).unwrap();
+        assert_eq!(actual, expected);
+        let (recovered, errors) = parse_with_actions_recovering(&mut BuildParseTree, input);
+        assert_eq!(errors, vec![]);
+        assert_eq!(recovered, expected);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::spans::{BindingMapKind, ExpressionId, Span, Spans};
 use crate::text;
 use id_arena::{Arena, Id};
 use std::borrow::Cow;
@@ -8,6 +9,93 @@ pub struct WebidlBindings {
     pub types: WebidlTypes,
     pub bindings: FunctionBindings,
     pub binds: Binds,
+    pub extensions: Extensions,
+}
+
+/// The parts of a binary section that this crate doesn't understand, such as
+/// subsections added by newer producers. They are kept when the section is
+/// decoded, and written back out unchanged when it is encoded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Extensions {
+    /// The optional feature flags in the section's header that aren't known.
+    pub features: u32,
+    /// The subsections with unknown ids, in the order that they appeared, each
+    /// with its position among the known subsections.
+    pub subsections: Vec<UnknownSubsection>,
+}
+
+/// A subsection with an id that this crate doesn't know about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnknownSubsection {
+    pub id: u8,
+    /// The subsection's contents, without its id and size.
+    pub data: Vec<u8>,
+    /// Where the subsection was, so that it is written back out in the same
+    /// place.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub position: SubsectionPosition,
+}
+
+/// Where an unknown subsection is in a section, relative to the known
+/// subsections.
+///
+/// A position is kept even when the known subsection before it is left out, for
+/// example because the section has no names, so the unknown subsection still
+/// comes after the known ones that come before that position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SubsectionPosition {
+    /// Right after the header.
+    AfterHeader,
+    /// After the string table subsection.
+    AfterStringTable,
+    /// After the Web IDL type subsection.
+    AfterTypes,
+    /// After the function binding subsection.
+    AfterBindings,
+    /// After the names subsection, at the end of the section, which is where
+    /// new subsections usually go.
+    #[default]
+    AfterNames,
+}
+
+impl WebidlBindings {
+    /// Redirect every reference to a Wasm function or function type, including
+    /// those inside nested `bind-import` expressions, through `funcs` and
+    /// `types`.
+    ///
+    /// This is for tools that replace functions, for example with wrappers,
+    /// or merge types. It can also move a section to a different
+    /// `walrus::Module`, given the mapping from the old module's IDs to the new
+    /// module's.
+    pub fn remap<F, T>(&mut self, funcs: F, types: T)
+    where
+        F: Fn(walrus::FunctionId) -> walrus::FunctionId,
+        T: Fn(walrus::TypeId) -> walrus::TypeId,
+    {
+        for (_, bind) in self.binds.arena.iter_mut() {
+            bind.func = funcs(bind.func);
+        }
+        for (_, binding) in self.bindings.arena.iter_mut() {
+            match binding {
+                FunctionBinding::Import(i) => {
+                    i.wasm_ty = types(i.wasm_ty);
+                    for expr in &mut i.result.bindings {
+                        expr.remap_types(&types);
+                    }
+                }
+                FunctionBinding::Export(e) => {
+                    e.wasm_ty = types(e.wasm_ty);
+                    for expr in &mut e.params.bindings {
+                        expr.remap_types(&types);
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl walrus::CustomSection for WebidlBindings {
@@ -15,10 +103,14 @@ impl walrus::CustomSection for WebidlBindings {
         "webidl-bindings"
     }
 
-    fn data(&self, ids_to_indices: &walrus::IdsToIndices) -> Cow<[u8]> {
+    fn data(&self, ids_to_indices: &walrus::IdsToIndices) -> Cow<'_, [u8]> {
+        // Writing into a vec never fails, so this only fails if the section
+        // refers to something that isn't in it, which `binary::check` finds
+        // before the module is emitted.
         let mut data = vec![];
-        crate::binary::encode(self, ids_to_indices, &mut data)
-            .expect("writing into a vec never fails");
+        if let Err(e) = crate::binary::encode(self, ids_to_indices, &mut data) {
+            panic!("failed to encode the webidl-bindings section: {}", e);
+        }
         data.into()
     }
 }
@@ -52,6 +144,7 @@ id_newtypes! {
 #[derive(Debug, Default)]
 pub struct WebidlTypes {
     pub(crate) names: HashMap<String, Id<WebidlCompoundType>>,
+    pub(crate) aliases: HashMap<String, WebidlTypeRef>,
     indices: Vec<Id<WebidlCompoundType>>,
     pub(crate) arena: Arena<WebidlCompoundType>,
 }
@@ -111,8 +204,58 @@ impl_webidl_type_id! {
 }
 
 impl WebidlTypes {
+    /// Look up a compound type by name, following aliases to the compound type
+    /// that they refer to.
+    ///
+    /// Aliases of scalar types aren't found; use `resolve` for those.
     pub fn by_name(&self, name: &str) -> Option<Id<WebidlCompoundType>> {
-        self.names.get(name).cloned()
+        match self.resolve(name)? {
+            WebidlTypeRef::Id(id) => Some(id),
+            WebidlTypeRef::Scalar(_) => None,
+        }
+    }
+
+    /// Look up a type by name, following aliases to the compound or scalar type
+    /// that they refer to.
+    pub fn resolve(&self, name: &str) -> Option<WebidlTypeRef> {
+        self.names
+            .get(name)
+            .map(|id| WebidlTypeRef::Id(*id))
+            .or_else(|| self.aliases.get(name).cloned())
+    }
+
+    /// Give the compound type `id` the name `name`.
+    ///
+    /// It is an error for `name` to already be the name of an alias.
+    pub fn set_name<T>(&mut self, name: String, id: T) -> Result<(), failure::Error>
+    where
+        T: Into<Id<WebidlCompoundType>>,
+    {
+        if self.aliases.contains_key(&name) {
+            failure::bail!("Web IDL type name already defined: '{}'", name);
+        }
+        self.names.insert(name, id.into());
+        Ok(())
+    }
+
+    /// Give the type `ty` the additional name `name`.
+    ///
+    /// Unlike a named compound type, an alias may refer to a scalar type. It is
+    /// an error for `name` to already be the name of a type or another alias.
+    pub fn alias<T>(&mut self, name: String, ty: T) -> Result<(), failure::Error>
+    where
+        T: Into<WebidlTypeRef>,
+    {
+        if self.resolve(&name).is_some() {
+            failure::bail!("Web IDL type name already defined: '{}'", name);
+        }
+        self.aliases.insert(name, ty.into());
+        Ok(())
+    }
+
+    /// Iterate over all type aliases and the types they refer to.
+    pub fn aliases(&self) -> impl Iterator<Item = (&str, WebidlTypeRef)> {
+        self.aliases.iter().map(|(name, ty)| (name.as_str(), *ty))
     }
 
     pub fn by_index(&self, index: u32) -> Option<Id<WebidlCompoundType>> {
@@ -236,16 +379,20 @@ pub struct Binds {
 
 impl Binds {
     pub fn get(&self, id: Id<Bind>) -> Option<&Bind> {
-        self.arena.get(id.into())
+        self.arena.get(id)
     }
 
     pub fn get_mut(&mut self, id: Id<Bind>) -> Option<&mut Bind> {
-        self.arena.get_mut(id.into())
+        self.arena.get_mut(id)
     }
 
     pub fn insert(&mut self, bind: Bind) -> Id<Bind> {
         self.arena.alloc(bind)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id<Bind>, &Bind)> {
+        self.arena.iter()
+    }
 }
 
 #[derive(Debug)]
@@ -253,6 +400,10 @@ pub struct BuildAstActions<'a> {
     section: &'a mut WebidlBindings,
     module: &'a walrus::Module,
     ids: &'a walrus::IndicesToIds,
+    spans: Option<&'a mut Spans>,
+    /// The spans of the binding expressions that have been parsed, but not yet
+    /// made part of a function binding, in the order they were parsed.
+    expression_spans: Vec<Span>,
 }
 
 impl<'a> BuildAstActions<'a> {
@@ -265,7 +416,123 @@ impl<'a> BuildAstActions<'a> {
             section,
             module,
             ids,
+            spans: None,
+            expression_spans: vec![],
+        }
+    }
+
+    /// Also record the span of each AST node that is built in the given side
+    /// table.
+    pub fn with_spans(mut self, spans: &'a mut Spans) -> Self {
+        self.spans = Some(spans);
+        self
+    }
+
+    /// Move the spans of a new function binding's expressions from
+    /// `expression_spans` into the side table.
+    ///
+    /// `paths` are the paths to each of the binding's expressions, in the
+    /// order that they were parsed: every expression in the params map, then
+    /// every expression in the result map, with nested expressions before the
+    /// expressions that contain them.
+    fn expression_spans(
+        &mut self,
+        binding: Id<FunctionBinding>,
+        paths: Vec<(BindingMapKind, Vec<u32>)>,
+    ) {
+        let spans = match &mut self.spans {
+            Some(spans) => spans,
+            None => return,
+        };
+        // Any spans before this binding's are left over from an item that
+        // failed to parse.
+        if paths.len() <= self.expression_spans.len() {
+            let start = self.expression_spans.len() - paths.len();
+            for ((map, path), span) in paths.into_iter().zip(&self.expression_spans[start..]) {
+                let id = ExpressionId { binding, map, path };
+                spans.expressions.insert(id, *span);
+            }
+        }
+        self.expression_spans.clear();
+    }
+}
+
+/// Get the paths to each of the given outgoing binding expressions and their
+/// nested expressions, in the order they were parsed.
+fn outgoing_expression_paths(
+    exprs: &[OutgoingBindingExpression],
+    map: BindingMapKind,
+    prefix: &mut Vec<u32>,
+    paths: &mut Vec<(BindingMapKind, Vec<u32>)>,
+) {
+    for (i, expr) in exprs.iter().enumerate() {
+        prefix.push(i as u32);
+        if let OutgoingBindingExpression::Dict(dict) = expr {
+            outgoing_expression_paths(&dict.fields, map, prefix, paths);
         }
+        paths.push((map, prefix.clone()));
+        prefix.pop();
+    }
+}
+
+/// Get the paths to each of the given incoming binding expressions and their
+/// nested expressions, in the order they were parsed.
+fn incoming_expression_paths(
+    exprs: &[IncomingBindingExpression],
+    map: BindingMapKind,
+    paths: &mut Vec<(BindingMapKind, Vec<u32>)>,
+) {
+    for (i, mut expr) in exprs.iter().enumerate() {
+        // Walk down to the innermost expression, and then push the paths on
+        // the way back out.
+        let mut path = vec![i as u32];
+        let mut depth = 0;
+        while let Some(inner) = expr.nested() {
+            path.push(0);
+            depth += 1;
+            expr = inner;
+        }
+        for _ in 0..depth {
+            paths.push((map, path.clone()));
+            path.pop();
+        }
+        paths.push((map, path));
+    }
+}
+
+/// Find the function that `module` imports as `name` from `import_module`.
+pub(crate) fn imported_func(
+    module: &walrus::Module,
+    import_module: &str,
+    name: &str,
+) -> Result<walrus::FunctionId, String> {
+    let import = module
+        .imports
+        .iter()
+        .find(|i| i.module == import_module && i.name == name)
+        .ok_or_else(|| format!("unknown Wasm import: \"{}\" \"{}\"", import_module, name))?;
+    match import.kind {
+        walrus::ImportKind::Function(f) => Ok(f),
+        _ => Err(format!(
+            "Wasm import \"{}\" \"{}\" is not a function",
+            import_module, name
+        )),
+    }
+}
+
+/// Find the function that `module` exports as `name`.
+pub(crate) fn exported_func(
+    module: &walrus::Module,
+    name: &str,
+) -> Result<walrus::FunctionId, String> {
+    let export = module
+        .exports
+        .iter()
+        .find(|e| e.name == name)
+        .ok_or_else(|| format!("unknown Wasm export: \"{}\"", name))?;
+    match export.item {
+        walrus::ExportItem::Function(f) => Ok(f),
+        _ => Err(format!("Wasm export \"{}\" is not a function", name)),
     }
 }
 
@@ -277,13 +544,33 @@ impl<'a> text::Actions for BuildAstActions<'a> {
     fn webidl_type_subsection(&mut self, _types: Vec<()>) {}
 
     type WebidlType = ();
-    fn webidl_type(&mut self, name: Option<&str>, ty: Id<WebidlCompoundType>) {
+    fn webidl_type(
+        &mut self,
+        name: Option<&str>,
+        ty: Id<WebidlCompoundType>,
+    ) -> Result<(), String> {
         if let Some(name) = name {
-            self.section.types.names.insert(name.to_string(), ty);
+            self.section
+                .types
+                .set_name(name.to_string(), ty)
+                .map_err(|e| e.to_string())?;
         }
+        Ok(())
+    }
+
+    fn webidl_type_alias(&mut self, name: &str, ty: WebidlTypeRef) -> Result<(), String> {
+        self.section
+            .types
+            .alias(name.to_string(), ty)
+            .map_err(|e| e.to_string())
     }
 
     type WebidlCompoundType = Id<WebidlCompoundType>;
+    fn webidl_compound_type_span(&mut self, ty: &Id<WebidlCompoundType>, start: usize, end: usize) {
+        if let Some(spans) = &mut self.spans {
+            spans.types.insert(*ty, Span { start, end });
+        }
+    }
 
     type WebidlFunction = WebidlFunctionId;
     fn webidl_function(
@@ -358,11 +645,21 @@ impl<'a> text::Actions for BuildAstActions<'a> {
     }
 
     type WebidlFunctionBindingsSubsection = ();
-    fn webidl_function_bindings_subsection(&mut self, _bindings: Vec<()>, _binds: Vec<()>) {}
+    fn webidl_function_bindings_subsection(
+        &mut self,
+        _bindings: Vec<Id<FunctionBinding>>,
+        _binds: Vec<Id<Bind>>,
+    ) {
+    }
 
-    type FunctionBinding = ();
+    type FunctionBinding = Id<FunctionBinding>;
+    fn function_binding_span(&mut self, binding: &Id<FunctionBinding>, start: usize, end: usize) {
+        if let Some(spans) = &mut self.spans {
+            spans.bindings.insert(*binding, Span { start, end });
+        }
+    }
 
-    type ImportBinding = ();
+    type ImportBinding = ImportBindingId;
     fn import_binding(
         &mut self,
         name: Option<&str>,
@@ -370,7 +667,16 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         webidl_ty: WebidlTypeRef,
         params: OutgoingBindingMap,
         result: IncomingBindingMap,
-    ) {
+    ) -> ImportBindingId {
+        let mut paths = vec![];
+        outgoing_expression_paths(
+            &params.bindings,
+            BindingMapKind::Params,
+            &mut vec![],
+            &mut paths,
+        );
+        incoming_expression_paths(&result.bindings, BindingMapKind::Result, &mut paths);
+
         let id: ImportBindingId = self.section.bindings.insert(ImportBinding {
             wasm_ty,
             webidl_ty,
@@ -383,9 +689,11 @@ impl<'a> text::Actions for BuildAstActions<'a> {
                 .names
                 .insert(name.to_string(), id.into());
         }
+        self.expression_spans(id.into(), paths);
+        id
     }
 
-    type ExportBinding = ();
+    type ExportBinding = ExportBindingId;
     fn export_binding(
         &mut self,
         name: Option<&str>,
@@ -393,7 +701,16 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         webidl_ty: WebidlTypeRef,
         params: IncomingBindingMap,
         result: OutgoingBindingMap,
-    ) {
+    ) -> ExportBindingId {
+        let mut paths = vec![];
+        incoming_expression_paths(&params.bindings, BindingMapKind::Params, &mut paths);
+        outgoing_expression_paths(
+            &result.bindings,
+            BindingMapKind::Result,
+            &mut vec![],
+            &mut paths,
+        );
+
         let id: ExportBindingId = self.section.bindings.insert(ExportBinding {
             wasm_ty,
             webidl_ty,
@@ -406,11 +723,18 @@ impl<'a> text::Actions for BuildAstActions<'a> {
                 .names
                 .insert(name.to_string(), id.into());
         }
+        self.expression_spans(id.into(), paths);
+        id
     }
 
-    type Bind = ();
-    fn bind(&mut self, func: walrus::FunctionId, binding: Id<FunctionBinding>) {
-        self.section.binds.insert(Bind { func, binding });
+    type Bind = Id<Bind>;
+    fn bind(&mut self, func: walrus::FunctionId, binding: Id<FunctionBinding>) -> Id<Bind> {
+        self.section.binds.insert(Bind { func, binding })
+    }
+    fn bind_span(&mut self, bind: &Id<Bind>, start: usize, end: usize) {
+        if let Some(spans) = &mut self.spans {
+            spans.binds.insert(*bind, Span { start, end });
+        }
     }
 
     type OutgoingBindingMap = OutgoingBindingMap;
@@ -430,6 +754,16 @@ impl<'a> text::Actions for BuildAstActions<'a> {
     }
 
     type OutgoingBindingExpression = OutgoingBindingExpression;
+    fn outgoing_binding_expression_span(
+        &mut self,
+        _expr: &OutgoingBindingExpression,
+        start: usize,
+        end: usize,
+    ) {
+        if self.spans.is_some() {
+            self.expression_spans.push(Span { start, end });
+        }
+    }
 
     type OutgoingBindingExpressionAs = OutgoingBindingExpressionAs;
     fn outgoing_binding_expression_as(
@@ -507,7 +841,26 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         OutgoingBindingExpressionBindExport { ty, binding, idx }
     }
 
+    type OutgoingBindingExpressionCustom = OutgoingBindingExpressionCustom;
+    fn outgoing_binding_expression_custom(
+        &mut self,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> OutgoingBindingExpressionCustom {
+        OutgoingBindingExpressionCustom { opcode, payload }
+    }
+
     type IncomingBindingExpression = IncomingBindingExpression;
+    fn incoming_binding_expression_span(
+        &mut self,
+        _expr: &IncomingBindingExpression,
+        start: usize,
+        end: usize,
+    ) {
+        if self.spans.is_some() {
+            self.expression_spans.push(Span { start, end });
+        }
+    }
 
     type IncomingBindingExpressionGet = IncomingBindingExpressionGet;
     fn incoming_binding_expression_get(&mut self, idx: u32) -> IncomingBindingExpressionGet {
@@ -583,11 +936,20 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         IncomingBindingExpressionBindImport { ty, binding, expr }
     }
 
+    type IncomingBindingExpressionCustom = IncomingBindingExpressionCustom;
+    fn incoming_binding_expression_custom(
+        &mut self,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> IncomingBindingExpressionCustom {
+        IncomingBindingExpressionCustom { opcode, payload }
+    }
+
     type WebidlTypeRef = WebidlTypeRef;
 
     type WebidlTypeRefNamed = WebidlTypeRef;
     fn webidl_type_ref_named(&mut self, name: &str) -> Option<WebidlTypeRef> {
-        self.section.types.by_name(name).map(Into::into)
+        self.section.types.resolve(name)
     }
 
     type WebidlTypeRefIndexed = WebidlTypeRef;
@@ -731,6 +1093,20 @@ impl<'a> text::Actions for BuildAstActions<'a> {
         self.ids.get_func(idx).ok()
     }
 
+    type WasmFuncRefImport = walrus::FunctionId;
+    fn wasm_func_ref_import(
+        &mut self,
+        module: &str,
+        name: &str,
+    ) -> Result<walrus::FunctionId, String> {
+        imported_func(self.module, module, name)
+    }
+
+    type WasmFuncRefExport = walrus::FunctionId;
+    fn wasm_func_ref_export(&mut self, name: &str) -> Result<walrus::FunctionId, String> {
+        exported_func(self.module, name)
+    }
+
     type BindingRef = Id<FunctionBinding>;
 
     type BindingRefNamed = Id<FunctionBinding>;
@@ -745,12 +1121,15 @@ impl<'a> text::Actions for BuildAstActions<'a> {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlType {
     pub name: Option<String>,
     pub ty: WebidlCompoundType,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "kebab-case"))]
 pub enum WebidlCompoundType {
     Function(WebidlFunction),
     Dictionary(WebidlDictionary),
@@ -782,14 +1161,55 @@ impl From<WebidlUnion> for WebidlCompoundType {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl WebidlCompoundType {
+    /// Get every reference to another type in this one.
+    pub(crate) fn type_refs(&self) -> Vec<WebidlTypeRef> {
+        match self {
+            WebidlCompoundType::Function(f) => {
+                let mut refs = vec![];
+                if let WebidlFunctionKind::Method(m) = &f.kind {
+                    refs.push(m.ty);
+                }
+                refs.extend(f.params.iter().cloned());
+                refs.extend(f.result);
+                refs
+            }
+            WebidlCompoundType::Dictionary(d) => d.fields.iter().map(|f| f.ty).collect(),
+            WebidlCompoundType::Enumeration(_) => vec![],
+            WebidlCompoundType::Union(u) => u.members.clone(),
+        }
+    }
+
+    /// Get a mutable reference to every reference to another type in this one.
+    pub(crate) fn type_refs_mut(&mut self) -> Vec<&mut WebidlTypeRef> {
+        match self {
+            WebidlCompoundType::Function(f) => {
+                let mut refs = vec![];
+                if let WebidlFunctionKind::Method(m) = &mut f.kind {
+                    refs.push(&mut m.ty);
+                }
+                refs.extend(f.params.iter_mut());
+                refs.extend(f.result.iter_mut());
+                refs
+            }
+            WebidlCompoundType::Dictionary(d) => d.fields.iter_mut().map(|f| &mut f.ty).collect(),
+            WebidlCompoundType::Enumeration(_) => vec![],
+            WebidlCompoundType::Union(u) => u.members.iter_mut().collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlFunction {
     pub kind: WebidlFunctionKind,
     pub params: Vec<WebidlTypeRef>,
     pub result: Option<WebidlTypeRef>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
 pub enum WebidlFunctionKind {
     Static,
     Method(WebidlFunctionKindMethod),
@@ -802,33 +1222,40 @@ impl From<WebidlFunctionKindMethod> for WebidlFunctionKind {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlFunctionKindMethod {
     pub ty: WebidlTypeRef,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlDictionary {
     pub fields: Vec<WebidlDictionaryField>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlDictionaryField {
     pub name: String,
     pub ty: WebidlTypeRef,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlEnumeration {
     pub values: Vec<String>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WebidlUnion {
     pub members: Vec<WebidlTypeRef>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
 pub enum FunctionBinding {
     Import(ImportBinding),
     Export(ExportBinding),
@@ -846,8 +1273,53 @@ impl From<ExportBinding> for FunctionBinding {
     }
 }
 
+impl FunctionBinding {
+    /// Call `types` with every reference to a Web IDL type in this binding, and
+    /// `bindings` with every reference to another function binding, including
+    /// those in nested binding expressions.
+    pub(crate) fn visit_refs(
+        &self,
+        types: &mut dyn FnMut(WebidlTypeRef),
+        bindings: &mut dyn FnMut(Id<FunctionBinding>),
+    ) {
+        let (webidl_ty, outgoing, incoming) = match self {
+            FunctionBinding::Import(i) => (i.webidl_ty, &i.params, &i.result),
+            FunctionBinding::Export(e) => (e.webidl_ty, &e.result, &e.params),
+        };
+        types(webidl_ty);
+        for expr in &outgoing.bindings {
+            expr.visit_refs(types, bindings);
+        }
+        for expr in &incoming.bindings {
+            expr.visit_refs(types, bindings);
+        }
+    }
+
+    /// Like `visit_refs`, but with mutable references, so that they can be
+    /// redirected.
+    pub(crate) fn visit_refs_mut(
+        &mut self,
+        types: &mut dyn FnMut(&mut WebidlTypeRef),
+        bindings: &mut dyn FnMut(&mut Id<FunctionBinding>),
+    ) {
+        let (webidl_ty, outgoing, incoming) = match self {
+            FunctionBinding::Import(i) => (&mut i.webidl_ty, &mut i.params, &mut i.result),
+            FunctionBinding::Export(e) => (&mut e.webidl_ty, &mut e.result, &mut e.params),
+        };
+        types(webidl_ty);
+        for expr in &mut outgoing.bindings {
+            expr.visit_refs_mut(types, bindings);
+        }
+        for expr in &mut incoming.bindings {
+            expr.visit_refs_mut(types, bindings);
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportBinding {
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::wasm_type_id"))]
     pub wasm_ty: walrus::TypeId,
     pub webidl_ty: WebidlTypeRef,
     pub params: OutgoingBindingMap,
@@ -855,7 +1327,9 @@ pub struct ImportBinding {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExportBinding {
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::wasm_type_id"))]
     pub wasm_ty: walrus::TypeId,
     pub webidl_ty: WebidlTypeRef,
     pub params: IncomingBindingMap,
@@ -863,31 +1337,94 @@ pub struct ExportBinding {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bind {
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::wasm_func_id"))]
     pub func: walrus::FunctionId,
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::binding_id"))]
     pub binding: Id<FunctionBinding>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingMap {
     pub bindings: Vec<OutgoingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingMap {
     pub bindings: Vec<IncomingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
 pub enum OutgoingBindingExpression {
     As(OutgoingBindingExpressionAs),
     Utf8Str(OutgoingBindingExpressionUtf8Str),
+    #[cfg_attr(feature = "serde", serde(rename = "utf8-cstr"))]
     Utf8CStr(OutgoingBindingExpressionUtf8CStr),
     I32ToEnum(OutgoingBindingExpressionI32ToEnum),
     View(OutgoingBindingExpressionView),
     Copy(OutgoingBindingExpressionCopy),
     Dict(OutgoingBindingExpressionDict),
     BindExport(OutgoingBindingExpressionBindExport),
+    Custom(OutgoingBindingExpressionCustom),
+}
+
+impl OutgoingBindingExpression {
+    fn visit_refs(
+        &self,
+        types: &mut dyn FnMut(WebidlTypeRef),
+        bindings: &mut dyn FnMut(Id<FunctionBinding>),
+    ) {
+        match self {
+            OutgoingBindingExpression::As(e) => types(e.ty),
+            OutgoingBindingExpression::Utf8Str(e) => types(e.ty),
+            OutgoingBindingExpression::Utf8CStr(e) => types(e.ty),
+            OutgoingBindingExpression::I32ToEnum(e) => types(e.ty),
+            OutgoingBindingExpression::View(e) => types(e.ty),
+            OutgoingBindingExpression::Copy(e) => types(e.ty),
+            OutgoingBindingExpression::Dict(e) => {
+                types(e.ty);
+                for field in &e.fields {
+                    field.visit_refs(types, bindings);
+                }
+            }
+            OutgoingBindingExpression::BindExport(e) => {
+                types(e.ty);
+                bindings(e.binding);
+            }
+            OutgoingBindingExpression::Custom(_) => {}
+        }
+    }
+
+    fn visit_refs_mut(
+        &mut self,
+        types: &mut dyn FnMut(&mut WebidlTypeRef),
+        bindings: &mut dyn FnMut(&mut Id<FunctionBinding>),
+    ) {
+        match self {
+            OutgoingBindingExpression::As(e) => types(&mut e.ty),
+            OutgoingBindingExpression::Utf8Str(e) => types(&mut e.ty),
+            OutgoingBindingExpression::Utf8CStr(e) => types(&mut e.ty),
+            OutgoingBindingExpression::I32ToEnum(e) => types(&mut e.ty),
+            OutgoingBindingExpression::View(e) => types(&mut e.ty),
+            OutgoingBindingExpression::Copy(e) => types(&mut e.ty),
+            OutgoingBindingExpression::Dict(e) => {
+                types(&mut e.ty);
+                for field in &mut e.fields {
+                    field.visit_refs_mut(types, bindings);
+                }
+            }
+            OutgoingBindingExpression::BindExport(e) => {
+                types(&mut e.ty);
+                bindings(&mut e.binding);
+            }
+            OutgoingBindingExpression::Custom(_) => {}
+        }
+    }
 }
 
 impl From<OutgoingBindingExpressionAs> for OutgoingBindingExpression {
@@ -938,13 +1475,21 @@ impl From<OutgoingBindingExpressionBindExport> for OutgoingBindingExpression {
     }
 }
 
+impl From<OutgoingBindingExpressionCustom> for OutgoingBindingExpression {
+    fn from(s: OutgoingBindingExpressionCustom) -> Self {
+        OutgoingBindingExpression::Custom(s)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionAs {
     pub ty: WebidlTypeRef,
     pub idx: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionUtf8Str {
     pub ty: WebidlTypeRef,
     pub offset: u32,
@@ -952,18 +1497,21 @@ pub struct OutgoingBindingExpressionUtf8Str {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionUtf8CStr {
     pub ty: WebidlTypeRef,
     pub offset: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionI32ToEnum {
     pub ty: WebidlTypeRef,
     pub idx: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionView {
     pub ty: WebidlTypeRef,
     pub offset: u32,
@@ -971,6 +1519,7 @@ pub struct OutgoingBindingExpressionView {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionCopy {
     pub ty: WebidlTypeRef,
     pub offset: u32,
@@ -978,19 +1527,36 @@ pub struct OutgoingBindingExpressionCopy {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionDict {
     pub ty: WebidlTypeRef,
     pub fields: Vec<OutgoingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OutgoingBindingExpressionBindExport {
     pub ty: WebidlTypeRef,
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::binding_id"))]
     pub binding: Id<FunctionBinding>,
     pub idx: u32,
 }
 
+/// An experimental binding expression that isn't part of the proposal.
+///
+/// Its `opcode` must be one of the opcodes from 128 to 255, which are reserved
+/// for custom expressions, and its `payload` is opaque to this crate, so it is
+/// encoded, decoded and printed as is, without being validated.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutgoingBindingExpressionCustom {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "kebab-case"))]
 pub enum IncomingBindingExpression {
     Get(IncomingBindingExpressionGet),
     As(IncomingBindingExpressionAs),
@@ -999,6 +1565,79 @@ pub enum IncomingBindingExpression {
     EnumToI32(IncomingBindingExpressionEnumToI32),
     Field(IncomingBindingExpressionField),
     BindImport(IncomingBindingExpressionBindImport),
+    Custom(IncomingBindingExpressionCustom),
+}
+
+impl IncomingBindingExpression {
+    /// Get the expression nested inside this one, if any.
+    pub fn nested(&self) -> Option<&IncomingBindingExpression> {
+        match self {
+            IncomingBindingExpression::Get(_) => None,
+            IncomingBindingExpression::As(e) => Some(&e.expr),
+            IncomingBindingExpression::AllocUtf8Str(e) => Some(&e.expr),
+            IncomingBindingExpression::AllocCopy(e) => Some(&e.expr),
+            IncomingBindingExpression::EnumToI32(e) => Some(&e.expr),
+            IncomingBindingExpression::Field(e) => Some(&e.expr),
+            IncomingBindingExpression::BindImport(e) => Some(&e.expr),
+            IncomingBindingExpression::Custom(_) => None,
+        }
+    }
+
+    /// Get the expression nested inside this one, if any, mutably.
+    pub fn nested_mut(&mut self) -> Option<&mut IncomingBindingExpression> {
+        match self {
+            IncomingBindingExpression::Get(_) => None,
+            IncomingBindingExpression::As(e) => Some(&mut e.expr),
+            IncomingBindingExpression::AllocUtf8Str(e) => Some(&mut e.expr),
+            IncomingBindingExpression::AllocCopy(e) => Some(&mut e.expr),
+            IncomingBindingExpression::EnumToI32(e) => Some(&mut e.expr),
+            IncomingBindingExpression::Field(e) => Some(&mut e.expr),
+            IncomingBindingExpression::BindImport(e) => Some(&mut e.expr),
+            IncomingBindingExpression::Custom(_) => None,
+        }
+    }
+
+    fn visit_refs(
+        &self,
+        types: &mut dyn FnMut(WebidlTypeRef),
+        bindings: &mut dyn FnMut(Id<FunctionBinding>),
+    ) {
+        match self {
+            IncomingBindingExpression::EnumToI32(e) => types(e.ty),
+            IncomingBindingExpression::BindImport(e) => bindings(e.binding),
+            _ => {}
+        }
+        if let Some(expr) = self.nested() {
+            expr.visit_refs(types, bindings);
+        }
+    }
+
+    fn visit_refs_mut(
+        &mut self,
+        types: &mut dyn FnMut(&mut WebidlTypeRef),
+        bindings: &mut dyn FnMut(&mut Id<FunctionBinding>),
+    ) {
+        match self {
+            IncomingBindingExpression::EnumToI32(e) => types(&mut e.ty),
+            IncomingBindingExpression::BindImport(e) => bindings(&mut e.binding),
+            _ => {}
+        }
+        if let Some(expr) = self.nested_mut() {
+            expr.visit_refs_mut(types, bindings);
+        }
+    }
+
+    fn remap_types<T>(&mut self, types: &T)
+    where
+        T: Fn(walrus::TypeId) -> walrus::TypeId,
+    {
+        if let IncomingBindingExpression::BindImport(e) = self {
+            e.ty = types(e.ty);
+        }
+        if let Some(expr) = self.nested_mut() {
+            expr.remap_types(types);
+        }
+    }
 }
 
 impl From<IncomingBindingExpressionGet> for IncomingBindingExpression {
@@ -1043,49 +1682,75 @@ impl From<IncomingBindingExpressionBindImport> for IncomingBindingExpression {
     }
 }
 
+impl From<IncomingBindingExpressionCustom> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionCustom) -> Self {
+        IncomingBindingExpression::Custom(a)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionGet {
     pub idx: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionAs {
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::val_type"))]
     pub ty: walrus::ValType,
     pub expr: Box<IncomingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionAllocUtf8Str {
     pub alloc_func_name: String,
     pub expr: Box<IncomingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionAllocCopy {
     pub alloc_func_name: String,
     pub expr: Box<IncomingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionEnumToI32 {
     pub ty: WebidlTypeRef,
     pub expr: Box<IncomingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionField {
     pub idx: u32,
     pub expr: Box<IncomingBindingExpression>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingBindingExpressionBindImport {
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::wasm_type_id"))]
     pub ty: walrus::TypeId,
+    #[cfg_attr(feature = "serde", serde(with = "crate::interchange::binding_id"))]
     pub binding: Id<FunctionBinding>,
     pub expr: Box<IncomingBindingExpression>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Like `OutgoingBindingExpressionCustom`, but for an incoming binding
+/// expression. Its payload can't contain nested expressions that this crate
+/// knows about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IncomingBindingExpressionCustom {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum WebidlTypeRef {
     Id(Id<WebidlCompoundType>),
     Scalar(WebidlScalarType),
@@ -1103,7 +1768,7 @@ impl From<Id<WebidlCompoundType>> for WebidlTypeRef {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum WebidlScalarType {
     Any,
     Boolean,
@@ -0,0 +1,304 @@
+use crate::ast::*;
+use crate::spans::{render_snippet, Spans};
+use id_arena::Id;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// An error for a section that can't be encoded, because one of its items
+/// refers to something that doesn't exist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodeError {
+    /// The item with the dangling reference.
+    pub item: EncodeErrorItem,
+    /// What the item refers to.
+    pub dangling: DanglingRef,
+}
+
+/// An item of a section, as named in an `EncodeError`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodeErrorItem {
+    /// The Web IDL type with the given index and ID, and its name, if it has
+    /// one.
+    WebidlType {
+        index: u32,
+        id: Id<WebidlCompoundType>,
+        name: Option<String>,
+    },
+    /// The function binding with the given index and ID, and its name, if it
+    /// has one.
+    FunctionBinding {
+        index: u32,
+        id: Id<FunctionBinding>,
+        name: Option<String>,
+    },
+    /// The bind with the given index and ID.
+    Bind { index: u32, id: Id<Bind> },
+    /// The Web IDL type name or alias with the given name.
+    Name(String),
+}
+
+/// A reference to something that isn't in the section, or that isn't in the
+/// Wasm module that the section is encoded for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DanglingRef {
+    /// A Web IDL type from a different `WebidlBindings`.
+    WebidlType(Id<WebidlCompoundType>),
+    /// A function binding from a different `WebidlBindings`.
+    FunctionBinding(Id<FunctionBinding>),
+    /// A Wasm function that isn't in the module, for example because it was
+    /// deleted.
+    WasmFunction(walrus::FunctionId),
+    /// A Wasm function type that isn't in the module.
+    WasmFunctionType(walrus::TypeId),
+}
+
+impl EncodeError {
+    /// Render this error along with a snippet of the text format `input` that
+    /// the item with the dangling reference was parsed from, using the `spans`
+    /// recorded by `text::parse_with_spans`.
+    ///
+    /// If `spans` doesn't have a span for the item, as for a Web IDL type name
+    /// or alias, then only the message is rendered.
+    pub fn render(&self, input: &str, spans: &Spans) -> String {
+        let span = match &self.item {
+            EncodeErrorItem::WebidlType { id, .. } => spans.webidl_type(*id),
+            EncodeErrorItem::FunctionBinding { id, .. } => spans.binding(*id),
+            EncodeErrorItem::Bind { id, .. } => spans.bind(*id),
+            EncodeErrorItem::Name(_) => None,
+        };
+        match span {
+            Some(span) => render_snippet(input, span, &self.to_string()),
+            None => format!("error: {}", self),
+        }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.item {
+            EncodeErrorItem::WebidlType { index, name, .. } => {
+                write!(f, "Web IDL type {}", index)?;
+                if let Some(name) = name {
+                    write!(f, " ({})", name)?;
+                }
+            }
+            EncodeErrorItem::FunctionBinding { index, name, .. } => {
+                write!(f, "function binding {}", index)?;
+                if let Some(name) = name {
+                    write!(f, " ({})", name)?;
+                }
+            }
+            EncodeErrorItem::Bind { index, .. } => write!(f, "bind {}", index)?,
+            EncodeErrorItem::Name(name) => write!(f, "the name {}", name)?,
+        }
+        match self.dangling {
+            DanglingRef::WebidlType(_) => {
+                write!(f, " refers to a Web IDL type that isn't in the section")
+            }
+            DanglingRef::FunctionBinding(_) => {
+                write!(f, " refers to a function binding that isn't in the section")
+            }
+            DanglingRef::WasmFunction(_) => {
+                write!(f, " refers to a Wasm function that isn't in the module")
+            }
+            DanglingRef::WasmFunctionType(_) => {
+                write!(
+                    f,
+                    " refers to a Wasm function type that isn't in the module"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Find every dangling reference in `section`. References to Wasm functions and
+/// function types are only checked if `module` is given.
+pub(crate) fn check(section: &WebidlBindings, module: Option<&walrus::Module>) -> Vec<EncodeError> {
+    let mut checker = Checker {
+        section,
+        funcs: module.map(|m| m.funcs.iter().map(|f| f.id()).collect()),
+        types: module.map(|m| m.types.iter().map(|t| t.id()).collect()),
+        item: EncodeErrorItem::Name(String::new()),
+        errors: vec![],
+    };
+
+    let type_names = invert(&section.types.names);
+    for (index, (id, ty)) in section.types.arena.iter().enumerate() {
+        checker.item = EncodeErrorItem::WebidlType {
+            index: index as u32,
+            id,
+            name: type_names.get(&id).map(|s| s.to_string()),
+        };
+        checker.webidl_type(ty);
+    }
+
+    let binding_names = invert(&section.bindings.names);
+    for (index, (id, binding)) in section.bindings.arena.iter().enumerate() {
+        checker.item = EncodeErrorItem::FunctionBinding {
+            index: index as u32,
+            id,
+            name: binding_names.get(&id).map(|s| s.to_string()),
+        };
+        checker.function_binding(binding);
+    }
+
+    for (index, (id, bind)) in section.binds.iter().enumerate() {
+        checker.item = EncodeErrorItem::Bind {
+            index: index as u32,
+            id,
+        };
+        checker.wasm_func(bind.func);
+        checker.binding_ref(bind.binding);
+    }
+
+    // Sort the names, so that errors don't come in hash map order.
+    let mut names: Vec<_> = section
+        .types
+        .names
+        .iter()
+        .map(|(name, id)| (name.as_str(), WebidlTypeRef::Id(*id)))
+        .chain(section.types.aliases())
+        .collect();
+    names.sort_by_key(|(name, _)| *name);
+    for (name, ty) in names {
+        checker.item = EncodeErrorItem::Name(name.to_string());
+        checker.webidl_type_ref(ty);
+    }
+
+    checker.errors
+}
+
+fn invert<T>(names: &HashMap<String, Id<T>>) -> HashMap<Id<T>, &str> {
+    names
+        .iter()
+        .map(|(name, id)| (*id, name.as_str()))
+        .collect()
+}
+
+struct Checker<'a> {
+    section: &'a WebidlBindings,
+    funcs: Option<HashSet<walrus::FunctionId>>,
+    types: Option<HashSet<walrus::TypeId>>,
+    item: EncodeErrorItem,
+    errors: Vec<EncodeError>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, dangling: DanglingRef) {
+        self.errors.push(EncodeError {
+            item: self.item.clone(),
+            dangling,
+        });
+    }
+
+    fn webidl_type(&mut self, ty: &WebidlCompoundType) {
+        match ty {
+            WebidlCompoundType::Function(f) => {
+                if let WebidlFunctionKind::Method(m) = &f.kind {
+                    self.webidl_type_ref(m.ty);
+                }
+                for ty in f.params.iter().chain(&f.result) {
+                    self.webidl_type_ref(*ty);
+                }
+            }
+            WebidlCompoundType::Dictionary(d) => {
+                for field in &d.fields {
+                    self.webidl_type_ref(field.ty);
+                }
+            }
+            WebidlCompoundType::Enumeration(_) => {}
+            WebidlCompoundType::Union(u) => {
+                for ty in &u.members {
+                    self.webidl_type_ref(*ty);
+                }
+            }
+        }
+    }
+
+    fn webidl_type_ref(&mut self, ty: WebidlTypeRef) {
+        if let WebidlTypeRef::Id(id) = ty {
+            if self.section.types.arena.get(id).is_none() {
+                self.error(DanglingRef::WebidlType(id));
+            }
+        }
+    }
+
+    fn binding_ref(&mut self, id: Id<FunctionBinding>) {
+        if self.section.bindings.arena.get(id).is_none() {
+            self.error(DanglingRef::FunctionBinding(id));
+        }
+    }
+
+    fn wasm_func(&mut self, id: walrus::FunctionId) {
+        if self
+            .funcs
+            .as_ref()
+            .map_or(false, |funcs| !funcs.contains(&id))
+        {
+            self.error(DanglingRef::WasmFunction(id));
+        }
+    }
+
+    fn wasm_func_type(&mut self, id: walrus::TypeId) {
+        if self
+            .types
+            .as_ref()
+            .map_or(false, |types| !types.contains(&id))
+        {
+            self.error(DanglingRef::WasmFunctionType(id));
+        }
+    }
+
+    fn function_binding(&mut self, binding: &FunctionBinding) {
+        match binding {
+            FunctionBinding::Import(i) => {
+                self.wasm_func_type(i.wasm_ty);
+                self.webidl_type_ref(i.webidl_ty);
+                i.params.bindings.iter().for_each(|e| self.outgoing(e));
+                i.result.bindings.iter().for_each(|e| self.incoming(e));
+            }
+            FunctionBinding::Export(e) => {
+                self.wasm_func_type(e.wasm_ty);
+                self.webidl_type_ref(e.webidl_ty);
+                e.params.bindings.iter().for_each(|e| self.incoming(e));
+                e.result.bindings.iter().for_each(|e| self.outgoing(e));
+            }
+        }
+    }
+
+    fn outgoing(&mut self, expr: &OutgoingBindingExpression) {
+        match expr {
+            OutgoingBindingExpression::As(e) => self.webidl_type_ref(e.ty),
+            OutgoingBindingExpression::Utf8Str(e) => self.webidl_type_ref(e.ty),
+            OutgoingBindingExpression::Utf8CStr(e) => self.webidl_type_ref(e.ty),
+            OutgoingBindingExpression::I32ToEnum(e) => self.webidl_type_ref(e.ty),
+            OutgoingBindingExpression::View(e) => self.webidl_type_ref(e.ty),
+            OutgoingBindingExpression::Copy(e) => self.webidl_type_ref(e.ty),
+            OutgoingBindingExpression::Dict(e) => {
+                self.webidl_type_ref(e.ty);
+                e.fields.iter().for_each(|e| self.outgoing(e));
+            }
+            OutgoingBindingExpression::BindExport(e) => {
+                self.webidl_type_ref(e.ty);
+                self.binding_ref(e.binding);
+            }
+            OutgoingBindingExpression::Custom(_) => {}
+        }
+    }
+
+    fn incoming(&mut self, expr: &IncomingBindingExpression) {
+        match expr {
+            IncomingBindingExpression::EnumToI32(e) => self.webidl_type_ref(e.ty),
+            IncomingBindingExpression::BindImport(e) => {
+                self.wasm_func_type(e.ty);
+                self.binding_ref(e.binding);
+            }
+            _ => {}
+        }
+        if let Some(expr) = expr.nested() {
+            self.incoming(expr);
+        }
+    }
+}
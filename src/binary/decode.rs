@@ -0,0 +1,1339 @@
+use super::dump::MAX_DEPTH;
+use super::{
+    CUSTOM_EXPRESSION_OPCODES, FEATURE_NAMES, FEATURE_STRING_TABLE, KNOWN_FEATURES,
+    LAST_KNOWN_SUBSECTION, OPTIONAL_FEATURES, VERSION,
+};
+use crate::ast::*;
+use crate::unresolved;
+use id_arena::{ArenaBehavior, DefaultArenaBehavior, Id};
+use std::sync::Arc;
+
+pub(crate) struct DecodeContext<'a, I> {
+    ids: &'a I,
+    // The IDs that the Web IDL types and function bindings will have once they
+    // are inserted, so that they can be referred to before they are decoded.
+    webidl_types: Vec<Id<WebidlCompoundType>>,
+    bindings: Vec<Id<FunctionBinding>>,
+    // How deeply nested the binding expression being decoded is.
+    depth: usize,
+}
+
+// Factor this out into a trait to make testing easier.
+pub(crate) trait Ids {
+    fn wasm_func_id(&self, idx: u32) -> Option<walrus::FunctionId>;
+    fn wasm_func_type_id(&self, idx: u32) -> Option<walrus::TypeId>;
+}
+
+impl Ids for walrus::IndicesToIds {
+    fn wasm_func_id(&self, idx: u32) -> Option<walrus::FunctionId> {
+        self.get_func(idx).ok()
+    }
+
+    fn wasm_func_type_id(&self, idx: u32) -> Option<walrus::TypeId> {
+        self.get_type(idx).ok()
+    }
+}
+
+impl<'a, I> DecodeContext<'a, I> {
+    pub fn new(ids: &'a I) -> DecodeContext<'a, I> {
+        DecodeContext {
+            ids,
+            webidl_types: vec![],
+            bindings: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Decode the binding expressions nested inside another one, failing once
+    /// they are nested more than `MAX_DEPTH` deep rather than overflowing the
+    /// stack.
+    fn nested<T>(&mut self, r: &mut Reader) -> Result<T, failure::Error>
+    where
+        T: Decode<Self>,
+    {
+        if self.depth >= MAX_DEPTH {
+            return r.error(r.pos, "too deeply nested");
+        }
+        self.depth += 1;
+        let result = T::decode(self, r);
+        self.depth -= 1;
+        result
+    }
+}
+
+/// Get the IDs that the next `n` items allocated in `arena` will have.
+fn next_ids<T>(arena: &id_arena::Arena<T>, n: u32) -> Vec<Id<T>> {
+    let next = arena.next_id();
+    let arena_id = DefaultArenaBehavior::<T>::arena_id(next);
+    (0..n as usize)
+        .map(|i| DefaultArenaBehavior::<T>::new_id(arena_id, next.index() + i))
+        .collect()
+}
+
+/// A cursor over the bytes of an encoded section.
+#[derive(Clone, Debug)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // The features from the section's header.
+    features: u32,
+    // Once the string table subsection has been read, strings are indices
+    // into it.
+    strings: Option<Arc<[&'a str]>>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data,
+            pos: 0,
+            features: 0,
+            strings: None,
+        }
+    }
+
+    pub fn error<T>(&self, at: usize, message: &str) -> Result<T, failure::Error> {
+        Err(failure::format_err!("{} at offset {}", message, at))
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    pub fn byte(&mut self) -> Result<u8, failure::Error> {
+        match self.peek() {
+            Some(b) => {
+                self.pos += 1;
+                Ok(b)
+            }
+            None => self.error(self.pos, "unexpected end of section"),
+        }
+    }
+
+    pub fn uleb(&mut self) -> Result<u32, failure::Error> {
+        let start = self.pos;
+        let mut rest = &self.data[self.pos..];
+        let val = match leb128::read::unsigned(&mut rest) {
+            Ok(val) => val,
+            Err(leb128::read::Error::IoError(_)) => {
+                return self.error(self.data.len(), "unexpected end of section")
+            }
+            Err(leb128::read::Error::Overflow) => return self.error(start, "LEB128 overflow"),
+        };
+        self.pos = self.data.len() - rest.len();
+        if val > u64::from(u32::MAX) {
+            return self.error(start, "integer too large");
+        }
+        Ok(val as u32)
+    }
+
+    pub fn ileb(&mut self) -> Result<i32, failure::Error> {
+        let start = self.pos;
+        let mut rest = &self.data[self.pos..];
+        let val = match leb128::read::signed(&mut rest) {
+            Ok(val) => val,
+            Err(leb128::read::Error::IoError(_)) => {
+                return self.error(self.data.len(), "unexpected end of section")
+            }
+            Err(leb128::read::Error::Overflow) => return self.error(start, "LEB128 overflow"),
+        };
+        self.pos = self.data.len() - rest.len();
+        if val < i64::from(i32::MIN) || val > i64::from(i32::MAX) {
+            return self.error(start, "integer too large");
+        }
+        Ok(val as i32)
+    }
+
+    /// Read a string, borrowing it from the section. This is an index into the
+    /// string table if there is one, and an inline string otherwise.
+    pub fn str(&mut self) -> Result<&'a str, failure::Error> {
+        self.string_ref().map(|(_, s)| s)
+    }
+
+    /// Like `str`, but also get the string's index in the string table, if
+    /// there is one.
+    pub fn string_ref(&mut self) -> Result<(Option<u32>, &'a str), failure::Error> {
+        let strings = match &self.strings {
+            Some(strings) => strings.clone(),
+            None => return self.inline_str().map(|s| (None, s)),
+        };
+        let at = self.pos;
+        let idx = self.uleb()?;
+        match strings.get(idx as usize) {
+            Some(s) => Ok((Some(idx), s)),
+            None => self.error(at, &format!("unknown string index {}", idx)),
+        }
+    }
+
+    /// Read the version from the section's header, rejecting versions that
+    /// this crate doesn't know how to decode.
+    pub fn version(&mut self) -> Result<u32, failure::Error> {
+        let at = self.pos;
+        let version = self.uleb()?;
+        if version == 0 || version > VERSION {
+            return self.error(
+                at,
+                &format!(
+                    "unsupported version {} (the newest supported version is {})",
+                    version, VERSION
+                ),
+            );
+        }
+        Ok(version)
+    }
+
+    /// Read the feature flags from the section's header. Unknown required
+    /// features are an error, while unknown optional features are ignored, so
+    /// only the known features are returned.
+    pub fn features(&mut self) -> Result<u32, failure::Error> {
+        let at = self.pos;
+        let features = self.uleb()?;
+        let unsupported = features & !KNOWN_FEATURES & !OPTIONAL_FEATURES;
+        if unsupported != 0 {
+            return self.error(at, &format!("unsupported features {:#x}", unsupported));
+        }
+        self.features = features;
+        Ok(features & KNOWN_FEATURES)
+    }
+
+    /// The optional features from the section's header that aren't known.
+    pub fn unknown_features(&self) -> u32 {
+        self.features & !KNOWN_FEATURES
+    }
+
+    /// Read the section's header.
+    pub fn header(&mut self) -> Result<(), failure::Error> {
+        self.version()?;
+        self.features()?;
+        Ok(())
+    }
+
+    /// Whether the section's header has the given feature.
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.features & feature != 0
+    }
+
+    /// Read the id of an optional subsection, which is present exactly when
+    /// the header has the given feature. Returns whether it is present.
+    pub fn feature_subsection(
+        &mut self,
+        feature: u32,
+        id: u8,
+        what: &str,
+    ) -> Result<bool, failure::Error> {
+        if !self.has_feature(feature) {
+            return Ok(false);
+        }
+        let at = self.pos;
+        if self.byte()? != id {
+            return self.error(at, &format!("expected the {}", what));
+        }
+        Ok(true)
+    }
+
+    /// Read the string table subsection if the header says that there is one,
+    /// after which strings are read as indices into it.
+    pub fn string_table_subsection(&mut self) -> Result<(), failure::Error> {
+        if !self.feature_subsection(FEATURE_STRING_TABLE, 3, "string table subsection")? {
+            return Ok(());
+        }
+        let end = self.subsection_size()?;
+        let len = self.uleb()?;
+        let mut strings = Vec::with_capacity((len as usize).min(self.data.len() - self.pos));
+        for _ in 0..len {
+            strings.push(self.inline_str()?);
+        }
+        self.subsection_end(end, "string table subsection")?;
+        self.set_string_table(strings);
+        Ok(())
+    }
+
+    /// Read the size of a subsection's contents, returning the offset where
+    /// they end.
+    pub fn subsection_size(&mut self) -> Result<usize, failure::Error> {
+        let size = self.uleb()? as usize;
+        if self.data.len() - self.pos < size {
+            return self.error(self.data.len(), "unexpected end of section");
+        }
+        Ok(self.pos + size)
+    }
+
+    /// Check that the contents of a subsection end at `end`, as its size said.
+    pub fn subsection_end(&self, end: usize, what: &str) -> Result<(), failure::Error> {
+        if self.pos != end {
+            return self.error(self.pos, &format!("the {} doesn't match its size", what));
+        }
+        Ok(())
+    }
+
+    pub fn set_string_table(&mut self, strings: Vec<&'a str>) {
+        self.strings = Some(strings.into());
+    }
+
+    /// Read a length-prefixed UTF-8 string, borrowing it from the section.
+    pub fn inline_str(&mut self) -> Result<&'a str, failure::Error> {
+        let len = self.uleb()? as usize;
+        let start = self.pos;
+        match std::str::from_utf8(self.bytes(len)?) {
+            Ok(s) => Ok(s),
+            Err(_) => self.error(start, "invalid UTF-8 string"),
+        }
+    }
+
+    /// Read `len` raw bytes, borrowing them from the section.
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], failure::Error> {
+        let start = self.pos;
+        if self.data.len() - start < len {
+            return self.error(self.data.len(), "unexpected end of section");
+        }
+        self.pos += len;
+        Ok(&self.data[start..self.pos])
+    }
+
+    /// Read a vector of raw bytes, prefixed with its length, borrowing them from
+    /// the section.
+    pub fn byte_vec(&mut self) -> Result<&'a [u8], failure::Error> {
+        let len = self.uleb()?;
+        self.bytes(len as usize)
+    }
+
+    /// Read a subsection with an unknown id, whose contents are prefixed with
+    /// their size, so that it can be skipped. Returns its id and contents.
+    pub fn unknown_subsection(&mut self) -> Result<(u8, &'a [u8]), failure::Error> {
+        let at = self.pos;
+        let id = self.byte()?;
+        if id <= LAST_KNOWN_SUBSECTION {
+            return self.error(at, "unexpected data after the last subsection");
+        }
+        let len = self.uleb()? as usize;
+        Ok((id, self.bytes(len)?))
+    }
+
+    pub fn vec<Cx, T>(&mut self, cx: &mut Cx) -> Result<Vec<T>, failure::Error>
+    where
+        T: Decode<Cx>,
+    {
+        let len = self.uleb()?;
+        // Don't trust `len` to preallocate, since each item is at least a byte.
+        let mut items = Vec::with_capacity((len as usize).min(self.data.len() - self.pos));
+        for _ in 0..len {
+            items.push(T::decode(cx, self)?);
+        }
+        Ok(items)
+    }
+}
+
+pub(crate) trait Decode<Cx>: Sized {
+    fn decode(cx: &mut Cx, r: &mut Reader) -> Result<Self, failure::Error>;
+}
+
+impl<Cx> Decode<Cx> for String {
+    fn decode(_cx: &mut Cx, r: &mut Reader) -> Result<Self, failure::Error> {
+        r.str().map(|s| s.to_string())
+    }
+}
+
+impl<Cx, T> Decode<Cx> for Vec<T>
+where
+    T: Decode<Cx>,
+{
+    fn decode(cx: &mut Cx, r: &mut Reader) -> Result<Self, failure::Error> {
+        r.vec(cx)
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for WebidlBindings
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let mut section = WebidlBindings::default();
+
+        r.header()?;
+        let extensions = &mut section.extensions;
+        extensions.features = r.unknown_features();
+        decode_extensions(r, SubsectionPosition::AfterHeader, extensions)?;
+
+        // String Table Subsection, which is optional.
+        r.string_table_subsection()?;
+        decode_extensions(r, SubsectionPosition::AfterStringTable, extensions)?;
+
+        // Web IDL Type Subsection, which is optional.
+        decode_types(cx, r, &mut section.types)?;
+        let extensions = &mut section.extensions;
+        decode_extensions(r, SubsectionPosition::AfterTypes, extensions)?;
+
+        // Web IDL Function Binding Subsection.
+        let at = r.pos;
+        if r.byte()? != 1 {
+            return r.error(at, "expected the function binding subsection");
+        }
+
+        // Bindings.
+        let len = r.uleb()?;
+        cx.bindings = next_ids(&section.bindings.arena, len);
+        for _ in 0..len {
+            match FunctionBinding::decode(cx, r)? {
+                FunctionBinding::Import(b) => {
+                    section.bindings.insert(b);
+                }
+                FunctionBinding::Export(b) => {
+                    section.bindings.insert(b);
+                }
+            }
+        }
+
+        // Binds.
+        for bind in r.vec::<_, Bind>(cx)? {
+            section.binds.insert(bind);
+        }
+        decode_extensions(
+            r,
+            SubsectionPosition::AfterBindings,
+            &mut section.extensions,
+        )?;
+
+        // Names Subsection, which is optional.
+        for (idx, name) in decode_names(cx, r, &mut section.types)? {
+            section.bindings.names.insert(name, cx.bindings[idx as usize]);
+        }
+
+        decode_extensions(r, SubsectionPosition::AfterNames, &mut section.extensions)?;
+        Ok(section)
+    }
+}
+
+/// Decode the unknown subsections at `position` into `extensions`. After the
+/// names subsection, everything up to the end of the section must be unknown
+/// subsections.
+fn decode_extensions(
+    r: &mut Reader,
+    position: SubsectionPosition,
+    extensions: &mut Extensions,
+) -> Result<(), failure::Error> {
+    loop {
+        let unknown = match r.peek() {
+            None => false,
+            Some(_) if position == SubsectionPosition::AfterNames => true,
+            Some(id) => id > LAST_KNOWN_SUBSECTION,
+        };
+        if !unknown {
+            return Ok(());
+        }
+        let (id, data) = r.unknown_subsection()?;
+        extensions.subsections.push(UnknownSubsection {
+            id,
+            data: data.to_vec(),
+            position,
+        });
+    }
+}
+
+/// Decode the optional Web IDL type subsection into `types`.
+fn decode_types<I>(
+    cx: &mut DecodeContext<I>,
+    r: &mut Reader,
+    types: &mut WebidlTypes,
+) -> Result<(), failure::Error> {
+    if r.peek() != Some(0) {
+        return Ok(());
+    }
+    r.byte()?;
+    let len = r.uleb()?;
+    cx.webidl_types = next_ids(&types.arena, len);
+    for _ in 0..len {
+        match WebidlCompoundType::decode(cx, r)? {
+            WebidlCompoundType::Function(ty) => {
+                types.insert(ty);
+            }
+            WebidlCompoundType::Dictionary(ty) => {
+                types.insert(ty);
+            }
+            WebidlCompoundType::Enumeration(ty) => {
+                types.insert(ty);
+            }
+            WebidlCompoundType::Union(ty) => {
+                types.insert(ty);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode the optional names subsection, adding the names of Web IDL types and
+/// type aliases to `types`, and returning the names of function bindings by
+/// index.
+fn decode_names<I>(
+    cx: &mut DecodeContext<I>,
+    r: &mut Reader,
+    types: &mut WebidlTypes,
+) -> Result<Vec<(u32, String)>, failure::Error> {
+    if !r.feature_subsection(FEATURE_NAMES, 2, "names subsection")? {
+        return Ok(vec![]);
+    }
+    let end = r.subsection_size()?;
+
+    for _ in 0..r.uleb()? {
+        let at = r.pos;
+        let idx = r.uleb()?;
+        let id = match cx.webidl_types.get(idx as usize) {
+            Some(id) => *id,
+            None => return r.error(at, &format!("unknown Web IDL type index {}", idx)),
+        };
+        types.names.insert(String::decode(cx, r)?, id);
+    }
+
+    let mut bindings = vec![];
+    for _ in 0..r.uleb()? {
+        let at = r.pos;
+        let idx = r.uleb()?;
+        if idx as usize >= cx.bindings.len() {
+            return r.error(at, &format!("unknown function binding index {}", idx));
+        }
+        bindings.push((idx, String::decode(cx, r)?));
+    }
+
+    for _ in 0..r.uleb()? {
+        let at = r.pos;
+        let name = String::decode(cx, r)?;
+        let ty = WebidlTypeRef::decode(cx, r)?;
+        if let Err(e) = types.alias(name, ty) {
+            return r.error(at, &e.to_string());
+        }
+    }
+    r.subsection_end(end, "names subsection")?;
+    Ok(bindings)
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for WebidlCompoundType {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => WebidlCompoundType::Function(WebidlFunction::decode(cx, r)?),
+            1 => WebidlCompoundType::Dictionary(WebidlDictionary { fields: r.vec(cx)? }),
+            2 => WebidlCompoundType::Enumeration(WebidlEnumeration { values: r.vec(cx)? }),
+            3 => WebidlCompoundType::Union(WebidlUnion {
+                members: r.vec(cx)?,
+            }),
+            _ => return r.error(at, "invalid Web IDL type"),
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for WebidlFunction {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        let kind = match r.byte()? {
+            0 => WebidlFunctionKind::Static,
+            1 => WebidlFunctionKind::Method(WebidlFunctionKindMethod {
+                ty: WebidlTypeRef::decode(cx, r)?,
+            }),
+            2 => WebidlFunctionKind::Constructor,
+            _ => return r.error(at, "invalid Web IDL function kind"),
+        };
+        let params = r.vec(cx)?;
+        let at = r.pos;
+        let result = match r.byte()? {
+            0 => None,
+            1 => Some(WebidlTypeRef::decode(cx, r)?),
+            _ => return r.error(at, "invalid Web IDL function result flag"),
+        };
+        Ok(WebidlFunction {
+            kind,
+            params,
+            result,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for WebidlTypeRef {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        let val = r.ileb()?;
+        if val >= 0 {
+            return match cx.webidl_types.get(val as usize) {
+                Some(id) => Ok(WebidlTypeRef::Id(*id)),
+                None => r.error(at, &format!("unknown Web IDL type index {}", val)),
+            };
+        }
+        match scalar_type(val) {
+            Some(scalar) => Ok(WebidlTypeRef::Scalar(scalar)),
+            None => r.error(at, &format!("unknown Web IDL scalar type {}", val)),
+        }
+    }
+}
+
+/// Get the scalar type that the negative type reference `code` stands for.
+pub(crate) fn scalar_type(code: i32) -> Option<WebidlScalarType> {
+    Some(match code {
+        -1 => WebidlScalarType::Any,
+        -2 => WebidlScalarType::Boolean,
+        -3 => WebidlScalarType::Byte,
+        -4 => WebidlScalarType::Octet,
+        -5 => WebidlScalarType::Long,
+        -6 => WebidlScalarType::UnsignedLong,
+        -7 => WebidlScalarType::Short,
+        -8 => WebidlScalarType::UnsignedShort,
+        -9 => WebidlScalarType::LongLong,
+        -10 => WebidlScalarType::UnsignedLongLong,
+        -11 => WebidlScalarType::Float,
+        -12 => WebidlScalarType::UnrestrictedFloat,
+        -13 => WebidlScalarType::Double,
+        -14 => WebidlScalarType::UnrestrictedDouble,
+        -15 => WebidlScalarType::DomString,
+        -16 => WebidlScalarType::ByteString,
+        -17 => WebidlScalarType::UsvString,
+        -18 => WebidlScalarType::Object,
+        -19 => WebidlScalarType::Symbol,
+        -20 => WebidlScalarType::ArrayBuffer,
+        -21 => WebidlScalarType::DataView,
+        -22 => WebidlScalarType::Int8Array,
+        -23 => WebidlScalarType::Int16Array,
+        -24 => WebidlScalarType::Int32Array,
+        -25 => WebidlScalarType::Uint8Array,
+        -26 => WebidlScalarType::Uint16Array,
+        -27 => WebidlScalarType::Uint32Array,
+        -28 => WebidlScalarType::Uint8ClampedArray,
+        -29 => WebidlScalarType::Float32Array,
+        -30 => WebidlScalarType::Float64Array,
+        _ => return None,
+    })
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for WebidlDictionaryField {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let name = String::decode(cx, r)?;
+        let ty = WebidlTypeRef::decode(cx, r)?;
+        Ok(WebidlDictionaryField { name, ty })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for FunctionBinding
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => FunctionBinding::Import(ImportBinding {
+                wasm_ty: walrus::TypeId::decode(cx, r)?,
+                webidl_ty: WebidlTypeRef::decode(cx, r)?,
+                params: OutgoingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+                result: IncomingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+            }),
+            1 => FunctionBinding::Export(ExportBinding {
+                wasm_ty: walrus::TypeId::decode(cx, r)?,
+                webidl_ty: WebidlTypeRef::decode(cx, r)?,
+                params: IncomingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+                result: OutgoingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+            }),
+            _ => return r.error(at, "invalid function binding kind"),
+        })
+    }
+}
+
+impl<Cx> Decode<Cx> for walrus::ValType {
+    fn decode(_cx: &mut Cx, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0x7f => walrus::ValType::I32,
+            0x7e => walrus::ValType::I64,
+            0x7d => walrus::ValType::F32,
+            0x7c => walrus::ValType::F64,
+            0x7b => walrus::ValType::V128,
+            0x6f => walrus::ValType::Anyref,
+            _ => return r.error(at, "invalid Wasm value type"),
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for walrus::TypeId
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        let idx = r.uleb()?;
+        match cx.ids.wasm_func_type_id(idx) {
+            Some(id) => Ok(id),
+            None => r.error(at, &format!("unknown Wasm function type index {}", idx)),
+        }
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for walrus::FunctionId
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        let idx = r.uleb()?;
+        match cx.ids.wasm_func_id(idx) {
+            Some(id) => Ok(id),
+            None => r.error(at, &format!("unknown Wasm function index {}", idx)),
+        }
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for Id<FunctionBinding>
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        let idx = r.uleb()?;
+        match cx.bindings.get(idx as usize) {
+            Some(id) => Ok(*id),
+            None => r.error(at, &format!("unknown function binding index {}", idx)),
+        }
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpression
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => OutgoingBindingExpression::As(Decode::decode(cx, r)?),
+            1 => OutgoingBindingExpression::Utf8Str(Decode::decode(cx, r)?),
+            2 => OutgoingBindingExpression::Utf8CStr(Decode::decode(cx, r)?),
+            3 => OutgoingBindingExpression::I32ToEnum(Decode::decode(cx, r)?),
+            4 => OutgoingBindingExpression::View(Decode::decode(cx, r)?),
+            5 => OutgoingBindingExpression::Copy(Decode::decode(cx, r)?),
+            6 => OutgoingBindingExpression::Dict(OutgoingBindingExpressionDict {
+                ty: WebidlTypeRef::decode(cx, r)?,
+                fields: cx.nested(r)?,
+            }),
+            7 => OutgoingBindingExpression::BindExport(OutgoingBindingExpressionBindExport {
+                ty: WebidlTypeRef::decode(cx, r)?,
+                binding: Id::<FunctionBinding>::decode(cx, r)?,
+                idx: r.uleb()?,
+            }),
+            opcode if opcode >= CUSTOM_EXPRESSION_OPCODES => {
+                OutgoingBindingExpression::Custom(OutgoingBindingExpressionCustom {
+                    opcode,
+                    payload: r.byte_vec()?.to_vec(),
+                })
+            }
+            _ => return r.error(at, "invalid outgoing binding expression"),
+        })
+    }
+}
+
+// The outgoing binding expressions that are shared with the unresolved AST.
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpressionAs {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(OutgoingBindingExpressionAs {
+            ty: WebidlTypeRef::decode(cx, r)?,
+            idx: r.uleb()?,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpressionUtf8Str {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(OutgoingBindingExpressionUtf8Str {
+            ty: WebidlTypeRef::decode(cx, r)?,
+            offset: r.uleb()?,
+            length: r.uleb()?,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpressionUtf8CStr {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(OutgoingBindingExpressionUtf8CStr {
+            ty: WebidlTypeRef::decode(cx, r)?,
+            offset: r.uleb()?,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpressionI32ToEnum {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(OutgoingBindingExpressionI32ToEnum {
+            ty: WebidlTypeRef::decode(cx, r)?,
+            idx: r.uleb()?,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpressionView {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(OutgoingBindingExpressionView {
+            ty: WebidlTypeRef::decode(cx, r)?,
+            offset: r.uleb()?,
+            length: r.uleb()?,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for OutgoingBindingExpressionCopy {
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(OutgoingBindingExpressionCopy {
+            ty: WebidlTypeRef::decode(cx, r)?,
+            offset: r.uleb()?,
+            length: r.uleb()?,
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for IncomingBindingExpression
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => IncomingBindingExpression::Get(IncomingBindingExpressionGet { idx: r.uleb()? }),
+            1 => IncomingBindingExpression::As(IncomingBindingExpressionAs {
+                ty: walrus::ValType::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            2 => IncomingBindingExpression::AllocUtf8Str(IncomingBindingExpressionAllocUtf8Str {
+                alloc_func_name: String::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            3 => IncomingBindingExpression::AllocCopy(IncomingBindingExpressionAllocCopy {
+                alloc_func_name: String::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            4 => IncomingBindingExpression::EnumToI32(IncomingBindingExpressionEnumToI32 {
+                ty: WebidlTypeRef::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            5 => IncomingBindingExpression::Field(IncomingBindingExpressionField {
+                idx: r.uleb()?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            6 => IncomingBindingExpression::BindImport(IncomingBindingExpressionBindImport {
+                ty: walrus::TypeId::decode(cx, r)?,
+                binding: Id::<FunctionBinding>::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            opcode if opcode >= CUSTOM_EXPRESSION_OPCODES => {
+                IncomingBindingExpression::Custom(IncomingBindingExpressionCustom {
+                    opcode,
+                    payload: r.byte_vec()?.to_vec(),
+                })
+            }
+            _ => return r.error(at, "invalid incoming binding expression"),
+        })
+    }
+}
+
+impl<I> Decode<DecodeContext<'_, I>> for Bind
+where
+    I: Ids,
+{
+    fn decode(cx: &mut DecodeContext<I>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let func = walrus::FunctionId::decode(cx, r)?;
+        let binding = Id::<FunctionBinding>::decode(cx, r)?;
+        Ok(Bind { func, binding })
+    }
+}
+
+// The unresolved AST, which keeps Wasm functions and function types as
+// indices, and doesn't need the Wasm module.
+
+impl Decode<DecodeContext<'_, ()>> for unresolved::WebidlBindings {
+    fn decode(cx: &mut DecodeContext<()>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let mut section = unresolved::WebidlBindings::default();
+
+        r.header()?;
+        let extensions = &mut section.extensions;
+        extensions.features = r.unknown_features();
+        decode_extensions(r, SubsectionPosition::AfterHeader, extensions)?;
+
+        // String Table Subsection, which is optional.
+        r.string_table_subsection()?;
+        decode_extensions(r, SubsectionPosition::AfterStringTable, extensions)?;
+
+        // Web IDL Type Subsection, which is optional.
+        decode_types(cx, r, &mut section.types)?;
+        let extensions = &mut section.extensions;
+        decode_extensions(r, SubsectionPosition::AfterTypes, extensions)?;
+
+        // Web IDL Function Binding Subsection.
+        let at = r.pos;
+        if r.byte()? != 1 {
+            return r.error(at, "expected the function binding subsection");
+        }
+
+        // Bindings. Their IDs are never used, but the indices that refer to
+        // them are still checked against the number of bindings.
+        let len = r.uleb()?;
+        cx.bindings = next_ids(&id_arena::Arena::new(), len);
+        for _ in 0..len {
+            section.bindings.push(Decode::decode(cx, r)?);
+        }
+
+        // Binds.
+        section.binds = r.vec(cx)?;
+        decode_extensions(
+            r,
+            SubsectionPosition::AfterBindings,
+            &mut section.extensions,
+        )?;
+
+        // Names Subsection, which is optional.
+        for (idx, name) in decode_names(cx, r, &mut section.types)? {
+            match &mut section.bindings[idx as usize] {
+                unresolved::FunctionBinding::Import(b) => b.name = Some(name),
+                unresolved::FunctionBinding::Export(b) => b.name = Some(name),
+            }
+        }
+
+        decode_extensions(r, SubsectionPosition::AfterNames, &mut section.extensions)?;
+        Ok(section)
+    }
+}
+
+impl Decode<DecodeContext<'_, ()>> for unresolved::FunctionBinding {
+    fn decode(cx: &mut DecodeContext<()>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => unresolved::FunctionBinding::Import(unresolved::ImportBinding {
+                name: None,
+                wasm_ty: unresolved::WasmRef::decode(cx, r)?,
+                webidl_ty: WebidlTypeRef::decode(cx, r)?,
+                params: unresolved::OutgoingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+                result: unresolved::IncomingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+            }),
+            1 => unresolved::FunctionBinding::Export(unresolved::ExportBinding {
+                name: None,
+                wasm_ty: unresolved::WasmRef::decode(cx, r)?,
+                webidl_ty: WebidlTypeRef::decode(cx, r)?,
+                params: unresolved::IncomingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+                result: unresolved::OutgoingBindingMap {
+                    bindings: r.vec(cx)?,
+                },
+            }),
+            _ => return r.error(at, "invalid function binding kind"),
+        })
+    }
+}
+
+impl<Cx> Decode<Cx> for unresolved::WasmRef {
+    fn decode(_cx: &mut Cx, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(unresolved::WasmRef::Indexed(r.uleb()?))
+    }
+}
+
+impl<Cx> Decode<Cx> for unresolved::WasmFuncRef {
+    fn decode(_cx: &mut Cx, r: &mut Reader) -> Result<Self, failure::Error> {
+        Ok(unresolved::WasmFuncRef::Indexed(r.uleb()?))
+    }
+}
+
+/// Decode a function binding index, checking that it is in bounds.
+fn binding_index(cx: &mut DecodeContext<()>, r: &mut Reader) -> Result<u32, failure::Error> {
+    let at = r.pos;
+    let idx = r.uleb()?;
+    if idx as usize >= cx.bindings.len() {
+        return r.error(at, &format!("unknown function binding index {}", idx));
+    }
+    Ok(idx)
+}
+
+impl Decode<DecodeContext<'_, ()>> for unresolved::OutgoingBindingExpression {
+    fn decode(cx: &mut DecodeContext<()>, r: &mut Reader) -> Result<Self, failure::Error> {
+        use crate::unresolved::OutgoingBindingExpression as E;
+
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => E::As(Decode::decode(cx, r)?),
+            1 => E::Utf8Str(Decode::decode(cx, r)?),
+            2 => E::Utf8CStr(Decode::decode(cx, r)?),
+            3 => E::I32ToEnum(Decode::decode(cx, r)?),
+            4 => E::View(Decode::decode(cx, r)?),
+            5 => E::Copy(Decode::decode(cx, r)?),
+            6 => E::Dict(unresolved::OutgoingBindingExpressionDict {
+                ty: WebidlTypeRef::decode(cx, r)?,
+                fields: cx.nested(r)?,
+            }),
+            7 => E::BindExport(unresolved::OutgoingBindingExpressionBindExport {
+                ty: WebidlTypeRef::decode(cx, r)?,
+                binding: binding_index(cx, r)?,
+                idx: r.uleb()?,
+            }),
+            opcode if opcode >= CUSTOM_EXPRESSION_OPCODES => {
+                E::Custom(OutgoingBindingExpressionCustom {
+                    opcode,
+                    payload: r.byte_vec()?.to_vec(),
+                })
+            }
+            _ => return r.error(at, "invalid outgoing binding expression"),
+        })
+    }
+}
+
+impl Decode<DecodeContext<'_, ()>> for unresolved::IncomingBindingExpression {
+    fn decode(cx: &mut DecodeContext<()>, r: &mut Reader) -> Result<Self, failure::Error> {
+        use crate::unresolved::IncomingBindingExpression as E;
+
+        let at = r.pos;
+        Ok(match r.byte()? {
+            0 => E::Get(IncomingBindingExpressionGet { idx: r.uleb()? }),
+            1 => E::As(unresolved::IncomingBindingExpressionAs {
+                ty: walrus::ValType::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            2 => E::AllocUtf8Str(unresolved::IncomingBindingExpressionAllocUtf8Str {
+                alloc_func_name: String::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            3 => E::AllocCopy(unresolved::IncomingBindingExpressionAllocCopy {
+                alloc_func_name: String::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            4 => E::EnumToI32(unresolved::IncomingBindingExpressionEnumToI32 {
+                ty: WebidlTypeRef::decode(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            5 => E::Field(unresolved::IncomingBindingExpressionField {
+                idx: r.uleb()?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            6 => E::BindImport(unresolved::IncomingBindingExpressionBindImport {
+                ty: unresolved::WasmRef::decode(cx, r)?,
+                binding: binding_index(cx, r)?,
+                expr: Box::new(cx.nested(r)?),
+            }),
+            opcode if opcode >= CUSTOM_EXPRESSION_OPCODES => {
+                E::Custom(IncomingBindingExpressionCustom {
+                    opcode,
+                    payload: r.byte_vec()?.to_vec(),
+                })
+            }
+            _ => return r.error(at, "invalid incoming binding expression"),
+        })
+    }
+}
+
+impl Decode<DecodeContext<'_, ()>> for unresolved::Bind {
+    fn decode(cx: &mut DecodeContext<()>, r: &mut Reader) -> Result<Self, failure::Error> {
+        let func = unresolved::WasmFuncRef::decode(cx, r)?;
+        let binding = binding_index(cx, r)?;
+        Ok(unresolved::Bind { func, binding })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestIds {
+        funcs: Vec<walrus::FunctionId>,
+        types: Vec<walrus::TypeId>,
+    }
+
+    impl Ids for TestIds {
+        fn wasm_func_id(&self, idx: u32) -> Option<walrus::FunctionId> {
+            self.funcs.get(idx as usize).cloned()
+        }
+
+        fn wasm_func_type_id(&self, idx: u32) -> Option<walrus::TypeId> {
+            self.types.get(idx as usize).cloned()
+        }
+    }
+
+    fn test_ids() -> TestIds {
+        let mut m = walrus::Module::default();
+        let ty = m.types.add(&[], &[]);
+        let func = walrus::FunctionBuilder::new().finish(ty, vec![], vec![], &mut m);
+        TestIds {
+            funcs: vec![func],
+            types: vec![ty],
+        }
+    }
+
+    fn decode_section(data: &[u8]) -> Result<WebidlBindings, failure::Error> {
+        let ids = test_ids();
+        WebidlBindings::decode(&mut DecodeContext::new(&ids), &mut Reader::new(data))
+    }
+
+    fn assert_error(data: &[u8], expected: &str) {
+        match decode_section(data) {
+            Ok(section) => panic!("expected an error, decoded {:?}", section),
+            Err(e) => assert_eq!(e.to_string(), expected),
+        }
+    }
+
+    #[test]
+    fn decode_section_with_forward_references() {
+        #[rustfmt::skip]
+        let section = decode_section(&[
+            // Header: version 1, no features.
+            1, 0,
+            // Web IDL Type Subsection: a dictionary whose field refers to the
+            // union after it, and a union of `USVString` and the dictionary.
+            0, 2,
+            1, 1, 1, b'a', 1,
+            3, 2, 0x6f, 0,
+            // Web IDL Function Binding Subsection: an import binding whose
+            // param refers to the export binding after it.
+            1, 2,
+            0, 0, 0x7b, 1, 7, 0x7b, 1, 0, 0,
+            1, 0, 0x76, 0, 0,
+            // Binds.
+            1, 0, 1,
+        ])
+        .unwrap();
+
+        let types: Vec<_> = section.types.arena.iter().map(|(id, _)| id).collect();
+        match &section.types.arena[types[0]] {
+            WebidlCompoundType::Dictionary(d) => {
+                assert_eq!(d.fields[0].name, "a");
+                assert_eq!(d.fields[0].ty, WebidlTypeRef::Id(types[1]));
+            }
+            other => panic!("expected a dictionary, found {:?}", other),
+        }
+        match &section.types.arena[types[1]] {
+            WebidlCompoundType::Union(u) => assert_eq!(
+                u.members,
+                [
+                    WebidlTypeRef::Scalar(WebidlScalarType::UsvString),
+                    WebidlTypeRef::Id(types[0]),
+                ]
+            ),
+            other => panic!("expected a union, found {:?}", other),
+        }
+
+        let bindings: Vec<_> = section.bindings.arena.iter().map(|(id, _)| id).collect();
+        match &section.bindings.arena[bindings[0]] {
+            FunctionBinding::Import(b) => match &b.params.bindings[..] {
+                [OutgoingBindingExpression::BindExport(e)] => assert_eq!(e.binding, bindings[1]),
+                other => panic!("expected a bind-export, found {:?}", other),
+            },
+            other => panic!("expected an import binding, found {:?}", other),
+        }
+
+        let (_, bind) = section.binds.arena.iter().next().unwrap();
+        assert_eq!(bind.binding, bindings[1]);
+    }
+
+    #[test]
+    fn type_subsection_is_optional() {
+        let section = decode_section(&[1, 0, 1, 0, 0]).unwrap();
+        assert_eq!(section.types.arena.len(), 0);
+        assert_eq!(section.bindings.arena.len(), 0);
+    }
+
+    #[test]
+    fn names_subsection() {
+        #[rustfmt::skip]
+        let section = decode_section(&[
+            1, 0x80, 0x80, 0x04,
+            0, 1, 2, 0,
+            1, 1, 0, 0, 0x7b, 0, 0, 0,
+            2, 15,
+            1, 0, 2, b'$', b'E',
+            1, 0, 2, b'$', b'b',
+            1, 2, b'$', b'S', 0x6f,
+        ])
+        .unwrap();
+        let ty = section.types.by_index(0).unwrap();
+        assert_eq!(section.types.by_name("$E"), Some(ty));
+        assert_eq!(
+            section.types.resolve("$S"),
+            Some(WebidlTypeRef::Scalar(WebidlScalarType::UsvString))
+        );
+        assert_eq!(
+            section.bindings.by_name("$b"),
+            section.bindings.by_index(0)
+        );
+
+        assert_error(
+            &[1, 0x80, 0x80, 0x04, 1, 0, 0, 2, 6, 1, 0, 1, b'a', 0, 0],
+            "unknown Web IDL type index 0 at offset 10",
+        );
+        assert_error(
+            &[1, 0x80, 0x80, 0x04, 1, 0, 0, 2, 6, 0, 1, 0, 1, b'a', 0],
+            "unknown function binding index 0 at offset 11",
+        );
+        assert_error(
+            &[1, 0x80, 0x80, 0x04, 1, 0, 0, 2, 2, 0, 0, 0],
+            "the names subsection doesn't match its size at offset 12",
+        );
+    }
+
+    #[test]
+    fn string_table_subsection() {
+        #[rustfmt::skip]
+        let section = decode_section(&[
+            1, 2,
+            3, 5, 2, 1, b'a', 1, b'b',
+            0, 1, 2, 3, 1, 0, 1,
+            1, 0, 0,
+        ])
+        .unwrap();
+        let ty = section.types.by_index(0).unwrap();
+        match &section.types.arena[ty] {
+            WebidlCompoundType::Enumeration(e) => assert_eq!(e.values, ["b", "a", "b"]),
+            ty => panic!("expected an enumeration, found {:?}", ty),
+        }
+
+        assert_error(
+            &[1, 2, 3, 3, 1, 1, b'a', 0, 1, 2, 1, 1, 1, 0, 0],
+            "unknown string index 1 at offset 11",
+        );
+        assert_error(&[1, 2, 3, 3, 1, 1], "unexpected end of section at offset 6");
+        assert_error(
+            &[1, 2, 3, 4, 1, 1, b'a', 0, 1, 0, 0],
+            "the string table subsection doesn't match its size at offset 7",
+        );
+    }
+
+    #[test]
+    fn header() {
+        assert_error(
+            &[0, 0, 1, 0, 0],
+            "unsupported version 0 (the newest supported version is 1) at offset 0",
+        );
+        assert_error(
+            &[2, 0, 1, 0, 0],
+            "unsupported version 2 (the newest supported version is 1) at offset 0",
+        );
+        assert_error(&[1, 4, 1, 0, 0], "unsupported features 0x4 at offset 1");
+        assert_error(&[1, 1, 1, 0, 0], "unsupported features 0x1 at offset 1");
+
+        // Unknown optional features are ignored.
+        decode_section(&[1, 0x80, 0x80, 0x08, 1, 0, 0]).unwrap();
+
+        // The features say which optional subsections there are.
+        assert_error(
+            &[1, 0x80, 0x80, 0x04, 1, 0, 0, 3],
+            "expected the names subsection at offset 7",
+        );
+        assert_error(
+            &[1, 2, 1, 0, 0],
+            "expected the string table subsection at offset 2",
+        );
+        assert_error(
+            &[1, 0, 1, 0, 0, 2, 0, 0, 0],
+            "unexpected data after the last subsection at offset 5",
+        );
+    }
+
+    /// An export binding whose param is `depth` nested `field` expressions.
+    fn nested_fields(depth: usize) -> Vec<u8> {
+        let mut data = vec![1, 0, 1, 1, 1, 0, 0x7b, 1];
+        for _ in 0..depth {
+            data.extend_from_slice(&[5, 0]);
+        }
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        decode_section(&nested_fields(MAX_DEPTH)).unwrap();
+        let expected = format!("too deeply nested at offset {}", 10 + 2 * MAX_DEPTH);
+        assert_error(&nested_fields(MAX_DEPTH + 1), &expected);
+
+        // Much deeper nesting is an error too, rather than a stack overflow.
+        let data = nested_fields(2_000_000);
+        assert_error(&data, &expected);
+        let e = unresolved::WebidlBindings::decode(
+            &mut DecodeContext::new(&()),
+            &mut Reader::new(&data),
+        )
+        .unwrap_err();
+        assert_eq!(e.to_string(), expected);
+
+        #[rustfmt::skip]
+        let mut data = vec![
+            1, 0, 1, 1,
+            // An import binding whose param is nested `dict` expressions.
+            0, 0, 0x7b, 1,
+        ];
+        for _ in 0..=MAX_DEPTH {
+            data.extend_from_slice(&[6, 0x7b, 1]);
+        }
+        data.extend_from_slice(&[0, 0, 0]);
+        assert_error(
+            &data,
+            &format!("too deeply nested at offset {}", 10 + 3 * MAX_DEPTH),
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_error(&[], "unexpected end of section at offset 0");
+        assert_error(&[1, 0], "unexpected end of section at offset 2");
+        assert_error(&[1, 0, 2], "expected the function binding subsection at offset 2");
+        assert_error(
+            &[1, 0, 1, 0, 0, 0],
+            "unexpected data after the last subsection at offset 5",
+        );
+        assert_error(&[1, 0, 0, 1, 4], "invalid Web IDL type at offset 4");
+        assert_error(&[1, 0, 0, 1, 3, 1, 1], "unknown Web IDL type index 1 at offset 6");
+        assert_error(
+            &[1, 0, 0, 1, 3, 1, 0x61],
+            "unknown Web IDL scalar type -31 at offset 6",
+        );
+        assert_error(
+            &[1, 0, 0, 1, 1, 1, 2, 0xff],
+            "unexpected end of section at offset 8",
+        );
+        assert_error(
+            &[1, 0, 0, 1, 1, 1, 1, 0xff, 0x7b],
+            "invalid UTF-8 string at offset 7",
+        );
+        assert_error(
+            &[1, 0, 1, 1, 0, 1],
+            "unknown Wasm function type index 1 at offset 5",
+        );
+        assert_error(
+            &[1, 0, 1, 1, 0, 0, 0x7b, 1, 8],
+            "invalid outgoing binding expression at offset 8",
+        );
+        assert_error(
+            &[1, 0, 1, 1, 1, 0, 0x7b, 1, 1, 0x70],
+            "invalid Wasm value type at offset 9",
+        );
+        assert_error(
+            &[1, 0, 1, 0, 1, 1, 0],
+            "unknown Wasm function index 1 at offset 5",
+        );
+        assert_error(
+            &[1, 0, 1, 0, 1, 0, 0],
+            "unknown function binding index 0 at offset 6",
+        );
+        assert_error(&[1, 0, 1, 0x80], "unexpected end of section at offset 4");
+        assert_error(
+            &[1, 0, 1, 0, 0, 7, 3, 0],
+            "unexpected end of section at offset 8",
+        );
+        assert_error(
+            &[1, 0, 1, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            "integer too large at offset 3",
+        );
+    }
+}
@@ -0,0 +1,678 @@
+use super::decode::{scalar_type, Decode, Reader};
+use super::{
+    CUSTOM_EXPRESSION_OPCODES, FEATURE_NAMES, FEATURE_STRING_TABLE, LAST_KNOWN_SUBSECTION,
+};
+use crate::text::scalar_type_name;
+use std::io;
+
+/// Binding expressions nest, so bound how deep they may go to avoid
+/// overflowing the stack on malformed input.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// The most raw bytes shown on one line of the dump.
+const MAX_HEX_BYTES: usize = 8;
+
+struct Line {
+    start: usize,
+    end: usize,
+    depth: usize,
+    text: String,
+}
+
+pub(crate) struct Dumper<'a> {
+    data: &'a [u8],
+    r: Reader<'a>,
+    lines: Vec<Line>,
+    depth: usize,
+}
+
+impl<'a> Dumper<'a> {
+    pub fn new(data: &'a [u8]) -> Dumper<'a> {
+        Dumper {
+            data,
+            r: Reader::new(data),
+            lines: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Write every line dumped so far.
+    pub fn finish<W>(self, w: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        for line in &self.lines {
+            let bytes = &self.data[line.start..line.end];
+            let mut hex: Vec<_> = bytes
+                .iter()
+                .take(MAX_HEX_BYTES)
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            if bytes.len() > MAX_HEX_BYTES {
+                hex.pop();
+                hex.push("..".to_string());
+            }
+            writeln!(
+                w,
+                "{:07x}: {:<23} ; {:indent$}{}",
+                line.start,
+                hex.join(" "),
+                "",
+                line.text,
+                indent = line.depth * 2
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Add a line for the bytes from `start` up to the current position.
+    fn line(&mut self, start: usize, text: String) {
+        self.lines.push(Line {
+            start,
+            end: self.r.pos(),
+            depth: self.depth,
+            text,
+        });
+    }
+
+    fn nested<F>(&mut self, f: F) -> Result<(), failure::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), failure::Error>,
+    {
+        if self.depth == MAX_DEPTH {
+            return self.r.error(self.r.pos(), "too deeply nested");
+        }
+        self.depth += 1;
+        f(self)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// Dump a `vec` of items, with a line for its length.
+    fn vec<F>(&mut self, what: &str, mut f: F) -> Result<(), failure::Error>
+    where
+        F: FnMut(&mut Self, u32) -> Result<(), failure::Error>,
+    {
+        let len = self.uleb(what)?;
+        self.nested(|d| (0..len).try_for_each(|i| f(d, i)))
+    }
+
+    fn uleb(&mut self, what: &str) -> Result<u32, failure::Error> {
+        let start = self.r.pos();
+        let val = self.r.uleb()?;
+        self.line(start, format!("{}: {}", what, val));
+        Ok(val)
+    }
+
+    fn string(&mut self, what: &str) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let text = match self.r.string_ref()? {
+            (Some(idx), s) => format!("{}: string[{}] {:?}", what, idx, s),
+            (None, s) => format!("{}: {:?}", what, s),
+        };
+        self.line(start, text);
+        Ok(())
+    }
+
+    fn webidl_type_ref(&mut self, what: &str) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let val = self.r.ileb()?;
+        let text = if val >= 0 {
+            format!("{}: type[{}]", what, val)
+        } else {
+            match scalar_type(val) {
+                Some(s) => format!("{}: {} {}", what, val, scalar_type_name(s)),
+                None => {
+                    return self
+                        .r
+                        .error(start, &format!("unknown Web IDL scalar type {}", val))
+                }
+            }
+        };
+        self.line(start, text);
+        Ok(())
+    }
+
+    /// Dump the opaque payload of a custom binding expression.
+    fn payload(&mut self) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let payload = self.r.byte_vec()?;
+        self.line(start, format!("payload: {} bytes", payload.len()));
+        Ok(())
+    }
+
+    fn val_type(&mut self, what: &str) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let ty = walrus::ValType::decode(&mut (), &mut self.r)?;
+        self.line(start, format!("{}: {}", what, ty));
+        Ok(())
+    }
+
+    /// Dump a subsection, with a line for its id and the size of its contents.
+    fn subsection<F>(&mut self, id: u8, name: &str, f: F) -> Result<(), failure::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), failure::Error>,
+    {
+        let start = self.r.pos();
+        if self.r.byte()? != id {
+            return self.r.error(start, &format!("expected the {}", name));
+        }
+        let header = self.lines.len();
+        self.line(start, format!("{} (id {})", name, id));
+        self.nested(f)?;
+        let size = self.r.pos() - start - 1;
+        self.lines[header].text = format!("{} (id {}, {} bytes)", name, id, size);
+        Ok(())
+    }
+
+    /// Dump a subsection whose contents are prefixed with their size, with a
+    /// line for its id and size.
+    fn sized_subsection<F>(&mut self, id: u8, name: &str, f: F) -> Result<(), failure::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), failure::Error>,
+    {
+        let start = self.r.pos();
+        if self.r.byte()? != id {
+            return self.r.error(start, &format!("expected the {}", name));
+        }
+        let end = self.r.subsection_size()?;
+        let size = end - self.r.pos();
+        self.line(start, format!("{} (id {}, {} bytes)", name, id, size));
+        self.nested(f)?;
+        self.r.subsection_end(end, name)
+    }
+
+    pub fn section(&mut self) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let version = self.r.version()?;
+        self.line(start, format!("version: {}", version));
+        let start = self.r.pos();
+        let features = self.r.features()?;
+        let all = features | self.r.unknown_features();
+        let mut names = vec![];
+        if features & FEATURE_NAMES != 0 {
+            names.push("names");
+        }
+        if features & FEATURE_STRING_TABLE != 0 {
+            names.push("string table");
+        }
+        let text = if names.is_empty() {
+            format!("features: {:#x}", all)
+        } else {
+            format!("features: {:#x} ({})", all, names.join(", "))
+        };
+        self.line(start, text);
+        self.unknown_subsections(false)?;
+
+        if self.r.has_feature(FEATURE_STRING_TABLE) {
+            let mut strings = vec![];
+            self.sized_subsection(3, "string table subsection", |d| {
+                d.vec("strings", |d, i| {
+                    let start = d.r.pos();
+                    let s = d.r.inline_str()?;
+                    d.line(start, format!("string[{}]: {:?}", i, s));
+                    strings.push(s);
+                    Ok(())
+                })
+            })?;
+            self.r.set_string_table(strings);
+        }
+        self.unknown_subsections(false)?;
+
+        if self.r.peek() == Some(0) {
+            self.subsection(0, "Web IDL type subsection", |d| {
+                d.vec("types", Self::webidl_type)
+            })?;
+            self.unknown_subsections(false)?;
+        }
+
+        self.subsection(1, "function binding subsection", |d| {
+            d.vec("bindings", Self::function_binding)?;
+            d.vec("binds", |d, i| {
+                let start = d.r.pos();
+                d.line(start, format!("bind[{}]", i));
+                d.nested(|d| {
+                    d.uleb("Wasm function")?;
+                    d.uleb("binding")?;
+                    Ok(())
+                })
+            })
+        })?;
+        self.unknown_subsections(false)?;
+
+        if self.r.has_feature(FEATURE_NAMES) {
+            self.sized_subsection(2, "names subsection", |d| {
+                d.vec("type names", |d, i| d.name("type", i))?;
+                d.vec("binding names", |d, i| d.name("binding", i))?;
+                d.vec("type aliases", |d, i| {
+                    let start = d.r.pos();
+                    d.line(start, format!("alias[{}]", i));
+                    d.nested(|d| {
+                        d.string("name")?;
+                        d.webidl_type_ref("type")
+                    })
+                })
+            })?;
+        }
+
+        // Everything after the known subsections is unknown subsections.
+        self.unknown_subsections(true)
+    }
+
+    /// Dump the unknown subsections that come next, or if `rest` is set, all
+    /// of the rest of the section as unknown subsections.
+    fn unknown_subsections(&mut self, rest: bool) -> Result<(), failure::Error> {
+        while let Some(id) = self.r.peek() {
+            if !rest && id <= LAST_KNOWN_SUBSECTION {
+                break;
+            }
+            let start = self.r.pos();
+            let (id, data) = self.r.unknown_subsection()?;
+            self.line(
+                start,
+                format!("unknown subsection (id {}, {} bytes)", id, data.len()),
+            );
+        }
+        Ok(())
+    }
+
+    /// Dump an entry of the names subsection, which names the item with the
+    /// given index.
+    fn name(&mut self, what: &str, i: u32) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let idx = self.r.uleb()?;
+        let name = match self.r.string_ref()? {
+            (Some(string), name) => format!("string[{}] {:?}", string, name),
+            (None, name) => format!("{:?}", name),
+        };
+        self.line(start, format!("name[{}]: {}[{}] {}", i, what, idx, name));
+        Ok(())
+    }
+
+    fn webidl_type(&mut self, i: u32) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        match self.r.byte()? {
+            0 => {
+                self.line(start, format!("type[{}]: function", i));
+                self.nested(Self::webidl_function)
+            }
+            1 => {
+                self.line(start, format!("type[{}]: dictionary", i));
+                self.nested(|d| {
+                    d.vec("fields", |d, _| {
+                        d.string("name")?;
+                        d.webidl_type_ref("type")
+                    })
+                })
+            }
+            2 => {
+                self.line(start, format!("type[{}]: enumeration", i));
+                self.nested(|d| d.vec("values", |d, _| d.string("value")))
+            }
+            3 => {
+                self.line(start, format!("type[{}]: union", i));
+                self.nested(|d| d.vec("members", |d, _| d.webidl_type_ref("member")))
+            }
+            _ => self.r.error(start, "invalid Web IDL type"),
+        }
+    }
+
+    fn webidl_function(&mut self) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        match self.r.byte()? {
+            0 => self.line(start, "kind: static".to_string()),
+            1 => {
+                self.line(start, "kind: method".to_string());
+                self.nested(|d| d.webidl_type_ref("receiver"))?;
+            }
+            2 => self.line(start, "kind: constructor".to_string()),
+            _ => return self.r.error(start, "invalid Web IDL function kind"),
+        }
+        self.vec("params", |d, _| d.webidl_type_ref("param"))?;
+        let start = self.r.pos();
+        match self.r.byte()? {
+            0 => self.line(start, "no result".to_string()),
+            1 => {
+                self.line(start, "has result".to_string());
+                self.nested(|d| d.webidl_type_ref("result"))?;
+            }
+            _ => return self.r.error(start, "invalid Web IDL function result flag"),
+        }
+        Ok(())
+    }
+
+    fn function_binding(&mut self, i: u32) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let import = match self.r.byte()? {
+            0 => true,
+            1 => false,
+            _ => return self.r.error(start, "invalid function binding kind"),
+        };
+        let kind = if import { "import" } else { "export" };
+        self.line(start, format!("binding[{}]: {}", i, kind));
+        self.nested(|d| {
+            d.uleb("Wasm function type")?;
+            d.webidl_type_ref("Web IDL type")?;
+            if import {
+                d.vec("params", |d, _| d.outgoing())?;
+                d.vec("results", |d, _| d.incoming())
+            } else {
+                d.vec("params", |d, _| d.incoming())?;
+                d.vec("results", |d, _| d.outgoing())
+            }
+        })
+    }
+
+    fn expression(&mut self, start: usize, direction: &str, opcode: u8, name: &str) {
+        self.line(start, format!("{} {} (opcode {})", direction, name, opcode));
+    }
+
+    fn outgoing(&mut self) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let opcode = self.r.byte()?;
+        let name = match opcode {
+            0 => "as",
+            1 => "utf8-str",
+            2 => "utf8-cstr",
+            3 => "i32-to-enum",
+            4 => "view",
+            5 => "copy",
+            6 => "dict",
+            7 => "bind-export",
+            _ if opcode >= CUSTOM_EXPRESSION_OPCODES => "custom",
+            _ => return self.r.error(start, "invalid outgoing binding expression"),
+        };
+        self.expression(start, "outgoing", opcode, name);
+        self.nested(|d| {
+            if opcode >= CUSTOM_EXPRESSION_OPCODES {
+                return d.payload();
+            }
+            d.webidl_type_ref("type")?;
+            match opcode {
+                0 | 3 => {
+                    d.uleb("index")?;
+                }
+                1 | 4 | 5 => {
+                    d.uleb("offset")?;
+                    d.uleb("length")?;
+                }
+                2 => {
+                    d.uleb("offset")?;
+                }
+                6 => d.vec("fields", |d, _| d.outgoing())?,
+                _ => {
+                    d.uleb("binding")?;
+                    d.uleb("index")?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn incoming(&mut self) -> Result<(), failure::Error> {
+        let start = self.r.pos();
+        let opcode = self.r.byte()?;
+        let name = match opcode {
+            0 => "get",
+            1 => "as",
+            2 => "alloc-utf8-str",
+            3 => "alloc-copy",
+            4 => "enum-to-i32",
+            5 => "field",
+            6 => "bind-import",
+            _ if opcode >= CUSTOM_EXPRESSION_OPCODES => "custom",
+            _ => return self.r.error(start, "invalid incoming binding expression"),
+        };
+        self.expression(start, "incoming", opcode, name);
+        self.nested(|d| {
+            match opcode {
+                0 => {
+                    d.uleb("index")?;
+                    return Ok(());
+                }
+                _ if opcode >= CUSTOM_EXPRESSION_OPCODES => return d.payload(),
+                1 => d.val_type("type")?,
+                2 | 3 => d.string("allocator")?,
+                4 => d.webidl_type_ref("type")?,
+                5 => {
+                    d.uleb("index")?;
+                }
+                _ => {
+                    d.uleb("Wasm function type")?;
+                    d.uleb("binding")?;
+                }
+            }
+            d.incoming()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn do_dump(data: &[u8]) -> (String, Result<(), failure::Error>) {
+        let mut out = vec![];
+        let result = crate::binary::dump(data, &mut out);
+        (String::from_utf8(out).unwrap(), result)
+    }
+
+    #[test]
+    fn dump_section() {
+        #[rustfmt::skip]
+        let (out, result) = do_dump(&[
+            1, 0,
+            0, 2,
+            1, 1, 1, b'a', 1,
+            3, 2, 0x6f, 0,
+            1, 2,
+            0, 0, 0x7b, 1, 7, 0x7b, 1, 0, 1, 1, 0x7f, 0, 0,
+            1, 0, 0x76, 1, 2, 5, b'a', b'l', b'l', b'o', b'c', 0, 1, 0,
+            1, 0, 1,
+        ]);
+        result.unwrap();
+        assert_eq!(
+            out,
+            "\
+0000000: 01                      ; version: 1
+0000001: 00                      ; features: 0x0
+0000002: 00                      ; Web IDL type subsection (id 0, 10 bytes)
+0000003: 02                      ;   types: 2
+0000004: 01                      ;     type[0]: dictionary
+0000005: 01                      ;       fields: 1
+0000006: 01 61                   ;         name: \"a\"
+0000008: 01                      ;         type: type[1]
+0000009: 03                      ;     type[1]: union
+000000a: 02                      ;       members: 2
+000000b: 6f                      ;         member: -17 USVString
+000000c: 00                      ;         member: type[0]
+000000d: 01                      ; function binding subsection (id 1, 31 bytes)
+000000e: 02                      ;   bindings: 2
+000000f: 00                      ;     binding[0]: import
+0000010: 00                      ;       Wasm function type: 0
+0000011: 7b                      ;       Web IDL type: -5 long
+0000012: 01                      ;       params: 1
+0000013: 07                      ;         outgoing bind-export (opcode 7)
+0000014: 7b                      ;           type: -5 long
+0000015: 01                      ;           binding: 1
+0000016: 00                      ;           index: 0
+0000017: 01                      ;       results: 1
+0000018: 01                      ;         incoming as (opcode 1)
+0000019: 7f                      ;           type: i32
+000001a: 00                      ;           incoming get (opcode 0)
+000001b: 00                      ;             index: 0
+000001c: 01                      ;     binding[1]: export
+000001d: 00                      ;       Wasm function type: 0
+000001e: 76                      ;       Web IDL type: -10 unsigned long long
+000001f: 01                      ;       params: 1
+0000020: 02                      ;         incoming alloc-utf8-str (opcode 2)
+0000021: 05 61 6c 6c 6f 63       ;           allocator: \"alloc\"
+0000027: 00                      ;           incoming get (opcode 0)
+0000028: 01                      ;             index: 1
+0000029: 00                      ;       results: 0
+000002a: 01                      ;   binds: 1
+000002b:                         ;     bind[0]
+000002b: 00                      ;       Wasm function: 0
+000002c: 01                      ;       binding: 1
+"
+        );
+    }
+
+    #[test]
+    fn dump_names_subsection() {
+        #[rustfmt::skip]
+        let (out, result) = do_dump(&[
+            1, 0x80, 0x80, 0x04,
+            1, 0, 0,
+            2, 15,
+            1, 3, 2, b'$', b'T',
+            1, 0, 2, b'$', b'b',
+            1, 2, b'$', b'S', 0x6f,
+        ]);
+        result.unwrap();
+        assert!(
+            out.ends_with(
+                "\
+0000007: 02 0f                   ; names subsection (id 2, 15 bytes)
+0000009: 01                      ;   type names: 1
+000000a: 03 02 24 54             ;     name[0]: type[3] \"$T\"
+000000e: 01                      ;   binding names: 1
+000000f: 00 02 24 62             ;     name[0]: binding[0] \"$b\"
+0000013: 01                      ;   type aliases: 1
+0000014:                         ;     alias[0]
+0000014: 02 24 53                ;       name: \"$S\"
+0000017: 6f                      ;       type: -17 USVString
+"
+            ),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn dump_string_table_subsection() {
+        #[rustfmt::skip]
+        let (out, result) = do_dump(&[
+            1, 2,
+            3, 5, 2, 1, b'a', 1, b'b',
+            0, 1, 2, 2, 1, 0,
+            1, 0, 0,
+        ]);
+        result.unwrap();
+        assert!(
+            out.starts_with(
+                "\
+0000000: 01                      ; version: 1
+0000001: 02                      ; features: 0x2 (string table)
+0000002: 03 05                   ; string table subsection (id 3, 5 bytes)
+0000004: 02                      ;   strings: 2
+0000005: 01 61                   ;     string[0]: \"a\"
+0000007: 01 62                   ;     string[1]: \"b\"
+0000009: 00                      ; Web IDL type subsection (id 0, 5 bytes)
+000000a: 01                      ;   types: 1
+000000b: 02                      ;     type[0]: enumeration
+000000c: 02                      ;       values: 2
+000000d: 01                      ;         value: string[1] \"b\"
+000000e: 00                      ;         value: string[0] \"a\"
+"
+            ),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn dump_custom_expressions() {
+        let (out, result) = do_dump(&[1, 0, 1, 1, 0, 0, 0x7b, 1, 128, 3, 1, 2, 3, 1, 255, 0, 0]);
+        result.unwrap();
+        assert_eq!(
+            out,
+            "\
+0000000: 01                      ; version: 1
+0000001: 00                      ; features: 0x0
+0000002: 01                      ; function binding subsection (id 1, 14 bytes)
+0000003: 01                      ;   bindings: 1
+0000004: 00                      ;     binding[0]: import
+0000005: 00                      ;       Wasm function type: 0
+0000006: 7b                      ;       Web IDL type: -5 long
+0000007: 01                      ;       params: 1
+0000008: 80                      ;         outgoing custom (opcode 128)
+0000009: 03 01 02 03             ;           payload: 3 bytes
+000000d: 01                      ;       results: 1
+000000e: ff                      ;         incoming custom (opcode 255)
+000000f: 00                      ;           payload: 0 bytes
+0000010: 00                      ;   binds: 0
+"
+        );
+    }
+
+    #[test]
+    fn dump_unknown_subsections() {
+        let (out, result) = do_dump(&[
+            1, 0x80, 0x80, 0x08, 8, 1, 0xcc, 1, 0, 0, 7, 2, 0xaa, 0xbb, 9, 0,
+        ]);
+        result.unwrap();
+        assert_eq!(
+            out,
+            "\
+0000000: 01                      ; version: 1
+0000001: 80 80 08                ; features: 0x20000
+0000004: 08 01 cc                ; unknown subsection (id 8, 1 bytes)
+0000007: 01                      ; function binding subsection (id 1, 2 bytes)
+0000008: 00                      ;   bindings: 0
+0000009: 00                      ;   binds: 0
+000000a: 07 02 aa bb             ; unknown subsection (id 7, 2 bytes)
+000000e: 09 00                   ; unknown subsection (id 9, 0 bytes)
+"
+        );
+    }
+
+    #[test]
+    fn dump_malformed_section() {
+        let (out, result) = do_dump(&[1, 0, 0, 1, 2, 0x80]);
+        assert_eq!(
+            out,
+            "\
+0000000: 01                      ; version: 1
+0000001: 00                      ; features: 0x0
+0000002: 00                      ; Web IDL type subsection (id 0)
+0000003: 01                      ;   types: 1
+0000004: 02                      ;     type[0]: enumeration
+"
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unexpected end of section at offset 6"
+        );
+
+        let (out, result) = do_dump(&[1, 0, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(out.ends_with("0000004: 00                      ;   binds: 0\n"));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unexpected data after the last subsection at offset 5"
+        );
+
+        let (_, result) = do_dump(&[1, 0, 1, 1, 1, 0, 0x61]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unknown Web IDL scalar type -31 at offset 6"
+        );
+
+        let mut nested = vec![1, 0, 1, 1, 1, 0, 0x7b, 1];
+        nested.extend((0..1000).flat_map(|_| vec![5, 0]));
+        let (_, result) = do_dump(&nested);
+        assert!(result.unwrap_err().to_string().starts_with("too deeply nested"));
+    }
+
+    #[test]
+    fn long_items_are_elided() {
+        let mut data = vec![1, 0, 0, 1, 2, 1, 10];
+        data.extend(b"abcdefghij");
+        data.extend(&[1, 0, 0]);
+        let (out, result) = do_dump(&data);
+        result.unwrap();
+        assert!(
+            out.contains("0000006: 0a 61 62 63 64 65 66 .. ;         value: \"abcdefghij\"\n"),
+            "{}",
+            out
+        );
+    }
+}
@@ -1,25 +1,80 @@
+use super::{
+    CUSTOM_EXPRESSION_OPCODES, KNOWN_FEATURES, LAST_KNOWN_SUBSECTION, OPTIONAL_FEATURES, VERSION,
+};
 use crate::ast::*;
+use crate::unresolved;
 use id_arena::Id;
 use std::collections::HashMap;
 use std::io;
 
-pub(crate) struct EncodeContext<'a> {
-    indices: &'a walrus::IdsToIndices,
+/// Maps references to Wasm functions and function types to their indices in
+/// the Wasm binary that a section is being encoded for.
+///
+/// `walrus::IdsToIndices` implements this, and is what is used when a section
+/// is emitted as a custom section of a `walrus::Module`. Implement it to
+/// encode a section for a Wasm binary that is emitted some other way.
+pub trait Indices {
+    /// Get the index of the given Wasm function.
+    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32;
+
+    /// Get the index of the given Wasm function type.
+    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32;
+}
+
+impl Indices for walrus::IdsToIndices {
+    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32 {
+        self.get_func_index(id)
+    }
+
+    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32 {
+        self.get_type_index(id)
+    }
+}
+
+pub(crate) struct EncodeContext<'a, I: ?Sized> {
+    indices: &'a I,
     webidl_type_id_to_idx: HashMap<Id<WebidlCompoundType>, u32>,
     binding_id_to_idx: HashMap<Id<FunctionBinding>, u32>,
+    strings: Strings,
+}
+
+/// How strings are encoded.
+enum Strings {
+    /// Each string is written inline.
+    Inline,
+    /// Nothing is really being written yet. Instead, count how many times each
+    /// string is used, and remember the order they are first used in.
+    Count(HashMap<String, (usize, usize)>),
+    /// Each string is written as its index in the string table.
+    Table(HashMap<String, u32>),
 }
 
 // Factor this out into a trait to make testing easier.
-pub(crate) trait Indices {
+pub(crate) trait Context: Indices {
     fn assign_webidl_type_index(&mut self, id: Id<WebidlCompoundType>);
     fn webidl_type_index(&self, id: Id<WebidlCompoundType>) -> u32;
     fn assign_binding_index(&mut self, id: Id<FunctionBinding>);
     fn binding_index(&self, id: Id<FunctionBinding>) -> u32;
-    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32;
-    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32;
+    fn string_index(&mut self, s: &str) -> Option<u32>;
 }
 
-impl Indices for EncodeContext<'_> {
+impl<I> Indices for EncodeContext<'_, I>
+where
+    I: ?Sized + Indices,
+{
+    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32 {
+        self.indices.wasm_func_index(id)
+    }
+
+    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32 {
+        self.indices.wasm_func_type_index(id)
+    }
+}
+
+impl<I> Context for EncodeContext<'_, I>
+where
+    I: ?Sized + Indices,
+{
     fn assign_webidl_type_index(&mut self, id: Id<WebidlCompoundType>) {
         let idx = self.webidl_type_id_to_idx.len() as u32;
         let old_idx = self.webidl_type_id_to_idx.insert(id, idx);
@@ -40,39 +95,171 @@ impl Indices for EncodeContext<'_> {
         self.binding_id_to_idx[&id]
     }
 
-    fn wasm_func_index(&self, id: walrus::FunctionId) -> u32 {
-        self.indices.get_func_index(id)
-    }
-
-    fn wasm_func_type_index(&self, id: walrus::TypeId) -> u32 {
-        self.indices.get_type_index(id)
+    fn string_index(&mut self, s: &str) -> Option<u32> {
+        match &mut self.strings {
+            Strings::Inline => None,
+            Strings::Count(counts) => {
+                let first = counts.len();
+                counts.entry(s.to_string()).or_insert((0, first)).0 += 1;
+                Some(0)
+            }
+            Strings::Table(indices) => Some(indices[s]),
+        }
     }
 }
 
-impl EncodeContext<'_> {
-    pub fn new(indices: &walrus::IdsToIndices) -> EncodeContext {
+impl<I> EncodeContext<'_, I>
+where
+    I: ?Sized,
+{
+    pub fn new(indices: &I) -> EncodeContext<'_, I> {
         EncodeContext {
             indices,
             webidl_type_id_to_idx: Default::default(),
             binding_id_to_idx: Default::default(),
+            strings: Strings::Inline,
+        }
+    }
+
+    /// Start counting the strings that are encoded, instead of writing them.
+    pub fn count_strings(&mut self) {
+        self.strings = Strings::Count(HashMap::new());
+    }
+
+    /// Get the string table for the strings counted since `count_strings`.
+    ///
+    /// The most used strings come first, so that they get the smallest
+    /// indices, and otherwise strings are in the order they were first used.
+    pub fn string_table(&self) -> Vec<String> {
+        let counts = match &self.strings {
+            Strings::Count(counts) => counts,
+            _ => return vec![],
+        };
+        let mut table: Vec<_> = counts.iter().collect();
+        table.sort_by_key(|(_, (count, first))| (std::cmp::Reverse(*count), *first));
+        table.into_iter().map(|(s, _)| s.clone()).collect()
+    }
+
+    /// Write strings as their index in `table` from now on.
+    pub fn use_string_table(&mut self, table: &[String]) {
+        let indices = table
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| (s.clone(), idx as u32))
+            .collect();
+        self.strings = Strings::Table(indices);
+    }
+}
+
+/// Write the header of a section, with the current version and the given
+/// features.
+pub(crate) fn encode_header<W>(features: u32, w: &mut W) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    w.uleb(VERSION)?;
+    w.uleb(features)
+}
+
+/// Write the unknown subsections of a section that go at `position`.
+pub(crate) fn encode_extensions<W>(
+    extensions: &Extensions,
+    position: SubsectionPosition,
+    w: &mut W,
+) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    for subsection in &extensions.subsections {
+        if subsection.position == position {
+            w.byte(subsection.id)?;
+            w.bytes(&subsection.data)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that `extensions` can be encoded, and get their features, which must
+/// all be optional ones that this crate doesn't know about.
+pub(crate) fn extension_features(extensions: &Extensions) -> io::Result<u32> {
+    for subsection in &extensions.subsections {
+        if subsection.id <= LAST_KNOWN_SUBSECTION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unknown subsections can't use the id {} of a known subsection",
+                    subsection.id
+                ),
+            ));
         }
     }
+    if extensions.features & (!OPTIONAL_FEATURES | KNOWN_FEATURES) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "only unknown optional features can be kept as extensions, found {:#x}",
+                extensions.features
+            ),
+        ));
+    }
+    Ok(extensions.features)
+}
+
+/// Encode the string table subsection.
+pub(crate) fn encode_string_table<W>(table: &[String], w: &mut W) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    sized_subsection(3, w, |w| {
+        w.uleb(table.len() as u32)?;
+        for s in table {
+            w.string(s)?;
+        }
+        Ok(())
+    })
+}
+
+/// Write a subsection whose contents are prefixed with their size, so that
+/// decoders can skip it.
+fn sized_subsection<W, F>(id: u8, w: &mut W, f: F) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+    F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+{
+    let mut contents = vec![];
+    f(&mut contents)?;
+    w.byte(id)?;
+    w.bytes(&contents)
+}
+
+/// The indices for encoding the unresolved AST, which never refers to walrus
+/// IDs.
+pub(crate) struct NoWalrus;
+
+impl Indices for NoWalrus {
+    fn wasm_func_index(&self, _: walrus::FunctionId) -> u32 {
+        unreachable!("the unresolved AST doesn't contain walrus IDs")
+    }
+
+    fn wasm_func_type_index(&self, _: walrus::TypeId) -> u32 {
+        unreachable!("the unresolved AST doesn't contain walrus IDs")
+    }
 }
 
 pub(crate) trait Encode {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write;
 }
 
-impl<'a, T> Encode for &'a T
+impl<T> Encode for &T
 where
-    T: Encode,
+    T: ?Sized + Encode,
 {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         (**self).encode(cx, w)
@@ -95,9 +282,19 @@ trait WriteExt: io::Write {
         Ok(())
     }
 
+    fn string(&mut self, s: &str) -> io::Result<()> {
+        self.uleb(s.len() as u32)?;
+        self.write_all(s.as_bytes())
+    }
+
+    fn bytes(&mut self, b: &[u8]) -> io::Result<()> {
+        self.uleb(b.len() as u32)?;
+        self.write_all(b)
+    }
+
     fn vec<Cx, I, E>(&mut self, cx: &mut Cx, items: I) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         I: IntoIterator<Item = E>,
         I::IntoIter: ExactSizeIterator,
         E: Encode,
@@ -114,24 +311,37 @@ trait WriteExt: io::Write {
 impl<W> WriteExt for W where W: ?Sized + io::Write {}
 
 impl Encode for String {
-    fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
-        w.uleb(self.len() as u32)?;
-        w.write_all(self.as_bytes())
+        self.as_str().encode(cx, w)
+    }
+}
+
+impl Encode for str {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        match cx.string_index(self) {
+            Some(idx) => w.uleb(idx),
+            None => w.string(self),
+        }
     }
 }
 
 impl Encode for WebidlBindings {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         // Web IDL Type Subsection.
         self.types.encode(cx, w)?;
+        encode_extensions(&self.extensions, SubsectionPosition::AfterTypes, w)?;
 
         // Web IDL Function Binding Subsection.
         w.byte(1)?;
@@ -146,14 +356,115 @@ impl Encode for WebidlBindings {
         w.vec(cx, self.bindings.arena.iter().map(|(_, binding)| binding))?;
 
         // Binds.
-        w.vec(cx, self.binds.arena.iter().map(|(_id, b)| b))
+        w.vec(cx, self.binds.arena.iter().map(|(_id, b)| b))?;
+        encode_extensions(&self.extensions, SubsectionPosition::AfterBindings, w)
     }
 }
 
+/// Whether the section has any names for the names subsection.
+pub(crate) fn has_names(section: &WebidlBindings) -> bool {
+    !section.types.names.is_empty()
+        || !section.bindings.names.is_empty()
+        || section.types.aliases().next().is_some()
+}
+
+/// Like `has_names`, but for an unresolved section.
+pub(crate) fn has_unresolved_names(section: &unresolved::WebidlBindings) -> bool {
+    !section.types.names.is_empty()
+        || section.bindings.iter().any(|b| b.name().is_some())
+        || section.types.aliases().next().is_some()
+}
+
+/// Encode the optional names subsection of `section`.
+///
+/// This must come after the rest of the section has been encoded, so that
+/// every Web IDL type and function binding has been assigned its index.
+pub(crate) fn encode_names<Cx, W>(
+    section: &WebidlBindings,
+    cx: &mut Cx,
+    w: &mut W,
+) -> io::Result<()>
+where
+    Cx: Context,
+    W: ?Sized + io::Write,
+{
+    let types = section
+        .types
+        .names
+        .iter()
+        .map(|(name, id)| (cx.webidl_type_index(*id), name.as_str()))
+        .collect();
+    let bindings = section
+        .bindings
+        .names
+        .iter()
+        .map(|(name, id)| (cx.binding_index(*id), name.as_str()))
+        .collect();
+    names_subsection(cx, w, types, bindings, section.types.aliases().collect())
+}
+
+/// Like `encode_names`, but for an unresolved section.
+pub(crate) fn encode_unresolved_names<Cx, W>(
+    section: &unresolved::WebidlBindings,
+    cx: &mut Cx,
+    w: &mut W,
+) -> io::Result<()>
+where
+    Cx: Context,
+    W: ?Sized + io::Write,
+{
+    let types = section
+        .types
+        .names
+        .iter()
+        .map(|(name, id)| (cx.webidl_type_index(*id), name.as_str()))
+        .collect();
+    let bindings = section
+        .bindings
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, b)| b.name().map(|name| (idx as u32, name)))
+        .collect();
+    names_subsection(cx, w, types, bindings, section.types.aliases().collect())
+}
+
+fn names_subsection<Cx, W>(
+    cx: &mut Cx,
+    w: &mut W,
+    mut types: Vec<(u32, &str)>,
+    mut bindings: Vec<(u32, &str)>,
+    mut aliases: Vec<(&str, WebidlTypeRef)>,
+) -> io::Result<()>
+where
+    Cx: Context,
+    W: ?Sized + io::Write,
+{
+    // Sort everything, so that the encoding doesn't depend on hash map order.
+    types.sort();
+    bindings.sort();
+    aliases.sort_by_key(|(name, _)| *name);
+
+    sized_subsection(2, w, |w| {
+        for names in &[types, bindings] {
+            w.uleb(names.len() as u32)?;
+            for (idx, name) in names {
+                w.uleb(*idx)?;
+                name.encode(cx, w)?;
+            }
+        }
+        w.uleb(aliases.len() as u32)?;
+        for (name, ty) in aliases {
+            name.encode(cx, w)?;
+            ty.encode(cx, w)?;
+        }
+        Ok(())
+    })
+}
+
 impl Encode for WebidlTypes {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.byte(0)?;
@@ -167,7 +478,7 @@ impl Encode for WebidlTypes {
 impl Encode for WebidlType {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         self.ty.encode(cx, w)
@@ -177,7 +488,7 @@ impl Encode for WebidlType {
 impl Encode for WebidlCompoundType {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -204,7 +515,7 @@ impl Encode for WebidlCompoundType {
 impl Encode for WebidlFunction {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         self.kind.encode(cx, w)?;
@@ -221,7 +532,7 @@ impl Encode for WebidlFunction {
 impl Encode for WebidlFunctionKind {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -238,7 +549,7 @@ impl Encode for WebidlFunctionKind {
 impl Encode for WebidlTypeRef {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -251,7 +562,7 @@ impl Encode for WebidlTypeRef {
 impl Encode for WebidlScalarType {
     fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -292,7 +603,7 @@ impl Encode for WebidlScalarType {
 impl Encode for WebidlDictionary {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.fields)
@@ -302,7 +613,7 @@ impl Encode for WebidlDictionary {
 impl Encode for WebidlDictionaryField {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         self.name.encode(cx, w)?;
@@ -313,7 +624,7 @@ impl Encode for WebidlDictionaryField {
 impl Encode for WebidlEnumeration {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.values)
@@ -323,7 +634,7 @@ impl Encode for WebidlEnumeration {
 impl Encode for WebidlUnion {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.members)
@@ -333,7 +644,7 @@ impl Encode for WebidlUnion {
 impl Encode for FunctionBinding {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -352,7 +663,7 @@ impl Encode for FunctionBinding {
 impl Encode for ImportBinding {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         self.wasm_ty.encode(cx, w)?;
@@ -365,7 +676,7 @@ impl Encode for ImportBinding {
 impl Encode for ExportBinding {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         self.wasm_ty.encode(cx, w)?;
@@ -378,7 +689,7 @@ impl Encode for ExportBinding {
 impl Encode for walrus::ValType {
     fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -395,7 +706,7 @@ impl Encode for walrus::ValType {
 impl Encode for walrus::TypeId {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.uleb(cx.wasm_func_type_index(*self))
@@ -405,7 +716,7 @@ impl Encode for walrus::TypeId {
 impl Encode for OutgoingBindingMap {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.bindings)
@@ -415,42 +726,33 @@ impl Encode for OutgoingBindingMap {
 impl Encode for OutgoingBindingExpression {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
             OutgoingBindingExpression::As(e) => {
                 w.byte(0)?;
-                e.ty.encode(cx, w)?;
-                w.uleb(e.idx)
+                e.encode(cx, w)
             }
             OutgoingBindingExpression::Utf8Str(e) => {
                 w.byte(1)?;
-                e.ty.encode(cx, w)?;
-                w.uleb(e.offset)?;
-                w.uleb(e.length)
+                e.encode(cx, w)
             }
             OutgoingBindingExpression::Utf8CStr(e) => {
                 w.byte(2)?;
-                e.ty.encode(cx, w)?;
-                w.uleb(e.offset)
+                e.encode(cx, w)
             }
             OutgoingBindingExpression::I32ToEnum(e) => {
                 w.byte(3)?;
-                e.ty.encode(cx, w)?;
-                w.uleb(e.idx)
+                e.encode(cx, w)
             }
             OutgoingBindingExpression::View(e) => {
                 w.byte(4)?;
-                e.ty.encode(cx, w)?;
-                w.uleb(e.offset)?;
-                w.uleb(e.length)
+                e.encode(cx, w)
             }
             OutgoingBindingExpression::Copy(e) => {
                 w.byte(5)?;
-                e.ty.encode(cx, w)?;
-                w.uleb(e.offset)?;
-                w.uleb(e.length)
+                e.encode(cx, w)
             }
             OutgoingBindingExpression::Dict(e) => {
                 w.byte(6)?;
@@ -463,14 +765,96 @@ impl Encode for OutgoingBindingExpression {
                 e.binding.encode(cx, w)?;
                 w.uleb(e.idx)
             }
+            OutgoingBindingExpression::Custom(e) => e.encode(cx, w),
         }
     }
 }
 
+// The outgoing binding expressions that are shared with the unresolved AST.
+
+impl Encode for OutgoingBindingExpressionAs {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.ty.encode(cx, w)?;
+        w.uleb(self.idx)
+    }
+}
+
+impl Encode for OutgoingBindingExpressionUtf8Str {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.ty.encode(cx, w)?;
+        w.uleb(self.offset)?;
+        w.uleb(self.length)
+    }
+}
+
+impl Encode for OutgoingBindingExpressionUtf8CStr {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.ty.encode(cx, w)?;
+        w.uleb(self.offset)
+    }
+}
+
+impl Encode for OutgoingBindingExpressionI32ToEnum {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.ty.encode(cx, w)?;
+        w.uleb(self.idx)
+    }
+}
+
+impl Encode for OutgoingBindingExpressionView {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.ty.encode(cx, w)?;
+        w.uleb(self.offset)?;
+        w.uleb(self.length)
+    }
+}
+
+impl Encode for OutgoingBindingExpressionCopy {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.ty.encode(cx, w)?;
+        w.uleb(self.offset)?;
+        w.uleb(self.length)
+    }
+}
+
+impl Encode for OutgoingBindingExpressionCustom {
+    fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        custom_expression(self.opcode, &self.payload, w)
+    }
+}
+
 impl Encode for Id<FunctionBinding> {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.uleb(cx.binding_index(*self))
@@ -480,7 +864,7 @@ impl Encode for Id<FunctionBinding> {
 impl Encode for IncomingBindingMap {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.vec(cx, &self.bindings)
@@ -490,7 +874,7 @@ impl Encode for IncomingBindingMap {
 impl Encode for IncomingBindingExpression {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         match self {
@@ -529,14 +913,44 @@ impl Encode for IncomingBindingExpression {
                 e.binding.encode(cx, w)?;
                 e.expr.encode(cx, w)
             }
+            IncomingBindingExpression::Custom(e) => e.encode(cx, w),
         }
     }
 }
 
+impl Encode for IncomingBindingExpressionCustom {
+    fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        custom_expression(self.opcode, &self.payload, w)
+    }
+}
+
+/// Custom expressions are their opcode followed by their payload, prefixed
+/// with its size, so that decoders can skip the ones they don't know.
+fn custom_expression<W>(opcode: u8, payload: &[u8], w: &mut W) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    if opcode < CUSTOM_EXPRESSION_OPCODES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "custom binding expression opcodes must be from {} to 255, found {}",
+                CUSTOM_EXPRESSION_OPCODES, opcode
+            ),
+        ));
+    }
+    w.byte(opcode)?;
+    w.bytes(payload)
+}
+
 impl Encode for Bind {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         self.func.encode(cx, w)?;
@@ -547,20 +961,210 @@ impl Encode for Bind {
 impl Encode for walrus::FunctionId {
     fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
     where
-        Cx: Indices,
+        Cx: Context,
         W: ?Sized + io::Write,
     {
         w.uleb(cx.wasm_func_index(*self))
     }
 }
 
+impl Encode for unresolved::WebidlBindings {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        // Web IDL Type Subsection.
+        self.types.encode(cx, w)?;
+        encode_extensions(&self.extensions, SubsectionPosition::AfterTypes, w)?;
+
+        // Web IDL Function Binding Subsection. Function bindings already refer
+        // to each other by index.
+        w.byte(1)?;
+        w.vec(cx, &self.bindings)?;
+        w.vec(cx, &self.binds)?;
+        encode_extensions(&self.extensions, SubsectionPosition::AfterBindings, w)
+    }
+}
+
+impl Encode for unresolved::WasmRef {
+    fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            unresolved::WasmRef::Indexed(idx) => w.uleb(*idx),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "only references to Wasm functions and function types by index \
+                     can be encoded, found {:?}",
+                    self
+                ),
+            )),
+        }
+    }
+}
+
+impl Encode for unresolved::WasmFuncRef {
+    fn encode<Cx, W>(&self, _cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            unresolved::WasmFuncRef::Indexed(idx) => w.uleb(*idx),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "only references to Wasm functions and function types by index \
+                     can be encoded, found {:?}",
+                    self
+                ),
+            )),
+        }
+    }
+}
+
+impl Encode for unresolved::FunctionBinding {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            unresolved::FunctionBinding::Import(i) => {
+                w.byte(0)?;
+                i.wasm_ty.encode(cx, w)?;
+                i.webidl_ty.encode(cx, w)?;
+                w.vec(cx, &i.params.bindings)?;
+                w.vec(cx, &i.result.bindings)
+            }
+            unresolved::FunctionBinding::Export(e) => {
+                w.byte(1)?;
+                e.wasm_ty.encode(cx, w)?;
+                e.webidl_ty.encode(cx, w)?;
+                w.vec(cx, &e.params.bindings)?;
+                w.vec(cx, &e.result.bindings)
+            }
+        }
+    }
+}
+
+impl Encode for unresolved::OutgoingBindingExpression {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            unresolved::OutgoingBindingExpression::As(e) => {
+                w.byte(0)?;
+                e.encode(cx, w)
+            }
+            unresolved::OutgoingBindingExpression::Utf8Str(e) => {
+                w.byte(1)?;
+                e.encode(cx, w)
+            }
+            unresolved::OutgoingBindingExpression::Utf8CStr(e) => {
+                w.byte(2)?;
+                e.encode(cx, w)
+            }
+            unresolved::OutgoingBindingExpression::I32ToEnum(e) => {
+                w.byte(3)?;
+                e.encode(cx, w)
+            }
+            unresolved::OutgoingBindingExpression::View(e) => {
+                w.byte(4)?;
+                e.encode(cx, w)
+            }
+            unresolved::OutgoingBindingExpression::Copy(e) => {
+                w.byte(5)?;
+                e.encode(cx, w)
+            }
+            unresolved::OutgoingBindingExpression::Dict(e) => {
+                w.byte(6)?;
+                e.ty.encode(cx, w)?;
+                w.vec(cx, &e.fields)
+            }
+            unresolved::OutgoingBindingExpression::BindExport(e) => {
+                w.byte(7)?;
+                e.ty.encode(cx, w)?;
+                w.uleb(e.binding)?;
+                w.uleb(e.idx)
+            }
+            unresolved::OutgoingBindingExpression::Custom(e) => e.encode(cx, w),
+        }
+    }
+}
+
+impl Encode for unresolved::IncomingBindingExpression {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        match self {
+            unresolved::IncomingBindingExpression::Get(e) => {
+                w.byte(0)?;
+                w.uleb(e.idx)
+            }
+            unresolved::IncomingBindingExpression::As(e) => {
+                w.byte(1)?;
+                e.ty.encode(cx, w)?;
+                e.expr.encode(cx, w)
+            }
+            unresolved::IncomingBindingExpression::AllocUtf8Str(e) => {
+                w.byte(2)?;
+                e.alloc_func_name.encode(cx, w)?;
+                e.expr.encode(cx, w)
+            }
+            unresolved::IncomingBindingExpression::AllocCopy(e) => {
+                w.byte(3)?;
+                e.alloc_func_name.encode(cx, w)?;
+                e.expr.encode(cx, w)
+            }
+            unresolved::IncomingBindingExpression::EnumToI32(e) => {
+                w.byte(4)?;
+                e.ty.encode(cx, w)?;
+                e.expr.encode(cx, w)
+            }
+            unresolved::IncomingBindingExpression::Field(e) => {
+                w.byte(5)?;
+                w.uleb(e.idx)?;
+                e.expr.encode(cx, w)
+            }
+            unresolved::IncomingBindingExpression::BindImport(e) => {
+                w.byte(6)?;
+                e.ty.encode(cx, w)?;
+                w.uleb(e.binding)?;
+                e.expr.encode(cx, w)
+            }
+            unresolved::IncomingBindingExpression::Custom(e) => e.encode(cx, w),
+        }
+    }
+}
+
+impl Encode for unresolved::Bind {
+    fn encode<Cx, W>(&self, cx: &mut Cx, w: &mut W) -> io::Result<()>
+    where
+        Cx: Context,
+        W: ?Sized + io::Write,
+    {
+        self.func.encode(cx, w)?;
+        w.uleb(self.binding)
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     struct TestIndices;
 
-    impl Indices for TestIndices {
+    impl Context for TestIndices {
         fn assign_webidl_type_index(&mut self, _: Id<WebidlCompoundType>) {}
 
         fn webidl_type_index(&self, _: Id<WebidlCompoundType>) -> u32 {
@@ -573,6 +1177,12 @@ mod tests {
             22
         }
 
+        fn string_index(&mut self, _: &str) -> Option<u32> {
+            None
+        }
+    }
+
+    impl Indices for TestIndices {
         fn wasm_func_index(&self, _: walrus::FunctionId) -> u32 {
             33
         }
@@ -601,13 +1211,12 @@ mod tests {
     }
 
     fn get_wasm_func_ref(m: &mut walrus::Module) -> walrus::FunctionId {
-        for f in m.funcs.iter() {
-            return f.id().into();
+        if let Some(f) = m.funcs.iter().next() {
+            return f.id();
         }
 
         let ty = m.types.add(&[], &[]);
-        let id = walrus::FunctionBuilder::new().finish(ty, vec![], vec![], m);
-        id.into()
+        walrus::FunctionBuilder::new().finish(ty, vec![], vec![], m)
     }
 
     fn get_wasm_func_type_ref(m: &mut walrus::Module) -> walrus::TypeId {
@@ -623,10 +1232,32 @@ mod tests {
             params: OutgoingBindingMap { bindings: vec![] },
             result: IncomingBindingMap { bindings: vec![] },
         });
-        let id: Id<FunctionBinding> = id.into();
         id.into()
     }
 
+    // Every section starts with `VERSION` and the features that it uses.
+    // Decoders reject sections with a newer version or with unknown required
+    // features, so bump `VERSION` for changes that older decoders would
+    // misread, and add a feature flag for each new optional subsection.
+    #[test]
+    fn header() {
+        let mut bytes = vec![];
+        crate::binary::encode(&WebidlBindings::default(), &TestIndices, &mut bytes).unwrap();
+        assert_eq!(bytes, [VERSION as u8, 0, 0, 0, 1, 0, 0]);
+
+        let section = crate::text::parse_unresolved(r#"type $E (enum "a")"#).unwrap();
+        let mut config = crate::binary::EncodeConfig::new();
+        let mut bytes = vec![];
+        config.encode_unresolved(&section, &mut bytes).unwrap();
+        assert_eq!(bytes[..4], [VERSION as u8, 0x80, 0x80, 0x04]);
+
+        config.generate_names_subsection(false);
+        config.generate_string_table(true);
+        let mut bytes = vec![];
+        config.encode_unresolved(&section, &mut bytes).unwrap();
+        assert_eq!(bytes[..2], [VERSION as u8, 2]);
+    }
+
     macro_rules! assert_encoding {
         (
             $(
@@ -1,18 +1,285 @@
+mod check;
+mod decode;
+mod dump;
 mod encode;
+pub mod read;
 
-use self::encode::{Encode, EncodeContext};
-use crate::ast::WebidlBindings;
+pub use self::check::{DanglingRef, EncodeError, EncodeErrorItem};
+use self::decode::{Decode, DecodeContext, Reader};
+#[cfg(feature = "serde")]
+pub(crate) use self::decode::scalar_type;
+use self::dump::Dumper;
+use self::encode::{Encode, EncodeContext, NoWalrus};
+pub use self::encode::Indices;
+use crate::ast::{Extensions, SubsectionPosition, WebidlBindings};
+use crate::unresolved;
 use std::io;
 
+/// The version of the binary format that this crate encodes, which is the first
+/// field of every section.
+///
+/// The version is only bumped for changes that older decoders can't make sense
+/// of. Decoding rejects sections with a newer version than this.
+pub const VERSION: u32 = 1;
+
+/// The feature flag for the names subsection.
+///
+/// Names don't change what the bindings mean, so this is an optional feature,
+/// and decoders that don't know about it skip the subsection.
+pub const FEATURE_NAMES: u32 = 1 << 16;
+
+/// The feature flag for the string table subsection.
+pub const FEATURE_STRING_TABLE: u32 = 1 << 1;
+
+/// All of the feature flags that this crate knows about.
+pub const KNOWN_FEATURES: u32 = FEATURE_NAMES | FEATURE_STRING_TABLE;
+
+/// The feature flags that decoders which don't know about them may ignore.
+///
+/// The low 16 bits are required features, which change how the section is
+/// encoded, so an unknown one is a decoding error. The high 16 bits are
+/// optional features, which only add information that can be done without,
+/// so unknown ones are ignored.
+pub const OPTIONAL_FEATURES: u32 = 0xffff_0000;
+
+/// The first opcode of the range that is reserved for custom binding
+/// expressions, which goes up to 255. Binding expressions from the proposal
+/// never use these opcodes.
+pub const CUSTOM_EXPRESSION_OPCODES: u8 = 128;
+
+/// The biggest subsection id that this crate knows about. Subsections with
+/// bigger ids may come between any of the known ones, and are kept as
+/// `ast::UnknownSubsection`s.
+const LAST_KNOWN_SUBSECTION: u8 = 3;
+
 /// Encode the given Web IDL bindings section into the given write-able.
-pub fn encode<W>(
-    section: &WebidlBindings,
-    indices: &walrus::IdsToIndices,
-    into: &mut W,
-) -> io::Result<()>
+///
+/// `indices` gives the index of each Wasm function and function type that the
+/// section refers to. It is usually the `walrus::IdsToIndices` from emitting
+/// the module, but any other `Indices` implementation works too.
+///
+/// This uses the default `EncodeConfig`, which includes the names subsection.
+///
+/// If the section refers to a Web IDL type or function binding that isn't in
+/// it, nothing is written, and an `io::ErrorKind::InvalidInput` error is
+/// returned whose inner error is the `EncodeError` for the first such
+/// reference. References to Wasm functions and function types can only be
+/// checked against the module, with `check`.
+pub fn encode<I, W>(section: &WebidlBindings, indices: &I, into: &mut W) -> io::Result<()>
+where
+    I: ?Sized + Indices,
+    W: io::Write,
+{
+    EncodeConfig::new().encode(section, indices, into)
+}
+
+/// Encode an unresolved Web IDL bindings section, without a Wasm module.
+///
+/// Every reference to a Wasm function or function type must be a
+/// `WasmFuncRef::Indexed` or a `WasmRef::Indexed`, which is encoded as is. Any
+/// other reference is an `io::ErrorKind::InvalidInput` error, since it can't
+/// be resolved to an index without the module.
+///
+/// This uses the default `EncodeConfig`, which includes the names subsection.
+pub fn encode_unresolved<W>(section: &unresolved::WebidlBindings, into: &mut W) -> io::Result<()>
+where
+    W: io::Write,
+{
+    EncodeConfig::new().encode_unresolved(section, into)
+}
+
+/// Options for encoding a Web IDL bindings section.
+///
+/// To leave the names out of the section of a `walrus::Module`, or to use a
+/// string table, encode it with this and add the result to the module as a
+/// `walrus::RawCustomSection`, rather than adding the `WebidlBindings` itself.
+#[derive(Clone, Debug)]
+pub struct EncodeConfig {
+    generate_names_subsection: bool,
+    generate_string_table: bool,
+}
+
+impl Default for EncodeConfig {
+    fn default() -> EncodeConfig {
+        EncodeConfig {
+            generate_names_subsection: true,
+            generate_string_table: false,
+        }
+    }
+}
+
+impl EncodeConfig {
+    /// Create the default configuration.
+    pub fn new() -> EncodeConfig {
+        EncodeConfig::default()
+    }
+
+    /// Whether to include the names subsection, which records the names of Web
+    /// IDL types and function bindings, and type aliases. Defaults to `true`.
+    ///
+    /// The names are only for debugging and printing, so release builds may
+    /// want to leave them out.
+    pub fn generate_names_subsection(&mut self, generate: bool) -> &mut EncodeConfig {
+        self.generate_names_subsection = generate;
+        self
+    }
+
+    /// Whether to write each distinct string once, in a string table
+    /// subsection, and refer to it by index everywhere else. Defaults to
+    /// `false`.
+    ///
+    /// Sections that repeat dictionary field names, enumeration values or
+    /// allocator function names get smaller, while sections that don't
+    /// repeat strings get a little bigger.
+    pub fn generate_string_table(&mut self, generate: bool) -> &mut EncodeConfig {
+        self.generate_string_table = generate;
+        self
+    }
+
+    /// Encode the given Web IDL bindings section into the given write-able,
+    /// like `binary::encode`.
+    pub fn encode<I, W>(&self, section: &WebidlBindings, indices: &I, into: &mut W) -> io::Result<()>
+    where
+        I: ?Sized + Indices,
+        W: io::Write,
+    {
+        if let Some(error) = check::check(section, None).into_iter().next() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, error));
+        }
+        let names = self.generate_names_subsection && encode::has_names(section);
+        self.encode_with(indices, into, names, &section.extensions, |cx, w| {
+            section.encode(cx, w)?;
+            if names {
+                encode::encode_names(section, cx, w)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Encode an unresolved Web IDL bindings section, like
+    /// `binary::encode_unresolved`.
+    pub fn encode_unresolved<W>(
+        &self,
+        section: &unresolved::WebidlBindings,
+        into: &mut W,
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let names = self.generate_names_subsection && encode::has_unresolved_names(section);
+        self.encode_with(&NoWalrus, into, names, &section.extensions, |cx, w| {
+            section.encode(cx, w)?;
+            if names {
+                encode::encode_unresolved_names(section, cx, w)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn encode_with<I, W, F>(
+        &self,
+        indices: &I,
+        into: &mut W,
+        names: bool,
+        extensions: &Extensions,
+        encode: F,
+    ) -> io::Result<()>
+    where
+        I: ?Sized + Indices,
+        W: io::Write,
+        F: Fn(&mut EncodeContext<I>, &mut dyn io::Write) -> io::Result<()>,
+    {
+        let mut features = encode::extension_features(extensions)?;
+        if names {
+            features |= FEATURE_NAMES;
+        }
+
+        let mut cx = EncodeContext::new(indices);
+        let mut table = vec![];
+        if self.generate_string_table {
+            // Encode the section once just to find out which strings it uses,
+            // and how often.
+            cx.count_strings();
+            encode(&mut cx, &mut io::sink())?;
+            table = cx.string_table();
+
+            cx = EncodeContext::new(indices);
+            if !table.is_empty() {
+                features |= FEATURE_STRING_TABLE;
+                cx.use_string_table(&table);
+            }
+        }
+
+        encode::encode_header(features, into)?;
+        encode::encode_extensions(extensions, SubsectionPosition::AfterHeader, into)?;
+        if !table.is_empty() {
+            encode::encode_string_table(&table, into)?;
+        }
+        encode::encode_extensions(extensions, SubsectionPosition::AfterStringTable, into)?;
+        encode(&mut cx, into)?;
+        encode::encode_extensions(extensions, SubsectionPosition::AfterNames, into)
+    }
+}
+
+/// Check that every reference in `section` can be encoded for `module`.
+///
+/// Every Web IDL type and function binding that the section refers to must be
+/// in it, rather than in a different `WebidlBindings`, and every Wasm function
+/// and function type must still be in `module`. Otherwise, an error naming the
+/// type, function binding or bind with each dangling reference is returned.
+///
+/// walrus panics on a reference to a Wasm function or function type that isn't
+/// in the module while it emits the module, so run this first.
+pub fn check(section: &WebidlBindings, module: &walrus::Module) -> Result<(), Vec<EncodeError>> {
+    let errors = check::check(section, Some(module));
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Decode a Web IDL bindings section from the data of its custom section.
+///
+/// Wasm functions and function types are referred to by their index in the
+/// original Wasm binary, so this is usually called from a
+/// `walrus::ModuleConfig::on_parse` callback. Returns an error with the byte
+/// offset into `data` of the first malformed item or out of bounds index.
+pub fn decode(
+    indices_to_ids: &walrus::IndicesToIds,
+    data: &[u8],
+) -> Result<WebidlBindings, failure::Error> {
+    let cx = &mut DecodeContext::new(indices_to_ids);
+    WebidlBindings::decode(cx, &mut Reader::new(data))
+}
+
+/// Decode a Web IDL bindings section into the unresolved AST, without a Wasm
+/// module.
+///
+/// Wasm functions and function types are left as `WasmFuncRef::Indexed` and
+/// `WasmRef::Indexed`, so they aren't checked against anything, but references
+/// to Web IDL types and function bindings are still checked to be in bounds.
+pub fn decode_unresolved(data: &[u8]) -> Result<unresolved::WebidlBindings, failure::Error> {
+    let cx = &mut DecodeContext::new(&());
+    unresolved::WebidlBindings::decode(cx, &mut Reader::new(data))
+}
+
+/// Write an annotated dump of the data of a Web IDL bindings custom section,
+/// in the style of `wasm-objdump`.
+///
+/// Each line has the byte offset and raw bytes of one item, such as a
+/// subsection id, a `vec` length, a type reference or a binding expression
+/// opcode, along with what it decodes to. Unlike `decode`, this doesn't need
+/// the Wasm module, and leaves indices unresolved.
+///
+/// If `data` is malformed, the dump of everything before the malformed item is
+/// still written before returning the error.
+pub fn dump<W>(data: &[u8], into: &mut W) -> Result<(), failure::Error>
 where
     W: io::Write,
 {
-    let cx = &mut EncodeContext::new(indices);
-    section.encode(cx, into)
+    let mut dumper = Dumper::new(data);
+    let result = dumper.section();
+    dumper.finish(into)?;
+    result
 }
@@ -0,0 +1,1046 @@
+//! A zero-copy, streaming reader for the binary format.
+//!
+//! Unlike `binary::decode`, this never builds a `WebidlBindings`. Instead,
+//! `SectionReader` yields the section's subsections, types, function bindings
+//! and binds one at a time, as `Payload`s that borrow names and strings from
+//! the section's data. Lists nested inside an item, such as a dictionary's
+//! fields or a function binding's expressions, are `Items` iterators that are
+//! only decoded when they are iterated over.
+//!
+//! Every item comes with the byte offset into the section's data that it
+//! starts at, and every `Items` with the range of bytes that it covers, so
+//! that tools can report positions or slice out parts of the section.
+//!
+//! References to Web IDL types, function bindings, and Wasm functions and
+//! function types are raw indices that aren't checked against anything, but
+//! the structure of each item is checked before it is yielded, so a malformed
+//! section is reported at the item that contains the malformed bytes.
+//!
+//! ```
+//! use wasm_webidl_bindings::binary::read::{Payload, SectionReader};
+//!
+//! # fn foo(data: &[u8]) -> Result<(), failure::Error> {
+//! for payload in SectionReader::new(data) {
+//!     if let Payload::FunctionBinding { offset, binding } = payload? {
+//!         println!("function binding at offset {}: {:?}", offset, binding);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::decode::{scalar_type, Decode, Reader};
+use super::dump::MAX_DEPTH;
+use super::{
+    CUSTOM_EXPRESSION_OPCODES, FEATURE_NAMES, FEATURE_STRING_TABLE, LAST_KNOWN_SUBSECTION,
+};
+use crate::ast::WebidlScalarType;
+use std::fmt;
+use std::ops::Range;
+
+/// A reference to a Web IDL type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeRef {
+    /// The index of a type in the Web IDL type subsection.
+    Index(u32),
+    Scalar(WebidlScalarType),
+}
+
+/// One item of a section, in the order that it appears in the section.
+#[derive(Clone, Debug)]
+pub enum Payload<'a> {
+    /// The section's header. Sections with a version newer than
+    /// `binary::VERSION`, or with unknown required features, are an error
+    /// instead. Unknown optional features are left out of `features`.
+    Header {
+        offset: usize,
+        version: u32,
+        features: u32,
+    },
+    /// The optional string table subsection. When it is present, strings in
+    /// the rest of the section are written as indices into it, but they are
+    /// still yielded as the strings themselves.
+    StringTable {
+        offset: usize,
+        strings: Items<'a, &'a str>,
+    },
+    /// The start of the Web IDL type subsection, which is followed by `count`
+    /// `Type` payloads. Sections without any types may leave it out.
+    TypeSubsection {
+        offset: usize,
+        count: u32,
+    },
+    Type {
+        offset: usize,
+        ty: Type<'a>,
+    },
+    /// The start of the function binding subsection, which is followed by
+    /// `count` `FunctionBinding` payloads.
+    FunctionBindingSubsection {
+        offset: usize,
+        count: u32,
+    },
+    FunctionBinding {
+        offset: usize,
+        binding: FunctionBinding<'a>,
+    },
+    /// The start of the binds, which are followed by `count` `Bind` payloads.
+    Binds {
+        offset: usize,
+        count: u32,
+    },
+    /// A bind of the Wasm function with index `func` to the function binding
+    /// with index `binding`.
+    Bind {
+        offset: usize,
+        func: u32,
+        binding: u32,
+    },
+    /// The optional names subsection, with the names of Web IDL types and
+    /// function bindings by index, and type aliases.
+    NamesSubsection {
+        offset: usize,
+        types: Items<'a, Name<'a>>,
+        bindings: Items<'a, Name<'a>>,
+        aliases: Items<'a, TypeAlias<'a>>,
+    },
+    /// A subsection with an unknown id, which can come between or after the
+    /// known ones, along with its contents.
+    UnknownSubsection {
+        offset: usize,
+        id: u8,
+        data: &'a [u8],
+    },
+}
+
+impl Payload<'_> {
+    /// Get the byte offset into the section's data that this payload starts
+    /// at.
+    pub fn offset(&self) -> usize {
+        match *self {
+            Payload::Header { offset, .. }
+            | Payload::StringTable { offset, .. }
+            | Payload::TypeSubsection { offset, .. }
+            | Payload::Type { offset, .. }
+            | Payload::FunctionBindingSubsection { offset, .. }
+            | Payload::FunctionBinding { offset, .. }
+            | Payload::Binds { offset, .. }
+            | Payload::Bind { offset, .. }
+            | Payload::NamesSubsection { offset, .. }
+            | Payload::UnknownSubsection { offset, .. } => offset,
+        }
+    }
+}
+
+/// A Web IDL type.
+#[derive(Clone, Debug)]
+pub enum Type<'a> {
+    Function {
+        kind: FunctionKind,
+        params: Items<'a, TypeRef>,
+        result: Option<TypeRef>,
+    },
+    Dictionary {
+        fields: Items<'a, DictionaryField<'a>>,
+    },
+    Enumeration {
+        values: Items<'a, &'a str>,
+    },
+    Union {
+        members: Items<'a, TypeRef>,
+    },
+}
+
+/// The kind of a Web IDL function type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunctionKind {
+    Static,
+    /// A method, with the type of its receiver.
+    Method(TypeRef),
+    Constructor,
+}
+
+/// A field of a Web IDL dictionary type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DictionaryField<'a> {
+    pub name: &'a str,
+    pub ty: TypeRef,
+}
+
+/// The name of the Web IDL type or function binding with index `idx`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Name<'a> {
+    pub idx: u32,
+    pub name: &'a str,
+}
+
+/// A type alias, which is an additional name for a type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeAlias<'a> {
+    pub name: &'a str,
+    pub ty: TypeRef,
+}
+
+/// A function binding. `wasm_ty` is the index of a Wasm function type, and
+/// `webidl_ty` refers to a Web IDL function type.
+#[derive(Clone, Debug)]
+pub enum FunctionBinding<'a> {
+    Import {
+        wasm_ty: u32,
+        webidl_ty: TypeRef,
+        params: Items<'a, OutgoingExpr<'a>>,
+        result: Items<'a, IncomingExpr<'a>>,
+    },
+    Export {
+        wasm_ty: u32,
+        webidl_ty: TypeRef,
+        params: Items<'a, IncomingExpr<'a>>,
+        result: Items<'a, OutgoingExpr<'a>>,
+    },
+}
+
+/// An outgoing binding expression. `binding` is the index of a function
+/// binding.
+#[derive(Clone, Debug)]
+pub enum OutgoingExpr<'a> {
+    As {
+        ty: TypeRef,
+        idx: u32,
+    },
+    Utf8Str {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Utf8CStr {
+        ty: TypeRef,
+        offset: u32,
+    },
+    I32ToEnum {
+        ty: TypeRef,
+        idx: u32,
+    },
+    View {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Copy {
+        ty: TypeRef,
+        offset: u32,
+        length: u32,
+    },
+    Dict {
+        ty: TypeRef,
+        fields: Items<'a, OutgoingExpr<'a>>,
+    },
+    BindExport {
+        ty: TypeRef,
+        binding: u32,
+        idx: u32,
+    },
+    Custom {
+        opcode: u8,
+        payload: &'a [u8],
+    },
+}
+
+/// An incoming binding expression. `ty` in `BindImport` is the index of a Wasm
+/// function type, and `binding` is the index of a function binding.
+#[derive(Clone, Debug)]
+pub enum IncomingExpr<'a> {
+    Get {
+        idx: u32,
+    },
+    As {
+        ty: walrus::ValType,
+        expr: NestedIncomingExpr<'a>,
+    },
+    AllocUtf8Str {
+        alloc_func_name: &'a str,
+        expr: NestedIncomingExpr<'a>,
+    },
+    AllocCopy {
+        alloc_func_name: &'a str,
+        expr: NestedIncomingExpr<'a>,
+    },
+    EnumToI32 {
+        ty: TypeRef,
+        expr: NestedIncomingExpr<'a>,
+    },
+    Field {
+        idx: u32,
+        expr: NestedIncomingExpr<'a>,
+    },
+    BindImport {
+        ty: u32,
+        binding: u32,
+        expr: NestedIncomingExpr<'a>,
+    },
+    Custom {
+        opcode: u8,
+        payload: &'a [u8],
+    },
+}
+
+/// An incoming binding expression nested inside another one, which is only
+/// decoded by `read`.
+#[derive(Clone)]
+pub struct NestedIncomingExpr<'a> {
+    r: Reader<'a>,
+    depth: usize,
+}
+
+impl<'a> NestedIncomingExpr<'a> {
+    /// Check the expression to find where it ends.
+    fn new(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        if depth >= MAX_DEPTH {
+            return r.error(r.pos(), "too deeply nested");
+        }
+        let expr = r.clone();
+        IncomingExpr::read(r, depth + 1)?;
+        Ok(NestedIncomingExpr {
+            r: expr,
+            depth: depth + 1,
+        })
+    }
+
+    /// Get the byte offset into the section's data that the expression starts
+    /// at.
+    pub fn offset(&self) -> usize {
+        self.r.pos()
+    }
+
+    /// Decode the expression.
+    pub fn read(&self) -> Result<IncomingExpr<'a>, failure::Error> {
+        IncomingExpr::read(&mut self.r.clone(), self.depth)
+    }
+}
+
+impl fmt::Debug for NestedIncomingExpr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.read() {
+            Ok(expr) => expr.fmt(f),
+            Err(e) => write!(f, "<{}>", e),
+        }
+    }
+}
+
+/// A `vec` of items nested inside another item, which are decoded as they are
+/// iterated over.
+///
+/// Yields each item along with the byte offset that it starts at.
+pub struct Items<'a, T> {
+    r: Reader<'a>,
+    read: ReadFn<'a, T>,
+    depth: usize,
+    range: Range<usize>,
+    len: u32,
+    remaining: u32,
+}
+
+/// Reads one item at the given nesting depth.
+type ReadFn<'a, T> = fn(&mut Reader<'a>, usize) -> Result<T, failure::Error>;
+
+impl<'a, T> Items<'a, T> {
+    /// Read the items' length, and check each of them to find where they end.
+    fn new(r: &mut Reader<'a>, depth: usize, read: ReadFn<'a, T>) -> Result<Self, failure::Error> {
+        if depth >= MAX_DEPTH {
+            return r.error(r.pos(), "too deeply nested");
+        }
+        let start = r.pos();
+        let len = r.uleb()?;
+        let items = r.clone();
+        for _ in 0..len {
+            read(r, depth + 1)?;
+        }
+        Ok(Items {
+            r: items,
+            read,
+            depth: depth + 1,
+            range: start..r.pos(),
+            len,
+            remaining: len,
+        })
+    }
+
+    /// Get the number of items.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Whether there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the range of bytes in the section's data that the items, including
+    /// their `vec` length, take up.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl<T> Clone for Items<'_, T> {
+    fn clone(&self) -> Self {
+        Items {
+            r: self.r.clone(),
+            read: self.read,
+            depth: self.depth,
+            range: self.range.clone(),
+            len: self.len,
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Items<'_, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut list = f.debug_list();
+        for item in self.clone() {
+            match item {
+                Ok((_, item)) => list.entry(&item),
+                Err(e) => list.entry(&format_args!("<{}>", e)),
+            };
+        }
+        list.finish()
+    }
+}
+
+impl<T> Iterator for Items<'_, T> {
+    type Item = Result<(usize, T), failure::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let offset = self.r.pos();
+        match (self.read)(&mut self.r, self.depth) {
+            Ok(item) => {
+                self.remaining -= 1;
+                Some(Ok((offset, item)))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl TypeRef {
+    fn read(r: &mut Reader, _depth: usize) -> Result<Self, failure::Error> {
+        let at = r.pos();
+        let val = r.ileb()?;
+        if val >= 0 {
+            return Ok(TypeRef::Index(val as u32));
+        }
+        match scalar_type(val) {
+            Some(scalar) => Ok(TypeRef::Scalar(scalar)),
+            None => r.error(at, &format!("unknown Web IDL scalar type {}", val)),
+        }
+    }
+}
+
+impl<'a> DictionaryField<'a> {
+    fn read(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        let name = r.str()?;
+        let ty = TypeRef::read(r, depth)?;
+        Ok(DictionaryField { name, ty })
+    }
+}
+
+impl<'a> Name<'a> {
+    fn read(r: &mut Reader<'a>, _depth: usize) -> Result<Self, failure::Error> {
+        let idx = r.uleb()?;
+        let name = r.str()?;
+        Ok(Name { idx, name })
+    }
+}
+
+impl<'a> TypeAlias<'a> {
+    fn read(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        let name = r.str()?;
+        let ty = TypeRef::read(r, depth)?;
+        Ok(TypeAlias { name, ty })
+    }
+}
+
+impl<'a> Type<'a> {
+    fn read(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        let at = r.pos();
+        Ok(match r.byte()? {
+            0 => {
+                let at = r.pos();
+                let kind = match r.byte()? {
+                    0 => FunctionKind::Static,
+                    1 => FunctionKind::Method(TypeRef::read(r, depth)?),
+                    2 => FunctionKind::Constructor,
+                    _ => return r.error(at, "invalid Web IDL function kind"),
+                };
+                let params = Items::new(r, depth, TypeRef::read)?;
+                let at = r.pos();
+                let result = match r.byte()? {
+                    0 => None,
+                    1 => Some(TypeRef::read(r, depth)?),
+                    _ => return r.error(at, "invalid Web IDL function result flag"),
+                };
+                Type::Function {
+                    kind,
+                    params,
+                    result,
+                }
+            }
+            1 => Type::Dictionary {
+                fields: Items::new(r, depth, DictionaryField::read)?,
+            },
+            2 => Type::Enumeration {
+                values: Items::new(r, depth, |r, _| r.str())?,
+            },
+            3 => Type::Union {
+                members: Items::new(r, depth, TypeRef::read)?,
+            },
+            _ => return r.error(at, "invalid Web IDL type"),
+        })
+    }
+}
+
+impl<'a> FunctionBinding<'a> {
+    fn read(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        let at = r.pos();
+        Ok(match r.byte()? {
+            0 => FunctionBinding::Import {
+                wasm_ty: r.uleb()?,
+                webidl_ty: TypeRef::read(r, depth)?,
+                params: Items::new(r, depth, OutgoingExpr::read)?,
+                result: Items::new(r, depth, IncomingExpr::read)?,
+            },
+            1 => FunctionBinding::Export {
+                wasm_ty: r.uleb()?,
+                webidl_ty: TypeRef::read(r, depth)?,
+                params: Items::new(r, depth, IncomingExpr::read)?,
+                result: Items::new(r, depth, OutgoingExpr::read)?,
+            },
+            _ => return r.error(at, "invalid function binding kind"),
+        })
+    }
+}
+
+impl<'a> OutgoingExpr<'a> {
+    fn read(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        let at = r.pos();
+        Ok(match r.byte()? {
+            0 => OutgoingExpr::As {
+                ty: TypeRef::read(r, depth)?,
+                idx: r.uleb()?,
+            },
+            1 => OutgoingExpr::Utf8Str {
+                ty: TypeRef::read(r, depth)?,
+                offset: r.uleb()?,
+                length: r.uleb()?,
+            },
+            2 => OutgoingExpr::Utf8CStr {
+                ty: TypeRef::read(r, depth)?,
+                offset: r.uleb()?,
+            },
+            3 => OutgoingExpr::I32ToEnum {
+                ty: TypeRef::read(r, depth)?,
+                idx: r.uleb()?,
+            },
+            4 => OutgoingExpr::View {
+                ty: TypeRef::read(r, depth)?,
+                offset: r.uleb()?,
+                length: r.uleb()?,
+            },
+            5 => OutgoingExpr::Copy {
+                ty: TypeRef::read(r, depth)?,
+                offset: r.uleb()?,
+                length: r.uleb()?,
+            },
+            6 => OutgoingExpr::Dict {
+                ty: TypeRef::read(r, depth)?,
+                fields: Items::new(r, depth, OutgoingExpr::read)?,
+            },
+            7 => OutgoingExpr::BindExport {
+                ty: TypeRef::read(r, depth)?,
+                binding: r.uleb()?,
+                idx: r.uleb()?,
+            },
+            opcode if opcode >= CUSTOM_EXPRESSION_OPCODES => OutgoingExpr::Custom {
+                opcode,
+                payload: r.byte_vec()?,
+            },
+            _ => return r.error(at, "invalid outgoing binding expression"),
+        })
+    }
+}
+
+impl<'a> IncomingExpr<'a> {
+    fn read(r: &mut Reader<'a>, depth: usize) -> Result<Self, failure::Error> {
+        let at = r.pos();
+        Ok(match r.byte()? {
+            0 => IncomingExpr::Get { idx: r.uleb()? },
+            1 => IncomingExpr::As {
+                ty: walrus::ValType::decode(&mut (), r)?,
+                expr: NestedIncomingExpr::new(r, depth)?,
+            },
+            2 => IncomingExpr::AllocUtf8Str {
+                alloc_func_name: r.str()?,
+                expr: NestedIncomingExpr::new(r, depth)?,
+            },
+            3 => IncomingExpr::AllocCopy {
+                alloc_func_name: r.str()?,
+                expr: NestedIncomingExpr::new(r, depth)?,
+            },
+            4 => IncomingExpr::EnumToI32 {
+                ty: TypeRef::read(r, depth)?,
+                expr: NestedIncomingExpr::new(r, depth)?,
+            },
+            5 => IncomingExpr::Field {
+                idx: r.uleb()?,
+                expr: NestedIncomingExpr::new(r, depth)?,
+            },
+            6 => IncomingExpr::BindImport {
+                ty: r.uleb()?,
+                binding: r.uleb()?,
+                expr: NestedIncomingExpr::new(r, depth)?,
+            },
+            opcode if opcode >= CUSTOM_EXPRESSION_OPCODES => IncomingExpr::Custom {
+                opcode,
+                payload: r.byte_vec()?,
+            },
+            _ => return r.error(at, "invalid incoming binding expression"),
+        })
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Start,
+    StringTable,
+    TypeSubsection,
+    Types(u32),
+    FunctionBindingSubsection,
+    FunctionBindings(u32),
+    Binds(u32),
+    End,
+    Done,
+}
+
+/// An iterator over the `Payload`s of a section's data.
+///
+/// After the first error, the iterator yields nothing more.
+#[derive(Debug)]
+pub struct SectionReader<'a> {
+    r: Reader<'a>,
+    state: State,
+}
+
+impl<'a> SectionReader<'a> {
+    /// Create a reader over the data of a Web IDL bindings custom section.
+    pub fn new(data: &'a [u8]) -> SectionReader<'a> {
+        SectionReader {
+            r: Reader::new(data),
+            state: State::Start,
+        }
+    }
+
+    fn read(&mut self) -> Result<Option<Payload<'a>>, failure::Error> {
+        let r = &mut self.r;
+        loop {
+            let offset = r.pos();
+            // Unknown subsections can come between the known ones.
+            let between = matches!(
+                self.state,
+                State::StringTable
+                    | State::TypeSubsection
+                    | State::FunctionBindingSubsection
+                    | State::Binds(0)
+            );
+            if between && matches!(r.peek(), Some(id) if id > LAST_KNOWN_SUBSECTION) {
+                let (id, data) = r.unknown_subsection()?;
+                return Ok(Some(Payload::UnknownSubsection { offset, id, data }));
+            }
+            match self.state {
+                State::Start => {
+                    let version = r.version()?;
+                    let features = r.features()?;
+                    self.state = State::StringTable;
+                    return Ok(Some(Payload::Header {
+                        offset,
+                        version,
+                        features,
+                    }));
+                }
+                State::StringTable => {
+                    self.state = State::TypeSubsection;
+                    // The string table subsection is optional.
+                    if r.has_feature(FEATURE_STRING_TABLE) {
+                        let mut table = r.clone();
+                        table.feature_subsection(
+                            FEATURE_STRING_TABLE,
+                            3,
+                            "string table subsection",
+                        )?;
+                        table.subsection_size()?;
+                        let strings = Items::new(&mut table, 0, |r, _| r.inline_str())?;
+                        r.string_table_subsection()?;
+                        return Ok(Some(Payload::StringTable { offset, strings }));
+                    }
+                }
+                State::TypeSubsection => {
+                    self.state = State::FunctionBindingSubsection;
+                    // The Web IDL type subsection is optional.
+                    if r.peek() == Some(0) {
+                        r.byte()?;
+                        let count = r.uleb()?;
+                        self.state = State::Types(count);
+                        return Ok(Some(Payload::TypeSubsection { offset, count }));
+                    }
+                }
+                State::Types(0) => self.state = State::FunctionBindingSubsection,
+                State::Types(n) => {
+                    let ty = Type::read(r, 0)?;
+                    self.state = State::Types(n - 1);
+                    return Ok(Some(Payload::Type { offset, ty }));
+                }
+                State::FunctionBindingSubsection => {
+                    if r.byte()? != 1 {
+                        return r.error(offset, "expected the function binding subsection");
+                    }
+                    let count = r.uleb()?;
+                    self.state = State::FunctionBindings(count);
+                    return Ok(Some(Payload::FunctionBindingSubsection { offset, count }));
+                }
+                State::FunctionBindings(0) => {
+                    let count = r.uleb()?;
+                    self.state = State::Binds(count);
+                    return Ok(Some(Payload::Binds { offset, count }));
+                }
+                State::FunctionBindings(n) => {
+                    let binding = FunctionBinding::read(r, 0)?;
+                    self.state = State::FunctionBindings(n - 1);
+                    return Ok(Some(Payload::FunctionBinding { offset, binding }));
+                }
+                State::Binds(0) => {
+                    self.state = State::End;
+                    // The names subsection is optional.
+                    if r.feature_subsection(FEATURE_NAMES, 2, "names subsection")? {
+                        let end = r.subsection_size()?;
+                        let types = Items::new(r, 0, Name::read)?;
+                        let bindings = Items::new(r, 0, Name::read)?;
+                        let aliases = Items::new(r, 0, TypeAlias::read)?;
+                        r.subsection_end(end, "names subsection")?;
+                        return Ok(Some(Payload::NamesSubsection {
+                            offset,
+                            types,
+                            bindings,
+                            aliases,
+                        }));
+                    }
+                }
+                State::End => {
+                    if r.is_empty() {
+                        self.state = State::Done;
+                    } else {
+                        let (id, data) = r.unknown_subsection()?;
+                        return Ok(Some(Payload::UnknownSubsection { offset, id, data }));
+                    }
+                }
+                State::Binds(n) => {
+                    let func = r.uleb()?;
+                    let binding = r.uleb()?;
+                    self.state = State::Binds(n - 1);
+                    return Ok(Some(Payload::Bind {
+                        offset,
+                        func,
+                        binding,
+                    }));
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for SectionReader<'a> {
+    type Item = Result<Payload<'a>, failure::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read() {
+            Ok(payload) => payload.map(Ok),
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const SECTION: &[u8] = &[
+        1, 0,
+        0, 2,
+        1, 1, 1, b'a', 1,
+        3, 2, 0x6f, 0,
+        1, 2,
+        0, 0, 0x7b, 1, 7, 0x7b, 1, 0, 1, 1, 0x7f, 0, 0,
+        1, 0, 0x76, 1, 2, 5, b'a', b'l', b'l', b'o', b'c', 0, 1, 0,
+        1, 0, 1,
+    ];
+
+    fn collect<T>(items: Items<T>) -> Vec<(usize, T)> {
+        items.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn read_section() {
+        let payloads: Vec<_> = SectionReader::new(SECTION)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let offsets: Vec<_> = payloads.iter().map(|p| p.offset()).collect();
+        assert_eq!(offsets, [0, 2, 4, 9, 13, 15, 28, 42, 43]);
+
+        match payloads[0] {
+            Payload::Header {
+                version: 1,
+                features: 0,
+                ..
+            } => {}
+            ref p => panic!("expected the header, found {:?}", p),
+        }
+
+        match &payloads[2] {
+            Payload::Type {
+                ty: Type::Dictionary { fields },
+                ..
+            } => {
+                assert_eq!(fields.range(), 5..9);
+                let fields = collect(fields.clone());
+                assert_eq!(
+                    fields,
+                    [(
+                        6,
+                        DictionaryField {
+                            name: "a",
+                            ty: TypeRef::Index(1)
+                        }
+                    )]
+                );
+                // Names are borrowed from the section.
+                assert_eq!(fields[0].1.name.as_ptr(), SECTION[7..].as_ptr());
+            }
+            p => panic!("expected a dictionary, found {:?}", p),
+        }
+        match &payloads[3] {
+            Payload::Type {
+                ty: Type::Union { members },
+                ..
+            } => assert_eq!(
+                collect(members.clone()),
+                [
+                    (11, TypeRef::Scalar(WebidlScalarType::UsvString)),
+                    (12, TypeRef::Index(0))
+                ]
+            ),
+            p => panic!("expected a union, found {:?}", p),
+        }
+        match &payloads[6] {
+            Payload::FunctionBinding {
+                binding:
+                    FunctionBinding::Export {
+                        wasm_ty: 0,
+                        webidl_ty: TypeRef::Scalar(WebidlScalarType::UnsignedLongLong),
+                        params,
+                        result,
+                    },
+                ..
+            } => {
+                assert!(result.is_empty());
+                let params = collect(params.clone());
+                assert_eq!(params.len(), 1);
+                match &params[0] {
+                    (
+                        32,
+                        IncomingExpr::AllocUtf8Str {
+                            alloc_func_name: "alloc",
+                            expr,
+                        },
+                    ) => {
+                        assert_eq!(expr.offset(), 39);
+                        match expr.read().unwrap() {
+                            IncomingExpr::Get { idx: 1 } => {}
+                            e => panic!("expected a get, found {:?}", e),
+                        }
+                    }
+                    e => panic!("expected an alloc-utf8-str, found {:?}", e),
+                }
+            }
+            p => panic!("expected an export binding, found {:?}", p),
+        }
+        match payloads[8] {
+            Payload::Bind {
+                func: 0,
+                binding: 1,
+                ..
+            } => {}
+            ref p => panic!("expected a bind, found {:?}", p),
+        }
+    }
+
+    #[test]
+    fn read_names_subsection() {
+        #[rustfmt::skip]
+        let data = &[
+            1, 0x80, 0x80, 0x04,
+            1, 0, 0,
+            2, 11,
+            1, 3, 2, b'$', b'T',
+            0,
+            1, 2, b'$', b'S', 0x6f,
+        ];
+        let payloads: Vec<_> = SectionReader::new(data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(payloads.len(), 4);
+        match &payloads[3] {
+            Payload::NamesSubsection {
+                offset: 7,
+                types,
+                bindings,
+                aliases,
+            } => {
+                assert_eq!(collect(types.clone()), [(10, Name { idx: 3, name: "$T" })]);
+                assert!(bindings.is_empty());
+                assert_eq!(
+                    collect(aliases.clone()),
+                    [(
+                        16,
+                        TypeAlias {
+                            name: "$S",
+                            ty: TypeRef::Scalar(WebidlScalarType::UsvString)
+                        }
+                    )]
+                );
+            }
+            p => panic!("expected the names subsection, found {:?}", p),
+        }
+    }
+
+    #[test]
+    fn read_string_table_subsection() {
+        #[rustfmt::skip]
+        let data = &[
+            1, 2,
+            3, 5, 2, 1, b'a', 1, b'b',
+            0, 1, 2, 2, 1, 0,
+            1, 0, 0,
+        ];
+        let payloads: Vec<_> = SectionReader::new(data).collect::<Result<_, _>>().unwrap();
+        match &payloads[1] {
+            Payload::StringTable { offset: 2, strings } => {
+                assert_eq!(strings.range(), 4..9);
+                assert_eq!(collect(strings.clone()), [(5, "a"), (7, "b")]);
+            }
+            p => panic!("expected the string table, found {:?}", p),
+        }
+        match &payloads[3] {
+            Payload::Type {
+                offset: 11,
+                ty: Type::Enumeration { values },
+            } => {
+                let values = collect(values.clone());
+                assert_eq!(values, [(13, "b"), (14, "a")]);
+                // Strings from the table are still borrowed from the section.
+                assert_eq!(values[1].1.as_ptr(), data[6..].as_ptr());
+            }
+            p => panic!("expected an enumeration, found {:?}", p),
+        }
+    }
+
+    #[test]
+    fn read_unknown_subsections() {
+        let data = &[1, 0x80, 0x80, 0x08, 8, 1, 0xcc, 1, 0, 0, 7, 2, 0xaa, 0xbb];
+        let payloads: Vec<_> = SectionReader::new(data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(payloads.len(), 5);
+        match payloads[1] {
+            Payload::UnknownSubsection {
+                offset: 4,
+                id: 8,
+                data: contents,
+            } => assert_eq!(contents, [0xcc]),
+            ref p => panic!("expected an unknown subsection, found {:?}", p),
+        }
+        match payloads[4] {
+            Payload::UnknownSubsection {
+                offset: 10,
+                id: 7,
+                data: contents,
+            } => assert_eq!(contents.as_ptr(), data[12..].as_ptr()),
+            ref p => panic!("expected an unknown subsection, found {:?}", p),
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        fn errors(data: &[u8]) -> Vec<String> {
+            SectionReader::new(data)
+                .filter_map(|p| p.err())
+                .map(|e| e.to_string())
+                .collect()
+        }
+
+        // Nested items are checked before the item that contains them is
+        // yielded, and nothing is yielded after an error.
+        let mut reader = SectionReader::new(&[1, 0, 0, 1, 3, 2, 0x6f, 0x50, 1, 0, 0]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().to_string(),
+            "unknown Web IDL scalar type -48 at offset 7"
+        );
+        assert!(reader.next().is_none());
+
+        assert_eq!(
+            errors(&[1, 0, 1, 1, 0, 0, 0x7b, 0, 1, 1, 0x7f, 9]),
+            ["invalid incoming binding expression at offset 11"]
+        );
+        assert_eq!(
+            errors(&[1, 0, 2]),
+            ["expected the function binding subsection at offset 2"]
+        );
+        assert_eq!(
+            errors(&[1, 0, 1, 0, 0, 0]),
+            ["unexpected data after the last subsection at offset 5"]
+        );
+
+        assert_eq!(
+            errors(&[2, 0, 1, 0, 0]),
+            ["unsupported version 2 (the newest supported version is 1) at offset 0"]
+        );
+        assert_eq!(
+            errors(&[1, 8, 1, 0, 0]),
+            ["unsupported features 0x8 at offset 1"]
+        );
+
+        let mut data = vec![1, 0, 1, 1, 0, 0, 0x7b, 0, 1];
+        for _ in 0..MAX_DEPTH {
+            data.extend_from_slice(&[5, 0]);
+        }
+        data.extend_from_slice(&[0, 0, 0]);
+        assert_eq!(
+            errors(&data),
+            [format!("too deeply nested at offset {}", 9 + 2 * MAX_DEPTH)]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Embedding Web IDL bindings written in the text format into a Wasm binary,
+//! and extracting or stripping them again.
+//!
+//! Embedding is meant for build scripts, which otherwise have to parse the module,
+//! parse the bindings in an `on_parse` callback, and add the custom section
+//! themselves:
+//!
+//! ```no_run
+//! wasm_webidl_bindings::embed::embed_file(
+//!     "target/wasm32-unknown-unknown/release/my_crate.wasm",
+//!     "src/bindings.webidl",
+//!     "target/wasm32-unknown-unknown/release/my_crate.wasm",
+//! )?;
+//! # Ok::<(), failure::Error>(())
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const SECTION_NAME: &str = "webidl-bindings";
+
+/// An error in the bindings found while parsing the module, which is told
+/// apart from errors in the module itself by its type.
+#[derive(Debug)]
+struct InvalidBindings(String);
+
+impl fmt::Display for InvalidBindings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidBindings {}
+
+/// Parse the Web IDL bindings in `bindings`, and embed them in the Wasm binary
+/// `wasm`, returning the new Wasm binary.
+///
+/// Every reference in the bindings to a Wasm function or function type, Web
+/// IDL type, or function binding must resolve, and every error is reported
+/// with the line of `bindings` that it is on. Any `webidl-bindings` section
+/// that `wasm` already has is replaced.
+pub fn embed(wasm: &[u8], bindings: &str) -> Result<Vec<u8>, failure::Error> {
+    let input = bindings.to_string();
+    let mut config = walrus::ModuleConfig::default();
+    config.on_parse(move |module, indices_to_ids| {
+        let (bindings, errors) = crate::text::parse_recovering(module, indices_to_ids, &input);
+        if !errors.is_empty() {
+            let errors = crate::text::render_errors(&input, &errors);
+            let message = format!("invalid Web IDL bindings:\n\n{}", errors);
+            return Err(InvalidBindings(message).into());
+        }
+
+        while module.customs.remove_raw(SECTION_NAME).is_some() {}
+        module.customs.add(bindings);
+        Ok(())
+    });
+
+    emit(&parse(&config, wasm)?)
+}
+
+/// Decode the `webidl-bindings` section of the Wasm binary `wasm`, and print
+/// it in the text format with `text::print`.
+///
+/// Returns `None` if there is no `webidl-bindings` section. Returns an error if
+/// the section is malformed or refers to Wasm functions or function types that
+/// aren't in `wasm`, so this also validates the section.
+pub fn extract(wasm: &[u8]) -> Result<Option<String>, failure::Error> {
+    extract_with(wasm, crate::text::print)
+}
+
+/// Like `extract`, but serialize the section as JSON with
+/// `interchange::to_json` instead of printing it in the text format.
+#[cfg(feature = "serde")]
+pub fn extract_json(wasm: &[u8]) -> Result<Option<String>, failure::Error> {
+    extract_with(wasm, |section, _module, indices_to_ids| {
+        crate::interchange::to_json(section, indices_to_ids)
+    })
+}
+
+fn extract_with<F>(wasm: &[u8], f: F) -> Result<Option<String>, failure::Error>
+where
+    F: Fn(
+            &crate::ast::WebidlBindings,
+            &walrus::Module,
+            &walrus::IndicesToIds,
+        ) -> Result<String, failure::Error>
+        + Send
+        + Sync
+        + 'static,
+{
+    let text = Arc::new(Mutex::new(None));
+    let mut config = walrus::ModuleConfig::default();
+    let text2 = text.clone();
+    config.on_parse(move |module, indices_to_ids| {
+        let section = match module.customs.remove_raw(SECTION_NAME) {
+            Some(section) => section,
+            None => return Ok(()),
+        };
+        let bindings = crate::binary::decode(indices_to_ids, &section.data)
+            .map_err(|e| InvalidBindings(format!("invalid `{}` section: {}", SECTION_NAME, e)))?;
+        *text2.lock().unwrap() = Some(f(&bindings, module, indices_to_ids)?);
+        Ok(())
+    });
+    parse(&config, wasm)?;
+    let text = text.lock().unwrap().take();
+    Ok(text)
+}
+
+/// Remove any `webidl-bindings` section from the Wasm binary `wasm`, returning
+/// the new Wasm binary.
+pub fn strip(wasm: &[u8]) -> Result<Vec<u8>, failure::Error> {
+    let mut module = parse(&walrus::ModuleConfig::default(), wasm)?;
+    while module.customs.remove_raw(SECTION_NAME).is_some() {}
+    emit(&module)
+}
+
+fn parse(config: &walrus::ModuleConfig, wasm: &[u8]) -> Result<walrus::Module, failure::Error> {
+    config
+        .parse(wasm)
+        .map_err(|e| match e.downcast::<InvalidBindings>() {
+            Ok(e) => e.into(),
+            Err(e) => failure::format_err!("failed to parse the Wasm module: {}", e),
+        })
+}
+
+fn emit(module: &walrus::Module) -> Result<Vec<u8>, failure::Error> {
+    module
+        .emit_wasm()
+        .map_err(|e| failure::format_err!("failed to emit the Wasm module: {}", e))
+}
+
+/// Like `embed`, but read the Wasm binary from `wasm_in` and the bindings from
+/// `bindings`, and write the new Wasm binary to `wasm_out`.
+///
+/// `wasm_in` and `wasm_out` may be the same path.
+pub fn embed_file<P, Q, R>(wasm_in: P, bindings: Q, wasm_out: R) -> Result<(), failure::Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    let (wasm_in, bindings, wasm_out) = (wasm_in.as_ref(), bindings.as_ref(), wasm_out.as_ref());
+    let wasm = fs::read(wasm_in)
+        .map_err(|e| failure::format_err!("failed to read `{}`: {}", wasm_in.display(), e))?;
+    let text = fs::read_to_string(bindings)
+        .map_err(|e| failure::format_err!("failed to read `{}`: {}", bindings.display(), e))?;
+    let wasm = embed(&wasm, &text).map_err(|e| {
+        failure::format_err!(
+            "failed to embed `{}` in `{}`: {}",
+            bindings.display(),
+            wasm_in.display(),
+            e
+        )
+    })?;
+    fs::write(wasm_out, wasm)
+        .map_err(|e| failure::format_err!("failed to write `{}`: {}", wasm_out.display(), e))
+}
@@ -0,0 +1,107 @@
+//! Garbage collection for Wasm modules with Web IDL bindings.
+//!
+//! walrus' `passes::gc` removes everything that the module's exports don't use,
+//! but the functions that binds refer to, and the Wasm function types that
+//! function bindings refer to, are often not used by anything else. Running it
+//! on a module with a `WebidlBindings` custom section would delete them, and
+//! then encoding the section would fail. `run` is a drop-in replacement that
+//! keeps them, by temporarily exporting them while walrus' pass runs.
+
+use crate::ast::*;
+use std::collections::HashSet;
+
+/// The prefix of the names of the exports that temporarily root the bound
+/// functions and function types, and the name of the imported placeholder
+/// functions for the latter.
+const ROOT_EXPORT: &str = "__webidl_bindings_gc_root";
+
+/// The Wasm functions and function types that a section refers to, which must
+/// be kept alive for as long as the section is.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Roots {
+    /// The functions that binds refer to.
+    pub funcs: Vec<walrus::FunctionId>,
+    /// The function types that function bindings and `bind-import` expressions
+    /// refer to.
+    pub types: Vec<walrus::TypeId>,
+}
+
+/// Get the Wasm functions and function types that `section` refers to.
+pub fn roots(section: &WebidlBindings) -> Roots {
+    let mut roots = Roots::default();
+    for (_, bind) in section.binds.iter() {
+        roots.funcs.push(bind.func);
+    }
+    for (_, binding) in section.bindings.arena.iter() {
+        match binding {
+            FunctionBinding::Import(i) => {
+                roots.types.push(i.wasm_ty);
+                i.result.bindings.iter().for_each(|e| roots.incoming(e));
+            }
+            FunctionBinding::Export(e) => {
+                roots.types.push(e.wasm_ty);
+                e.params.bindings.iter().for_each(|e| roots.incoming(e));
+            }
+        }
+    }
+    roots
+}
+
+impl Roots {
+    fn incoming(&mut self, expr: &IncomingBindingExpression) {
+        if let IncomingBindingExpression::BindImport(e) = expr {
+            self.types.push(e.ty);
+        }
+        if let Some(expr) = expr.nested() {
+            self.incoming(expr);
+        }
+    }
+}
+
+/// Remove everything from `module` that is neither used by its exports nor
+/// referred to by its `WebidlBindings` custom section, like walrus'
+/// `passes::gc::run`.
+///
+/// The section must have been added to the module as a `WebidlBindings`,
+/// rather than as a `walrus::RawCustomSection`, since the raw bytes can't be
+/// understood without the module's indices.
+pub fn run(module: &mut walrus::Module) {
+    let roots = module
+        .customs
+        .get_typed::<WebidlBindings>()
+        .map(roots)
+        .unwrap_or_default();
+
+    // walrus' gc only keeps what the exports use, so export the roots for as
+    // long as it runs, under names that no real export has.
+    let taken: HashSet<_> = module.exports.iter().map(|e| e.name.clone()).collect();
+    let mut names = (0..)
+        .map(|i| format!("{}{}", ROOT_EXPORT, i))
+        .filter(|name| !taken.contains(name));
+    let mut exports = vec![];
+    for f in roots.funcs {
+        exports.push(module.exports.add(&names.next().unwrap(), f));
+    }
+
+    // A function type is only kept if a function has it, so import a
+    // placeholder function of each type, and export that.
+    let types: HashSet<_> = roots.types.into_iter().collect();
+    let mut placeholders = vec![];
+    for ty in types {
+        let f = module.add_import_func(ROOT_EXPORT, ROOT_EXPORT, ty);
+        placeholders.push(f);
+        exports.push(module.exports.add(&names.next().unwrap(), f));
+    }
+
+    walrus::passes::gc::run(module);
+
+    for id in exports {
+        module.exports.delete(id);
+    }
+    for f in placeholders {
+        if let walrus::FunctionKind::Import(import) = &module.funcs.get(f).kind {
+            module.imports.delete(import.import);
+        }
+        module.funcs.delete(f);
+    }
+}
@@ -0,0 +1,536 @@
+//! Serializing and deserializing Web IDL bindings with `serde`, and a JSON
+//! interchange format built on top of that.
+//!
+//! This module, and the `Serialize` and `Deserialize` implementations for the
+//! AST, are only available with the `serde` cargo feature.
+//!
+//! References to Web IDL types and function bindings are serialized as their
+//! index in the section, which is the same index that the binary format uses,
+//! and the names that they were given are serialized alongside them. Scalar
+//! Web IDL types are serialized as their name in the text format, such as
+//! `"USVString"`.
+//!
+//! References to Wasm functions and function types are serialized as their
+//! index in the Wasm binary that the module was parsed from, so serializing or
+//! deserializing anything that contains them must happen inside `with_module`.
+//! `to_json` and `from_json` do that for you:
+//!
+//! ```
+//! # fn foo() -> Result<(), failure::Error> {
+//! use wasm_webidl_bindings::{interchange, text};
+//!
+//! let module = walrus::Module::default();
+//! let indices_to_ids = walrus::IndicesToIds::default();
+//! let section = text::parse(&module, &indices_to_ids, "type $T (func (param long))")?;
+//!
+//! let json = interchange::to_json(&section, &indices_to_ids)?;
+//! let section = interchange::from_json(&indices_to_ids, &json)?;
+//! # let _ = section;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::ast::*;
+use crate::binary::scalar_type;
+use crate::text::scalar_type_name;
+use id_arena::{Arena, ArenaBehavior, DefaultArenaBehavior, Id};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+
+/// The mapping between walrus IDs and indices in the Wasm binary that is in
+/// effect inside `with_module`.
+struct ModuleIndices {
+    funcs: Vec<walrus::FunctionId>,
+    types: Vec<walrus::TypeId>,
+    func_indices: HashMap<walrus::FunctionId, u32>,
+    type_indices: HashMap<walrus::TypeId, u32>,
+}
+
+/// The section that is being deserialized, so that references to its Web IDL
+/// types and function bindings can be turned into IDs before they exist.
+#[derive(Clone, Copy)]
+struct SectionIds {
+    webidl_types: u32,
+    bindings: u32,
+    // One more than the largest index that has been referred to.
+    webidl_types_len: u32,
+    bindings_len: u32,
+}
+
+thread_local! {
+    static MODULE: RefCell<Option<Rc<ModuleIndices>>> = const { RefCell::new(None) };
+    static SECTION: Cell<Option<SectionIds>> = const { Cell::new(None) };
+}
+
+struct RestoreModule(Option<Rc<ModuleIndices>>);
+
+impl Drop for RestoreModule {
+    fn drop(&mut self) {
+        let prev = self.0.take();
+        MODULE.with(|m| *m.borrow_mut() = prev);
+    }
+}
+
+struct RestoreSection(Option<SectionIds>);
+
+impl Drop for RestoreSection {
+    fn drop(&mut self) {
+        SECTION.with(|s| s.set(self.0));
+    }
+}
+
+const NO_MODULE: &str = "references to Wasm functions and function types can only \
+                         be serialized or deserialized inside `interchange::with_module`";
+
+const NO_SECTION: &str = "references to Web IDL types and function bindings can only \
+                          be deserialized as part of a `WebidlBindings`";
+
+/// Call `f`, serializing and deserializing references to Wasm functions and
+/// function types inside it as their index in the Wasm binary that
+/// `indices_to_ids` came from.
+///
+/// Serializing a reference to a Wasm function or function type that isn't in
+/// that Wasm binary, such as one added after it was parsed, is an error.
+pub fn with_module<F, R>(indices_to_ids: &walrus::IndicesToIds, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let mut indices = ModuleIndices {
+        funcs: vec![],
+        types: vec![],
+        func_indices: HashMap::new(),
+        type_indices: HashMap::new(),
+    };
+    while let Ok(id) = indices_to_ids.get_func(indices.funcs.len() as u32) {
+        indices.func_indices.insert(id, indices.funcs.len() as u32);
+        indices.funcs.push(id);
+    }
+    while let Ok(id) = indices_to_ids.get_type(indices.types.len() as u32) {
+        indices.type_indices.insert(id, indices.types.len() as u32);
+        indices.types.push(id);
+    }
+
+    let prev = MODULE.with(|m| m.replace(Some(Rc::new(indices))));
+    let _restore = RestoreModule(prev);
+    f()
+}
+
+/// Serialize `section` as JSON.
+pub fn to_json(
+    section: &WebidlBindings,
+    indices_to_ids: &walrus::IndicesToIds,
+) -> Result<String, failure::Error> {
+    let json = with_module(indices_to_ids, || serde_json::to_string_pretty(section))?;
+    Ok(json)
+}
+
+/// Deserialize a section from JSON, resolving its references to Wasm functions
+/// and function types against the Wasm binary that `indices_to_ids` came from.
+///
+/// Returns an error if any reference doesn't resolve.
+pub fn from_json(
+    indices_to_ids: &walrus::IndicesToIds,
+    json: &str,
+) -> Result<WebidlBindings, failure::Error> {
+    let section = with_module(indices_to_ids, || serde_json::from_str(json))?;
+    Ok(section)
+}
+
+fn module() -> Option<Rc<ModuleIndices>> {
+    MODULE.with(|m| m.borrow().clone())
+}
+
+fn arena_id<T>(arena: &Arena<T>) -> u32 {
+    DefaultArenaBehavior::<T>::arena_id(arena.next_id())
+}
+
+/// The serialized form of `WebidlBindings`.
+#[derive(serde::Deserialize)]
+struct Section {
+    types: Vec<WebidlCompoundType>,
+    #[serde(default)]
+    type_names: HashMap<String, u32>,
+    #[serde(default)]
+    type_aliases: HashMap<String, WebidlTypeRef>,
+    bindings: Vec<FunctionBinding>,
+    #[serde(default)]
+    binding_names: HashMap<String, u32>,
+    binds: Vec<Bind>,
+    #[serde(default)]
+    extensions: Extensions,
+}
+
+impl Serialize for WebidlBindings {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let types: Vec<_> = self.types.arena.iter().map(|(_, ty)| ty).collect();
+        // Sort the names so that the output is deterministic.
+        let type_names: BTreeMap<_, _> = self
+            .types
+            .names
+            .iter()
+            .map(|(name, id)| (name, id.index()))
+            .collect();
+        let type_aliases: BTreeMap<_, _> = self.types.aliases.iter().collect();
+        let bindings: Vec<_> = self.bindings.arena.iter().map(|(_, b)| b).collect();
+        let binding_names: BTreeMap<_, _> = self
+            .bindings
+            .names
+            .iter()
+            .map(|(name, id)| (name, id.index()))
+            .collect();
+        let binds: Vec<_> = self.binds.arena.iter().map(|(_, b)| b).collect();
+
+        let mut s = serializer.serialize_struct("WebidlBindings", 7)?;
+        s.serialize_field("types", &types)?;
+        s.serialize_field("type_names", &type_names)?;
+        s.serialize_field("type_aliases", &type_aliases)?;
+        s.serialize_field("bindings", &bindings)?;
+        s.serialize_field("binding_names", &binding_names)?;
+        s.serialize_field("binds", &binds)?;
+        if self.extensions == Extensions::default() {
+            s.skip_field("extensions")?;
+        } else {
+            s.serialize_field("extensions", &self.extensions)?;
+        }
+        s.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for WebidlBindings {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let mut section = WebidlBindings::default();
+        let ids = SectionIds {
+            webidl_types: arena_id(&section.types.arena),
+            bindings: arena_id(&section.bindings.arena),
+            webidl_types_len: 0,
+            bindings_len: 0,
+        };
+        let prev = SECTION.with(|s| s.replace(Some(ids)));
+        let restore = RestoreSection(prev);
+        let raw = Section::deserialize(deserializer);
+        let ids = SECTION.with(|s| s.get()).unwrap();
+        drop(restore);
+        let raw = raw?;
+
+        if ids.webidl_types_len as usize > raw.types.len() {
+            return Err(D::Error::custom(format!(
+                "unknown Web IDL type index {}",
+                ids.webidl_types_len - 1
+            )));
+        }
+        if ids.bindings_len as usize > raw.bindings.len() {
+            return Err(D::Error::custom(format!(
+                "unknown function binding index {}",
+                ids.bindings_len - 1
+            )));
+        }
+
+        for ty in raw.types {
+            match ty {
+                WebidlCompoundType::Function(ty) => {
+                    section.types.insert(ty);
+                }
+                WebidlCompoundType::Dictionary(ty) => {
+                    section.types.insert(ty);
+                }
+                WebidlCompoundType::Enumeration(ty) => {
+                    section.types.insert(ty);
+                }
+                WebidlCompoundType::Union(ty) => {
+                    section.types.insert(ty);
+                }
+            }
+        }
+        for (name, idx) in raw.type_names {
+            match section.types.by_index(idx) {
+                Some(id) => section.types.set_name(name, id).map_err(D::Error::custom)?,
+                None => {
+                    return Err(D::Error::custom(format!(
+                        "unknown Web IDL type index {}",
+                        idx
+                    )))
+                }
+            }
+        }
+        for (name, ty) in raw.type_aliases {
+            section.types.alias(name, ty).map_err(D::Error::custom)?;
+        }
+
+        for binding in raw.bindings {
+            match binding {
+                FunctionBinding::Import(b) => {
+                    section.bindings.insert(b);
+                }
+                FunctionBinding::Export(b) => {
+                    section.bindings.insert(b);
+                }
+            }
+        }
+        for (name, idx) in raw.binding_names {
+            match section.bindings.by_index(idx) {
+                Some(id) => {
+                    section.bindings.names.insert(name, id);
+                }
+                None => {
+                    return Err(D::Error::custom(format!(
+                        "unknown function binding index {}",
+                        idx
+                    )))
+                }
+            }
+        }
+
+        for bind in raw.binds {
+            section.binds.insert(bind);
+        }
+        section.extensions = raw.extensions;
+        Ok(section)
+    }
+}
+
+impl Serialize for WebidlTypeRef {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            WebidlTypeRef::Id(id) => serializer.serialize_u64(id.index() as u64),
+            WebidlTypeRef::Scalar(s) => s.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WebidlTypeRef {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TypeRefVisitor;
+
+        impl<'de> de::Visitor<'de> for TypeRefVisitor {
+            type Value = WebidlTypeRef;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a Web IDL type index or scalar type name")
+            }
+
+            fn visit_u64<E>(self, idx: u64) -> Result<WebidlTypeRef, E>
+            where
+                E: de::Error,
+            {
+                let mut ids = match SECTION.with(|s| s.get()) {
+                    Some(ids) => ids,
+                    None => return Err(E::custom(NO_SECTION)),
+                };
+                if idx > u64::from(u32::MAX) - 1 {
+                    return Err(E::custom(format!("unknown Web IDL type index {}", idx)));
+                }
+                let idx = idx as u32;
+                ids.webidl_types_len = ids.webidl_types_len.max(idx + 1);
+                SECTION.with(|s| s.set(Some(ids)));
+                Ok(WebidlTypeRef::Id(DefaultArenaBehavior::new_id(
+                    ids.webidl_types,
+                    idx as usize,
+                )))
+            }
+
+            fn visit_i64<E>(self, idx: i64) -> Result<WebidlTypeRef, E>
+            where
+                E: de::Error,
+            {
+                if idx < 0 {
+                    return Err(E::custom(format!("unknown Web IDL type index {}", idx)));
+                }
+                self.visit_u64(idx as u64)
+            }
+
+            fn visit_str<E>(self, name: &str) -> Result<WebidlTypeRef, E>
+            where
+                E: de::Error,
+            {
+                scalar_type_by_name(name).map(WebidlTypeRef::Scalar)
+            }
+        }
+
+        deserializer.deserialize_any(TypeRefVisitor)
+    }
+}
+
+fn scalar_type_by_name<E>(name: &str) -> Result<WebidlScalarType, E>
+where
+    E: de::Error,
+{
+    (1..=30)
+        .filter_map(|code| scalar_type(-code))
+        .find(|s| scalar_type_name(*s) == name)
+        .ok_or_else(|| E::custom(format!("unknown Web IDL scalar type `{}`", name)))
+}
+
+impl Serialize for WebidlScalarType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(scalar_type_name(*self))
+    }
+}
+
+impl<'de> Deserialize<'de> for WebidlScalarType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        scalar_type_by_name(&name)
+    }
+}
+
+pub(crate) mod binding_id {
+    use super::*;
+
+    pub fn serialize<S>(id: &Id<FunctionBinding>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(id.index() as u64)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Id<FunctionBinding>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let idx = u32::deserialize(deserializer)?;
+        let mut ids = match SECTION.with(|s| s.get()) {
+            Some(ids) => ids,
+            None => return Err(D::Error::custom(NO_SECTION)),
+        };
+        if idx == u32::MAX {
+            return Err(D::Error::custom(format!(
+                "unknown function binding index {}",
+                idx
+            )));
+        }
+        ids.bindings_len = ids.bindings_len.max(idx + 1);
+        SECTION.with(|s| s.set(Some(ids)));
+        Ok(DefaultArenaBehavior::new_id(ids.bindings, idx as usize))
+    }
+}
+
+pub(crate) mod wasm_func_id {
+    use super::*;
+
+    pub fn serialize<S>(id: &walrus::FunctionId, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::Error;
+
+        let module = module().ok_or_else(|| S::Error::custom(NO_MODULE))?;
+        match module.func_indices.get(id) {
+            Some(idx) => serializer.serialize_u32(*idx),
+            None => Err(S::Error::custom(
+                "Wasm function is not in the original Wasm binary",
+            )),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<walrus::FunctionId, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let idx = u32::deserialize(deserializer)?;
+        let module = module().ok_or_else(|| D::Error::custom(NO_MODULE))?;
+        match module.funcs.get(idx as usize) {
+            Some(id) => Ok(*id),
+            None => Err(D::Error::custom(format!(
+                "unknown Wasm function index {}",
+                idx
+            ))),
+        }
+    }
+}
+
+pub(crate) mod wasm_type_id {
+    use super::*;
+
+    pub fn serialize<S>(id: &walrus::TypeId, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::Error;
+
+        let module = module().ok_or_else(|| S::Error::custom(NO_MODULE))?;
+        match module.type_indices.get(id) {
+            Some(idx) => serializer.serialize_u32(*idx),
+            None => Err(S::Error::custom(
+                "Wasm function type is not in the original Wasm binary",
+            )),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<walrus::TypeId, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let idx = u32::deserialize(deserializer)?;
+        let module = module().ok_or_else(|| D::Error::custom(NO_MODULE))?;
+        match module.types.get(idx as usize) {
+            Some(id) => Ok(*id),
+            None => Err(D::Error::custom(format!(
+                "unknown Wasm function type index {}",
+                idx
+            ))),
+        }
+    }
+}
+
+pub(crate) mod val_type {
+    use super::*;
+
+    pub fn serialize<S>(ty: &walrus::ValType, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&ty.to_string())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<walrus::ValType, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        Ok(match String::deserialize(deserializer)?.as_str() {
+            "i32" => walrus::ValType::I32,
+            "i64" => walrus::ValType::I64,
+            "f32" => walrus::ValType::F32,
+            "f64" => walrus::ValType::F64,
+            "v128" => walrus::ValType::V128,
+            "anyref" => walrus::ValType::Anyref,
+            other => {
+                return Err(D::Error::custom(format!(
+                    "unknown Wasm value type `{}`",
+                    other
+                )))
+            }
+        })
+    }
+}
@@ -14,8 +14,54 @@
 * A set of AST types for representing and manipulating WebIDL bindings. See
   `src/ast.rs`.
 
-* An encoder for the straw proposal binary format. See the implementation at
-  `src/binary/encode.rs` and details on the format at `BINARY.md`.
+* An unresolved AST that refers to Wasm functions and types by name or index,
+  for working with bindings before the Wasm module exists. See
+  `src/unresolved.rs`.
+
+* Support for writing bindings in `(@webidl ...)` annotations inside a
+  WebAssembly text format module. See `text::parse_wat`.
+
+* A `webidl_bindings!` procedural macro that checks bindings written in the
+  text format at compile time. See `crates/macro`.
+
+* A helper for build scripts that embeds a text format bindings file in a Wasm
+  binary. See `embed::embed_file`.
+
+* An encoder and decoder for the straw proposal binary format. See the
+  implementation at `src/binary/encode.rs` and `src/binary/decode.rs`, and
+  details on the format at `BINARY.md`.
+
+* Encoding and decoding without walrus, either through the unresolved AST with
+  indexed references (see `binary::encode_unresolved` and
+  `binary::decode_unresolved`), or by implementing `binary::Indices` for some
+  other Wasm emitter.
+
+* A zero-copy, streaming reader for the binary format that yields borrowed
+  items along with their byte offsets, for tools that don't need the whole
+  AST. See `binary::read::SectionReader`.
+
+* A `wasm-objdump`-style dumper that annotates the raw bytes of an encoded
+  section, even a malformed one. See `binary::dump`.
+
+* A printer that turns a decoded section back into the text format. See
+  `text::print`.
+
+* A replacement for walrus' garbage collection pass that keeps the Wasm
+  functions and function types that a section refers to. See `gc::run`.
+
+* Merging of the sections of several modules that are linked into one, which
+  shares identical Web IDL types and renames clashing names. See `merge::Merge`.
+
+* Splitting of a section alongside its module, giving each part exactly the
+  binds, function bindings and types that it needs. See `split::split`.
+
+* Optional `serde` support for the AST, and a JSON interchange format for
+  tools that want to read bindings without parsing Wasm. Enable the `serde`
+  cargo feature and see `interchange::to_json`.
+
+* A `wasm-webidl-bindings` command-line tool that embeds, extracts, validates,
+  strips and dumps sections, and formats text format bindings. See
+  `crates/cli`.
 
 ## Example
 
@@ -93,4 +139,17 @@ let new_raw_wasm = module.emit_wasm();
 
 pub mod ast;
 pub mod binary;
+pub mod embed;
+pub mod gc;
+#[cfg(feature = "serde")]
+pub mod interchange;
+pub mod merge;
+pub mod spans;
+pub mod split;
 pub mod text;
+pub mod unresolved;
+
+// Used by the code that `wasm_webidl_bindings_macro::webidl_bindings!` expands
+// to.
+#[doc(hidden)]
+pub use walrus;
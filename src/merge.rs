@@ -0,0 +1,300 @@
+//! Merging the Web IDL bindings sections of several Wasm modules into one
+//! section, for when the modules are linked or merged into one module.
+//!
+//! Identical Web IDL types are only kept once. Names of types and function
+//! bindings that clash with a name from an earlier section are given a suffix,
+//! like `$Contact_1`, and references to function bindings inside `bind-export`
+//! and `bind-import` expressions are redirected to the merged bindings.
+//!
+//! A type, function binding or bind that refers to a type or function binding
+//! that isn't in its own section has nothing to refer to in the merged section,
+//! so it is dropped, along with everything that refers to it in turn.
+//!
+//! Unknown subsections can't be understood, so they are kept in the order that
+//! the sections are added, and only identical copies of one are dropped. Two
+//! sections may still each have a different subsection with the same unknown
+//! id, which the producer that knows about that id has to be able to handle.
+
+use crate::ast::*;
+use id_arena::{Arena, Id};
+use std::collections::{HashMap, HashSet};
+
+/// Merge `sections` into one section, keeping their Wasm function and function
+/// type references as they are.
+///
+/// This is for sections that already refer to the merged module. Use `Merge`
+/// to redirect their references to the merged module's functions and types.
+pub fn merge<I>(sections: I) -> WebidlBindings
+where
+    I: IntoIterator<Item = WebidlBindings>,
+{
+    let mut merge = Merge::new();
+    for section in sections {
+        merge.add(section, |f| f, |ty| ty);
+    }
+    merge.finish()
+}
+
+/// Merges sections one at a time.
+#[derive(Debug, Default)]
+pub struct Merge {
+    section: WebidlBindings,
+    /// The merged types that can be shared with later sections.
+    types: HashMap<WebidlCompoundType, Id<WebidlCompoundType>>,
+}
+
+impl Merge {
+    /// Create a merge that doesn't have any sections yet.
+    pub fn new() -> Merge {
+        Merge::default()
+    }
+
+    /// Add `section` to the merge.
+    ///
+    /// `funcs` and `types` map the Wasm functions and function types of the
+    /// module that `section` is for to the merged module's, like
+    /// `WebidlBindings::remap`.
+    pub fn add<F, T>(&mut self, mut section: WebidlBindings, funcs: F, types: T)
+    where
+        F: Fn(walrus::FunctionId) -> walrus::FunctionId,
+        T: Fn(walrus::TypeId) -> walrus::TypeId,
+    {
+        section.remap(funcs, types);
+
+        let type_ids = self.add_types(&section.types);
+        let mut names: Vec<_> = section.types.names.iter().collect();
+        names.sort();
+        for (name, id) in names {
+            if let Some(id) = type_ids.get(id) {
+                let types = &self.section.types;
+                let name = unique_name(name, |name| match types.names.get(name) {
+                    Some(existing) => existing == id,
+                    None => types.resolve(name).is_none(),
+                });
+                self.section
+                    .types
+                    .set_name(name, *id)
+                    .expect("the name of a type is free");
+            }
+        }
+        let mut aliases: Vec<_> = section.types.aliases().collect();
+        aliases.sort_by_key(|(name, _)| *name);
+        for (name, mut ty) in aliases {
+            if let WebidlTypeRef::Id(id) = ty {
+                if !type_ids.contains_key(&id) {
+                    continue;
+                }
+            }
+            map_type_ref(&mut ty, &type_ids);
+            let types = &self.section.types;
+            let name = unique_name(name, |name| match types.aliases.get(name) {
+                Some(existing) => *existing == ty,
+                None => types.resolve(name).is_none(),
+            });
+            // The name is either free, or already an alias of the same type.
+            if !types.aliases.contains_key(&name) {
+                self.section
+                    .types
+                    .alias(name, ty)
+                    .expect("the name of an alias is free");
+            }
+        }
+
+        let bindings = &section.bindings.arena;
+        let dropped = dangling(bindings, |binding, dropped| {
+            let (mut bad_type, mut bad_binding) = (false, false);
+            binding.visit_refs(
+                &mut |ty| {
+                    if let WebidlTypeRef::Id(id) = ty {
+                        bad_type |= !type_ids.contains_key(&id);
+                    }
+                },
+                &mut |id| bad_binding |= bindings.get(id).is_none() || dropped.contains(&id),
+            );
+            bad_type || bad_binding
+        });
+        let mut binding_ids = HashMap::new();
+        for (id, binding) in section.bindings.arena.iter() {
+            if dropped.contains(&id) {
+                continue;
+            }
+            let new_id = match binding.clone() {
+                FunctionBinding::Import(i) => self.section.bindings.insert(i).into(),
+                FunctionBinding::Export(e) => self.section.bindings.insert(e).into(),
+            };
+            binding_ids.insert(id, new_id);
+        }
+        for new_id in binding_ids.values() {
+            self.section.bindings.arena[*new_id].visit_refs_mut(
+                &mut |ty| map_type_ref(ty, &type_ids),
+                &mut |binding| {
+                    if let Some(id) = binding_ids.get(binding) {
+                        *binding = *id;
+                    }
+                },
+            );
+        }
+        let mut names: Vec<_> = section.bindings.names.iter().collect();
+        names.sort();
+        for (name, id) in names {
+            if let Some(id) = binding_ids.get(id) {
+                let bindings = &self.section.bindings;
+                let name = unique_name(name, |name| match bindings.by_name(name) {
+                    Some(existing) => existing == *id,
+                    None => true,
+                });
+                self.section.bindings.names.insert(name, *id);
+            }
+        }
+
+        for (_, bind) in section.binds.iter() {
+            // A bind of a function binding that isn't in its section has
+            // nothing to refer to in the merged section either.
+            if let Some(binding) = binding_ids.get(&bind.binding) {
+                self.section.binds.insert(Bind {
+                    func: bind.func,
+                    binding: *binding,
+                });
+            }
+        }
+
+        let extensions = &mut self.section.extensions;
+        extensions.features |= section.extensions.features;
+        for subsection in section.extensions.subsections {
+            if !extensions.subsections.contains(&subsection) {
+                extensions.subsections.push(subsection);
+            }
+        }
+    }
+
+    /// Get the merged section.
+    pub fn finish(self) -> WebidlBindings {
+        self.section
+    }
+
+    /// Add the types of a section, returning the merged ID of each of them.
+    fn add_types(
+        &mut self,
+        types: &WebidlTypes,
+    ) -> HashMap<Id<WebidlCompoundType>, Id<WebidlCompoundType>> {
+        let mut ids = HashMap::new();
+        let dropped = dangling(&types.arena, |ty, dropped| {
+            ty.type_refs().into_iter().any(|r| match r {
+                WebidlTypeRef::Id(id) => types.arena.get(id).is_none() || dropped.contains(&id),
+                WebidlTypeRef::Scalar(_) => false,
+            })
+        });
+
+        // A type can only be compared with the merged types once the types it
+        // refers to are merged, so keep merging the types whose references
+        // are all merged until no more are.
+        let mut pending: Vec<_> = types
+            .arena
+            .iter()
+            .filter(|(id, _)| !dropped.contains(id))
+            .collect();
+        loop {
+            let len = pending.len();
+            pending.retain(|(id, ty)| {
+                let mut ty = (*ty).clone();
+                let merged = ty.type_refs_mut().into_iter().all(|r| match r {
+                    WebidlTypeRef::Id(id) => match ids.get(id) {
+                        Some(new_id) => {
+                            *id = *new_id;
+                            true
+                        }
+                        None => false,
+                    },
+                    WebidlTypeRef::Scalar(_) => true,
+                });
+                if !merged {
+                    return true;
+                }
+                let section = &mut self.section;
+                let new_id = *self
+                    .types
+                    .entry(ty.clone())
+                    .or_insert_with(|| insert_type(&mut section.types, ty));
+                ids.insert(*id, new_id);
+                false
+            });
+            if pending.is_empty() || pending.len() == len {
+                break;
+            }
+        }
+
+        // The rest refer to each other in cycles, so they are added as they
+        // are, and their references are fixed up afterwards.
+        let cyclic: Vec<_> = pending
+            .into_iter()
+            .map(|(id, ty)| {
+                let new_id = insert_type(&mut self.section.types, ty.clone());
+                ids.insert(id, new_id);
+                new_id
+            })
+            .collect();
+        for new_id in cyclic {
+            let ty = &mut self.section.types.arena[new_id];
+            for r in ty.type_refs_mut() {
+                map_type_ref(r, &ids);
+            }
+        }
+        ids
+    }
+}
+
+/// Find the items of `arena` that `is_dangling` says refer to something that
+/// isn't there, given the items that have been found so far, so that an item
+/// referring to one that was found is found as well.
+fn dangling<T>(
+    arena: &Arena<T>,
+    is_dangling: impl Fn(&T, &HashSet<Id<T>>) -> bool,
+) -> HashSet<Id<T>> {
+    let mut found = HashSet::new();
+    loop {
+        let len = found.len();
+        for (id, item) in arena.iter() {
+            if !found.contains(&id) && is_dangling(item, &found) {
+                found.insert(id);
+            }
+        }
+        if found.len() == len {
+            return found;
+        }
+    }
+}
+
+/// Get `name`, or if `available` says that it is taken, the first of
+/// `name_1`, `name_2`, and so on that isn't.
+fn unique_name(name: &str, available: impl Fn(&str) -> bool) -> String {
+    if available(name) {
+        return name.to_string();
+    }
+    (1..)
+        .map(|i| format!("{}_{}", name, i))
+        .find(|name| available(name))
+        .unwrap()
+}
+
+pub(crate) fn map_type_ref(
+    ty: &mut WebidlTypeRef,
+    ids: &HashMap<Id<WebidlCompoundType>, Id<WebidlCompoundType>>,
+) {
+    if let WebidlTypeRef::Id(id) = ty {
+        if let Some(new_id) = ids.get(id) {
+            *id = *new_id;
+        }
+    }
+}
+
+pub(crate) fn insert_type(
+    types: &mut WebidlTypes,
+    ty: WebidlCompoundType,
+) -> Id<WebidlCompoundType> {
+    match ty {
+        WebidlCompoundType::Function(f) => types.insert(f).into(),
+        WebidlCompoundType::Dictionary(d) => types.insert(d).into(),
+        WebidlCompoundType::Enumeration(e) => types.insert(e).into(),
+        WebidlCompoundType::Union(u) => types.insert(u).into(),
+    }
+}
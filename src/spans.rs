@@ -0,0 +1,129 @@
+//! Source spans for the AST nodes parsed from the text format.
+//!
+//! The AST itself doesn't remember where its nodes came from. When parsing with
+//! `text::parse_with_spans`, the `BuildAstActions` also fill in a `Spans` side
+//! table that maps AST nodes back to the byte ranges of the input they were
+//! parsed from, so that errors found later can point at the offending text, as
+//! `binary::EncodeError::render` does.
+
+use crate::ast::{Bind, FunctionBinding, WebidlCompoundType};
+use id_arena::Id;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// A byte range of the text format input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Which of a function binding's two binding maps an expression is in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BindingMapKind {
+    Params,
+    Result,
+}
+
+/// Identifies a binding expression within a function binding.
+///
+/// The `path` starts with the expression's index within its binding map. If
+/// the expression is nested in another expression, then that is followed by
+/// its index within the `fields` of an `OutgoingBindingExpressionDict`, or by
+/// `0` for the single `expr` of an incoming binding expression, and so on for
+/// each level of nesting.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExpressionId {
+    pub binding: Id<FunctionBinding>,
+    pub map: BindingMapKind,
+    pub path: Vec<u32>,
+}
+
+/// A side table mapping AST nodes to the spans of the text format input they
+/// were parsed from.
+#[derive(Debug, Default)]
+pub struct Spans {
+    pub(crate) types: HashMap<Id<WebidlCompoundType>, Span>,
+    pub(crate) bindings: HashMap<Id<FunctionBinding>, Span>,
+    pub(crate) binds: HashMap<Id<Bind>, Span>,
+    pub(crate) expressions: HashMap<ExpressionId, Span>,
+}
+
+impl Spans {
+    /// Get the span of the given compound type, e.g. `(dict ...)`.
+    pub fn webidl_type<T>(&self, id: T) -> Option<Span>
+    where
+        T: Into<Id<WebidlCompoundType>>,
+    {
+        self.types.get(&id.into()).cloned()
+    }
+
+    /// Get the span of the given function binding's `func-binding` item.
+    pub fn binding<T>(&self, id: T) -> Option<Span>
+    where
+        T: Into<Id<FunctionBinding>>,
+    {
+        self.bindings.get(&id.into()).cloned()
+    }
+
+    /// Get the span of the given `bind` item.
+    pub fn bind(&self, id: Id<Bind>) -> Option<Span> {
+        self.binds.get(&id).cloned()
+    }
+
+    /// Get the span of the given binding expression.
+    pub fn expression(&self, id: &ExpressionId) -> Option<Span> {
+        self.expressions.get(id).cloned()
+    }
+}
+
+/// Render an error message along with a snippet of the input that the span
+/// points at, like this:
+///
+/// ```text
+/// error: unknown thing
+///  --> 2:15
+///   |
+/// 2 | type $Broken (dict (field "age" $Nope))
+///   |                                 ^^^^^
+/// ```
+///
+/// Line and column numbers start at 1. Only the first line of a span that
+/// covers multiple lines is shown. Offsets past the end of the input, or in the
+/// middle of a character, are moved back to the nearest character boundary.
+pub fn render_snippet(input: &str, span: Span, message: &str) -> String {
+    let start = char_boundary(input, span.start);
+    let end = char_boundary(input, span.end.max(start));
+
+    let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+    let line = &input[line_start..line_end];
+    let line_number = input[..start].matches('\n').count() + 1;
+    let column = input[line_start..start].chars().count();
+    let width = input[start..end.min(line_end)].chars().count().max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    let mut out = String::new();
+    writeln!(out, "error: {}", message).unwrap();
+    writeln!(out, "{}--> {}:{}", gutter, line_number, column + 1).unwrap();
+    writeln!(out, "{} |", gutter).unwrap();
+    writeln!(out, "{} | {}", line_number, line).unwrap();
+    write!(
+        out,
+        "{} | {}{}",
+        gutter,
+        " ".repeat(column),
+        "^".repeat(width)
+    )
+    .unwrap();
+    out
+}
+
+/// Get the last character boundary of `input` at or before `offset`.
+fn char_boundary(input: &str, offset: usize) -> usize {
+    let mut offset = offset.min(input.len());
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
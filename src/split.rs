@@ -0,0 +1,182 @@
+//! Splitting a Web IDL bindings section alongside its Wasm module, for when the
+//! module is split into a primary module and lazily loaded secondary modules.
+//!
+//! Each part of the split is given the functions that end up in its module, and
+//! gets its own section with exactly the binds for those functions, the
+//! function bindings that they use, and the Web IDL types that those refer to.
+//! Types that several parts need are copied into each of them.
+
+use crate::ast::*;
+use crate::merge::{insert_type, map_type_ref};
+use std::collections::{HashMap, HashSet};
+
+/// One part of a split section.
+#[derive(Debug)]
+pub struct Part {
+    /// The section for this part's module.
+    ///
+    /// Its binds and function bindings still refer to the Wasm functions and
+    /// function types of the module that was split. Use `WebidlBindings::remap`
+    /// to redirect them to the part's module.
+    pub section: WebidlBindings,
+    /// The names of the exported allocator functions that this part's
+    /// `alloc-utf8-str` and `alloc-copy` expressions call, which the part's
+    /// module must export, sorted and without duplicates.
+    pub allocators: Vec<String>,
+}
+
+/// Split `section` into one part for each set of functions in `parts`.
+pub fn split(section: &WebidlBindings, parts: &[HashSet<walrus::FunctionId>]) -> Vec<Part> {
+    parts.iter().map(|funcs| part(section, funcs)).collect()
+}
+
+/// Get the part of `section` that binds the functions in `funcs`.
+pub fn part(section: &WebidlBindings, funcs: &HashSet<walrus::FunctionId>) -> Part {
+    let binds: Vec<_> = section
+        .binds
+        .iter()
+        .filter(|(_, bind)| funcs.contains(&bind.func))
+        .map(|(_, bind)| bind)
+        .collect();
+
+    // Find the function bindings that the binds use, and the bindings and
+    // types that those refer to in turn.
+    let mut bindings = HashSet::new();
+    let mut types = HashSet::new();
+    let mut allocators = vec![];
+    let mut stack: Vec<_> = binds.iter().map(|bind| bind.binding).collect();
+    while let Some(id) = stack.pop() {
+        if !bindings.insert(id) {
+            continue;
+        }
+        let binding = match section.bindings.arena.get(id) {
+            Some(binding) => binding,
+            None => continue,
+        };
+        binding.visit_refs(
+            &mut |ty| {
+                if let WebidlTypeRef::Id(id) = ty {
+                    types.insert(id);
+                }
+            },
+            &mut |binding| stack.push(binding),
+        );
+        let incoming = match binding {
+            FunctionBinding::Import(i) => &i.result,
+            FunctionBinding::Export(e) => &e.params,
+        };
+        for expr in &incoming.bindings {
+            allocator_names(expr, &mut allocators);
+        }
+    }
+    let mut stack: Vec<_> = types.drain().collect();
+    while let Some(id) = stack.pop() {
+        if !types.insert(id) {
+            continue;
+        }
+        if let Some(ty) = section.types.arena.get(id) {
+            for r in ty.type_refs() {
+                if let WebidlTypeRef::Id(id) = r {
+                    stack.push(id);
+                }
+            }
+        }
+    }
+    allocators.sort();
+    allocators.dedup();
+
+    // Copy everything that is needed, in the order of the original section.
+    let mut part = WebidlBindings::default();
+    let mut type_ids = HashMap::new();
+    for (id, ty) in section.types.arena.iter() {
+        if types.contains(&id) {
+            type_ids.insert(id, insert_type(&mut part.types, ty.clone()));
+        }
+    }
+    for new_id in type_ids.values() {
+        for r in part.types.arena[*new_id].type_refs_mut() {
+            map_type_ref(r, &type_ids);
+        }
+    }
+    for (name, id) in section.types.names.iter() {
+        if let Some(id) = type_ids.get(id) {
+            part.types
+                .set_name(name.clone(), *id)
+                .expect("the part has no aliases yet");
+        }
+    }
+    // Aliases of scalar types are only names, so every part keeps them, but an
+    // alias of a compound type is only kept along with the type.
+    for (name, ty) in section.types.aliases() {
+        let ty = match ty {
+            WebidlTypeRef::Id(id) => match type_ids.get(&id) {
+                Some(id) => WebidlTypeRef::Id(*id),
+                None => continue,
+            },
+            WebidlTypeRef::Scalar(_) => ty,
+        };
+        part.types
+            .alias(name.to_string(), ty)
+            .expect("the names and aliases of a section never clash");
+    }
+
+    let mut binding_ids = HashMap::new();
+    for (id, binding) in section.bindings.arena.iter() {
+        if bindings.contains(&id) {
+            let new_id = match binding.clone() {
+                FunctionBinding::Import(i) => part.bindings.insert(i).into(),
+                FunctionBinding::Export(e) => part.bindings.insert(e).into(),
+            };
+            binding_ids.insert(id, new_id);
+        }
+    }
+    for new_id in binding_ids.values() {
+        part.bindings.arena[*new_id].visit_refs_mut(
+            &mut |ty| map_type_ref(ty, &type_ids),
+            &mut |binding| {
+                if let Some(id) = binding_ids.get(binding) {
+                    *binding = *id;
+                }
+            },
+        );
+    }
+    for (name, id) in section.bindings.names.iter() {
+        if let Some(id) = binding_ids.get(id) {
+            part.bindings.names.insert(name.clone(), *id);
+        }
+    }
+
+    for bind in binds {
+        // A bind of a function binding that isn't in the section has nothing
+        // to refer to in the part either.
+        if let Some(binding) = binding_ids.get(&bind.binding) {
+            part.binds.insert(Bind {
+                func: bind.func,
+                binding: *binding,
+            });
+        }
+    }
+
+    // Unknown subsections can't be split, since we don't know what they refer
+    // to, so each part keeps all of them, along with the unknown optional
+    // features that may describe them. A part with a subsection that it
+    // doesn't need is harmless, since decoders that don't know its id skip
+    // it, but dropping one could lose data that the part does need.
+    part.extensions = section.extensions.clone();
+
+    Part {
+        section: part,
+        allocators,
+    }
+}
+
+fn allocator_names(expr: &IncomingBindingExpression, names: &mut Vec<String>) {
+    match expr {
+        IncomingBindingExpression::AllocUtf8Str(e) => names.push(e.alloc_func_name.clone()),
+        IncomingBindingExpression::AllocCopy(e) => names.push(e.alloc_func_name.clone()),
+        _ => {}
+    }
+    if let Some(expr) = expr.nested() {
+        allocator_names(expr, names);
+    }
+}
@@ -1,5 +1,10 @@
 //! Working with the text format.
 
+mod annotations;
+mod print;
+
+pub use self::print::print;
+pub(crate) use self::print::scalar_type_name;
 pub use wasm_webidl_bindings_text_parser::*;
 
 /// Parse the given straw proposal text format input into an AST.
@@ -13,3 +18,141 @@ pub fn parse(
     parse_with_actions(&mut actions, input)?;
     Ok(bindings)
 }
+
+/// Parse the given straw proposal text format input into an AST, along with a
+/// side table of the span of the input that each AST node was parsed from.
+pub fn parse_with_spans(
+    module: &walrus::Module,
+    indices_to_ids: &walrus::IndicesToIds,
+    input: &str,
+) -> Result<(crate::ast::WebidlBindings, crate::spans::Spans), failure::Error> {
+    let mut bindings = crate::ast::WebidlBindings::default();
+    let mut spans = crate::spans::Spans::default();
+    let mut actions = crate::ast::BuildAstActions::new(&mut bindings, module, indices_to_ids)
+        .with_spans(&mut spans);
+    parse_with_actions(&mut actions, input)?;
+    Ok((bindings, spans))
+}
+
+/// Parse the given straw proposal text format input into an AST whose
+/// references to Wasm functions and function types haven't been resolved yet.
+///
+/// This doesn't need the Wasm module that the bindings are for. Use
+/// `unresolved::WebidlBindings::resolve` to resolve the AST against the module
+/// once it is available.
+pub fn parse_unresolved(input: &str) -> Result<crate::unresolved::WebidlBindings, failure::Error> {
+    let mut bindings = crate::unresolved::WebidlBindings::default();
+    let mut actions = crate::unresolved::BuildUnresolvedAstActions::new(&mut bindings);
+    parse_with_actions(&mut actions, input)?;
+    Ok(bindings)
+}
+
+/// Parse the given straw proposal text format input into an AST, recovering
+/// from errors instead of stopping at the first one.
+///
+/// Returns the AST built from every top-level item that parsed successfully,
+/// along with all of the errors that were found.
+pub fn parse_recovering(
+    module: &walrus::Module,
+    indices_to_ids: &walrus::IndicesToIds,
+    input: &str,
+) -> (crate::ast::WebidlBindings, Vec<Error>) {
+    let mut bindings = crate::ast::WebidlBindings::default();
+    let mut actions = crate::ast::BuildAstActions::new(&mut bindings, module, indices_to_ids);
+    let ((), errors) = parse_with_actions_recovering(&mut actions, input);
+    (bindings, errors)
+}
+
+/// Compile the given WebAssembly text format module, and attach the Web IDL
+/// bindings written in its `(@webidl ...)` annotations to it as a custom
+/// section.
+///
+/// The annotations contain straw proposal text format items, and may appear
+/// anywhere in the module. Their contents are parsed in order, as if they were
+/// one text format input:
+///
+/// ```
+/// let module = wasm_webidl_bindings::text::parse_wat(r#"
+///     (module
+///       (type $EncodeIntoFuncWasm (func (param externref externref i32 i32) (result i64)))
+///       (import "TextEncoder" "encodeInto" (func $encodeInto (type $EncodeIntoFuncWasm)))
+///
+///       (@webidl
+///         type $EncodeIntoFuncWebIDL
+///           (func (method any) (param USVString Uint8Array) (result any))
+///         func-binding $encodeIntoBinding import $EncodeIntoFuncWasm $EncodeIntoFuncWebIDL
+///           (param (as any 0) (as any 1) (view Uint8Array 2 3))
+///           (result (as i64 (get 0))))
+///       (@webidl bind $encodeInto $encodeIntoBinding))
+/// "#)?;
+/// # let _ = module;
+/// # Ok::<(), failure::Error>(())
+/// ```
+///
+/// Wasm functions and function types may be referred to by their `$id`s in
+/// the WAT, as well as by index. Errors in the bindings are reported with the
+/// line of the WAT that they are on.
+pub fn parse_wat(wat: &str) -> Result<walrus::Module, failure::Error> {
+    let annotations::Extracted {
+        wat: stripped,
+        bindings: input,
+        found,
+        type_names,
+    } = annotations::extract(wat)?;
+    let wasm = ::wat::parse_str(&stripped)?;
+    if !found {
+        return walrus::Module::from_buffer(&wasm);
+    }
+
+    let source = wat.to_string();
+    let mut config = walrus::ModuleConfig::default();
+    config.on_parse(move |module, indices_to_ids| {
+        // Type names aren't in the name section, so give each type the `$id`
+        // that the WAT defined it with.
+        for (idx, name) in type_names.iter().enumerate() {
+            if let (Some(name), Ok(ty)) = (name, indices_to_ids.get_type(idx as u32)) {
+                module.types.get_mut(ty).name = Some(name.clone());
+            }
+        }
+
+        // The name section has function `$id`s without their `$`, so add it
+        // back while parsing the bindings.
+        let original_names: Vec<_> = module
+            .funcs
+            .iter()
+            .map(|f| (f.id(), f.name.clone()))
+            .collect();
+        for (id, name) in &original_names {
+            if let Some(name) = name {
+                module.funcs.get_mut(*id).name = Some(format!("${}", name));
+            }
+        }
+        let (bindings, errors) = parse_recovering(module, indices_to_ids, &input);
+        for (id, name) in original_names {
+            module.funcs.get_mut(id).name = name;
+        }
+
+        if !errors.is_empty() {
+            failure::bail!("{}", render_errors(&source, &errors));
+        }
+        module.customs.add(bindings);
+        Ok(())
+    });
+    config.parse(&wasm)
+}
+
+/// Render each of the given errors as a snippet of `input`, separated by blank
+/// lines.
+pub(crate) fn render_errors(input: &str, errors: &[Error]) -> String {
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|e| {
+            let span = crate::spans::Span {
+                start: e.start,
+                end: e.end,
+            };
+            crate::spans::render_snippet(input, span, &e.message)
+        })
+        .collect();
+    errors.join("\n\n")
+}
@@ -0,0 +1,194 @@
+//! Extracting `(@webidl ...)` annotations from the WebAssembly text format.
+//!
+//! The annotations are written in the style of the WebAssembly annotations
+//! proposal, and contain items of the straw proposal text format:
+//!
+//! ```wat
+//! (module
+//!   (type $t (func))
+//!   (import "env" "f" (func $f (type $t)))
+//!   (@webidl func-binding $b import 0 any (param) (result))
+//!   (@webidl bind $f $b))
+//! ```
+
+use crate::spans::{render_snippet, Span};
+
+const ANNOTATION: &str = "(@webidl";
+
+/// The result of extracting the `(@webidl ...)` annotations from a WAT module.
+///
+/// `wat` and `bindings` are the same length as the input, and everything in
+/// them is at the same offset that it was in the input, so that errors found in
+/// either one can be reported against the input.
+#[derive(Debug)]
+pub(crate) struct Extracted {
+    /// The input with every annotation blanked out.
+    pub wat: String,
+    /// The contents of every annotation, with everything else blanked out.
+    pub bindings: String,
+    /// Whether any annotations were found.
+    pub found: bool,
+    /// The `$id` of each of the module's `type` definitions, in order.
+    pub type_names: Vec<Option<String>>,
+}
+
+/// Replace every character in `bytes` other than a newline with a space.
+fn blank(bytes: &mut [u8]) {
+    for b in bytes {
+        if *b != b'\n' {
+            *b = b' ';
+        }
+    }
+}
+
+fn is_delimiter(b: u8) -> bool {
+    b.is_ascii_whitespace() || b == b'(' || b == b')' || b == b'"' || b == b';'
+}
+
+/// Get the length of the token at the start of `bytes`.
+fn token_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .position(|b| is_delimiter(*b))
+        .unwrap_or(bytes.len())
+}
+
+pub(crate) fn extract(input: &str) -> Result<Extracted, failure::Error> {
+    let bytes = input.as_bytes();
+    let mut wat = bytes.to_vec();
+    let mut bindings = bytes.to_vec();
+    blank(&mut bindings);
+    let mut found = false;
+    let mut type_names = vec![];
+
+    // The keyword at the head of each list that we are inside of.
+    let mut heads: Vec<&str> = vec![];
+    // The offset of the annotation that we are inside of, and how many lists
+    // we were inside of when it started.
+    let mut annotation: Option<(usize, usize)> = None;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(b";;") {
+            i += rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        } else if rest.starts_with(b"(;") {
+            // Block comments nest.
+            let mut depth = 0;
+            while i < bytes.len() {
+                if bytes[i..].starts_with(b"(;") {
+                    depth += 1;
+                    i += 2;
+                } else if bytes[i..].starts_with(b";)") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if rest[0] == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+        } else if rest[0] == b'(' {
+            let head_len = token_len(&rest[1..]);
+            let head = &input[i + 1..i + 1 + head_len];
+            if annotation.is_none() && &input[i..i + 1 + head_len] == ANNOTATION {
+                annotation = Some((i, heads.len()));
+                found = true;
+            } else if annotation.is_none()
+                && head == "type"
+                && heads.last().map_or(true, |h| *h == "module")
+            {
+                let after = i + 1 + head_len;
+                let ws = bytes[after..]
+                    .iter()
+                    .position(|b| !b.is_ascii_whitespace())
+                    .unwrap_or(bytes.len() - after);
+                let id = &input[after + ws..after + ws + token_len(&bytes[after + ws..])];
+                type_names.push(if id.starts_with('$') {
+                    Some(id.to_string())
+                } else {
+                    None
+                });
+            }
+            heads.push(head);
+            i += 1 + head_len;
+        } else if rest[0] == b')' {
+            heads.pop();
+            if let Some((start, depth)) = annotation {
+                if heads.len() == depth {
+                    let contents = start + ANNOTATION.len()..i;
+                    bindings[contents.clone()].copy_from_slice(&bytes[contents]);
+                    blank(&mut wat[start..=i]);
+                    annotation = None;
+                }
+            }
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    if let Some((start, _)) = annotation {
+        let span = Span {
+            start,
+            end: start + ANNOTATION.len(),
+        };
+        failure::bail!(
+            "{}",
+            render_snippet(input, span, "unterminated `(@webidl ...)` annotation")
+        );
+    }
+
+    // Only whole characters were blanked out or copied, so these are still
+    // valid UTF-8.
+    Ok(Extracted {
+        wat: String::from_utf8(wat).unwrap(),
+        bindings: String::from_utf8(bindings).unwrap(),
+        found,
+        type_names,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_annotations() {
+        let input = r#"(module
+  (type $t (func))
+  (type (func (param i32)))
+  (func $f (type $t) (@webidl type $T (dict (field "a)" any)))) ;; (@webidl nope)
+  (@webidl bind $f 0))"#;
+        let extracted = extract(input).unwrap();
+        assert_eq!(extracted.wat.len(), input.len());
+        assert_eq!(extracted.bindings.len(), input.len());
+        assert!(extracted.found);
+        assert_eq!(extracted.type_names, [Some("$t".to_string()), None]);
+        assert_eq!(
+            extracted.wat.split_whitespace().collect::<Vec<_>>(),
+            [
+                "(module", "(type", "$t", "(func))", "(type", "(func", "(param", "i32)))", "(func",
+                "$f", "(type", "$t)", ")", ";;", "(@webidl", "nope)", ")"
+            ]
+        );
+        assert_eq!(
+            extracted.bindings.split_whitespace().collect::<Vec<_>>(),
+            ["type", "$T", "(dict", "(field", "\"a)\"", "any))", "bind", "$f", "0"]
+        );
+        assert_eq!(extracted.bindings.find("type"), input.find("type $T"));
+    }
+
+    #[test]
+    fn unterminated_annotation() {
+        let error = extract("(module\n  (@webidl bind $f 0").unwrap_err();
+        assert!(error.to_string().contains("unterminated"));
+    }
+}
@@ -0,0 +1,410 @@
+//! Printing an AST in the straw proposal text format.
+
+use crate::ast::*;
+use id_arena::Id;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Print the given Web IDL bindings section in the straw proposal text format,
+/// formatted with `text::format`.
+///
+/// Web IDL types and function bindings are referred to by name if they have
+/// one, and by index otherwise. Wasm functions are referred to by their import
+/// or export name if they have one, and otherwise, like Wasm function types,
+/// by their index in the original Wasm binary. Returns an error if the section
+/// refers to a Wasm function or function type that wasn't in the original Wasm
+/// binary.
+pub fn print(
+    section: &WebidlBindings,
+    module: &walrus::Module,
+    indices_to_ids: &walrus::IndicesToIds,
+) -> Result<String, failure::Error> {
+    let mut p = Printer {
+        section,
+        module,
+        out: String::new(),
+        type_names: invert(&section.types.names),
+        type_indices: HashMap::new(),
+        binding_names: invert(&section.bindings.names),
+        binding_indices: HashMap::new(),
+        func_indices: HashMap::new(),
+        func_type_indices: HashMap::new(),
+    };
+    for (idx, (id, _)) in section.types.arena.iter().enumerate() {
+        p.type_indices.insert(id, idx as u32);
+    }
+    for (idx, (id, _)) in section.bindings.arena.iter().enumerate() {
+        p.binding_indices.insert(id, idx as u32);
+    }
+    let mut idx = 0;
+    while let Ok(id) = indices_to_ids.get_func(idx) {
+        p.func_indices.insert(id, idx);
+        idx += 1;
+    }
+    let mut idx = 0;
+    while let Ok(id) = indices_to_ids.get_type(idx) {
+        p.func_type_indices.insert(id, idx);
+        idx += 1;
+    }
+
+    p.section()?;
+    crate::text::format(&p.out).map_err(|e| failure::format_err!("{}", e))
+}
+
+/// Quote a string, escaping any `"` or `\` in it so that it parses back to the
+/// same string.
+fn quoted(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Map each ID to the first of its names, in sorted order.
+fn invert<T>(names: &HashMap<String, Id<T>>) -> HashMap<Id<T>, &str> {
+    let mut inverted = HashMap::new();
+    for (name, id) in names {
+        let first = inverted.entry(*id).or_insert(name.as_str());
+        if name.as_str() < *first {
+            *first = name.as_str();
+        }
+    }
+    inverted
+}
+
+struct Printer<'a> {
+    section: &'a WebidlBindings,
+    module: &'a walrus::Module,
+    out: String,
+    type_names: HashMap<Id<WebidlCompoundType>, &'a str>,
+    type_indices: HashMap<Id<WebidlCompoundType>, u32>,
+    binding_names: HashMap<Id<FunctionBinding>, &'a str>,
+    binding_indices: HashMap<Id<FunctionBinding>, u32>,
+    func_indices: HashMap<walrus::FunctionId, u32>,
+    func_type_indices: HashMap<walrus::TypeId, u32>,
+}
+
+impl Printer<'_> {
+    fn section(&mut self) -> Result<(), failure::Error> {
+        for (id, ty) in self.section.types.arena.iter() {
+            self.out.push_str("type ");
+            if let Some(name) = self.type_names.get(&id) {
+                write!(self.out, "{} ", name).unwrap();
+            }
+            self.compound_type(ty);
+            self.out.push('\n');
+        }
+
+        let mut aliases: Vec<_> = self.section.types.aliases().collect();
+        aliases.sort_by_key(|(name, _)| *name);
+        for (name, ty) in aliases {
+            write!(self.out, "type {} = ", name).unwrap();
+            self.webidl_type_ref(ty);
+            self.out.push('\n');
+        }
+
+        for (id, binding) in self.section.bindings.arena.iter() {
+            self.out.push_str("func-binding ");
+            if let Some(name) = self.binding_names.get(&id) {
+                write!(self.out, "{} ", name).unwrap();
+            }
+            match binding {
+                FunctionBinding::Import(b) => {
+                    self.out.push_str("import ");
+                    self.wasm_func_type_ref(b.wasm_ty)?;
+                    self.webidl_type_ref(b.webidl_ty);
+                    self.out.push_str(" (param");
+                    for expr in &b.params.bindings {
+                        self.outgoing(expr);
+                    }
+                    self.out.push_str(") (result");
+                    for expr in &b.result.bindings {
+                        self.incoming(expr)?;
+                    }
+                }
+                FunctionBinding::Export(b) => {
+                    self.out.push_str("export ");
+                    self.wasm_func_type_ref(b.wasm_ty)?;
+                    self.webidl_type_ref(b.webidl_ty);
+                    self.out.push_str(" (param");
+                    for expr in &b.params.bindings {
+                        self.incoming(expr)?;
+                    }
+                    self.out.push_str(") (result");
+                    for expr in &b.result.bindings {
+                        self.outgoing(expr);
+                    }
+                }
+            }
+            self.out.push_str(")\n");
+        }
+
+        for (_, bind) in self.section.binds.iter() {
+            self.out.push_str("bind ");
+            self.wasm_func_ref(bind.func)?;
+            self.binding_ref(bind.binding);
+            self.out.push('\n');
+        }
+        Ok(())
+    }
+
+    fn compound_type(&mut self, ty: &WebidlCompoundType) {
+        match ty {
+            WebidlCompoundType::Function(f) => {
+                self.out.push_str("(func");
+                match &f.kind {
+                    WebidlFunctionKind::Static => {}
+                    WebidlFunctionKind::Method(m) => {
+                        self.out.push_str(" (method ");
+                        self.webidl_type_ref(m.ty);
+                        self.out.push(')');
+                    }
+                    WebidlFunctionKind::Constructor => {
+                        self.out.push_str(" (constructor default-new-target)");
+                    }
+                }
+                self.out.push_str(" (param");
+                for ty in &f.params {
+                    self.out.push(' ');
+                    self.webidl_type_ref(*ty);
+                }
+                self.out.push(')');
+                if let Some(ty) = f.result {
+                    self.out.push_str(" (result ");
+                    self.webidl_type_ref(ty);
+                    self.out.push(')');
+                }
+                self.out.push(')');
+            }
+            WebidlCompoundType::Dictionary(d) => {
+                self.out.push_str("(dict");
+                for field in &d.fields {
+                    write!(self.out, " (field {} ", quoted(&field.name)).unwrap();
+                    self.webidl_type_ref(field.ty);
+                    self.out.push(')');
+                }
+                self.out.push(')');
+            }
+            WebidlCompoundType::Enumeration(e) => {
+                self.out.push_str("(enum");
+                for value in &e.values {
+                    write!(self.out, " {}", quoted(value)).unwrap();
+                }
+                self.out.push(')');
+            }
+            WebidlCompoundType::Union(u) => {
+                self.out.push_str("(union");
+                for ty in &u.members {
+                    self.out.push(' ');
+                    self.webidl_type_ref(*ty);
+                }
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn outgoing(&mut self, expr: &OutgoingBindingExpression) {
+        self.out.push_str(" (");
+        match expr {
+            OutgoingBindingExpression::As(e) => {
+                self.out.push_str("as ");
+                self.webidl_type_ref(e.ty);
+                write!(self.out, " {}", e.idx).unwrap();
+            }
+            OutgoingBindingExpression::Utf8Str(e) => {
+                self.out.push_str("utf8-str ");
+                self.webidl_type_ref(e.ty);
+                write!(self.out, " {} {}", e.offset, e.length).unwrap();
+            }
+            OutgoingBindingExpression::Utf8CStr(e) => {
+                self.out.push_str("utf8-cstr ");
+                self.webidl_type_ref(e.ty);
+                write!(self.out, " {}", e.offset).unwrap();
+            }
+            OutgoingBindingExpression::I32ToEnum(e) => {
+                self.out.push_str("i32-to-enum ");
+                self.webidl_type_ref(e.ty);
+                write!(self.out, " {}", e.idx).unwrap();
+            }
+            OutgoingBindingExpression::View(e) => {
+                self.out.push_str("view ");
+                self.webidl_type_ref(e.ty);
+                write!(self.out, " {} {}", e.offset, e.length).unwrap();
+            }
+            OutgoingBindingExpression::Copy(e) => {
+                self.out.push_str("copy ");
+                self.webidl_type_ref(e.ty);
+                write!(self.out, " {} {}", e.offset, e.length).unwrap();
+            }
+            OutgoingBindingExpression::Dict(e) => {
+                self.out.push_str("dict ");
+                self.webidl_type_ref(e.ty);
+                for field in &e.fields {
+                    self.outgoing(field);
+                }
+            }
+            OutgoingBindingExpression::BindExport(e) => {
+                self.out.push_str("bind-export ");
+                self.webidl_type_ref(e.ty);
+                self.out.push(' ');
+                self.binding_ref(e.binding);
+                write!(self.out, " {}", e.idx).unwrap();
+            }
+            OutgoingBindingExpression::Custom(e) => self.custom(e.opcode, &e.payload),
+        }
+        self.out.push(')');
+    }
+
+    fn incoming(&mut self, expr: &IncomingBindingExpression) -> Result<(), failure::Error> {
+        self.out.push_str(" (");
+        match expr {
+            IncomingBindingExpression::Get(e) => {
+                write!(self.out, "get {}", e.idx).unwrap();
+            }
+            IncomingBindingExpression::As(e) => {
+                let ty = match e.ty {
+                    walrus::ValType::I32 => "i32",
+                    walrus::ValType::I64 => "i64",
+                    walrus::ValType::F32 => "f32",
+                    walrus::ValType::F64 => "f64",
+                    walrus::ValType::V128 => "v128",
+                    walrus::ValType::Anyref => "anyref",
+                };
+                write!(self.out, "as {}", ty).unwrap();
+                self.incoming(&e.expr)?;
+            }
+            IncomingBindingExpression::AllocUtf8Str(e) => {
+                write!(self.out, "alloc-utf8-str {}", e.alloc_func_name).unwrap();
+                self.incoming(&e.expr)?;
+            }
+            IncomingBindingExpression::AllocCopy(e) => {
+                write!(self.out, "alloc-copy {}", e.alloc_func_name).unwrap();
+                self.incoming(&e.expr)?;
+            }
+            IncomingBindingExpression::EnumToI32(e) => {
+                self.out.push_str("enum-to-i32 ");
+                self.webidl_type_ref(e.ty);
+                self.incoming(&e.expr)?;
+            }
+            IncomingBindingExpression::Field(e) => {
+                write!(self.out, "field {}", e.idx).unwrap();
+                self.incoming(&e.expr)?;
+            }
+            IncomingBindingExpression::BindImport(e) => {
+                self.out.push_str("bind-import ");
+                self.wasm_func_type_ref(e.ty)?;
+                self.binding_ref(e.binding);
+                self.incoming(&e.expr)?;
+            }
+            IncomingBindingExpression::Custom(e) => self.custom(e.opcode, &e.payload),
+        }
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn custom(&mut self, opcode: u8, payload: &[u8]) {
+        write!(self.out, "custom {}", opcode).unwrap();
+        for b in payload {
+            write!(self.out, " {}", b).unwrap();
+        }
+    }
+
+    fn webidl_type_ref(&mut self, ty: WebidlTypeRef) {
+        let id = match ty {
+            WebidlTypeRef::Id(id) => id,
+            WebidlTypeRef::Scalar(s) => {
+                self.out.push_str(scalar_type_name(s));
+                return;
+            }
+        };
+        match self.type_names.get(&id) {
+            Some(name) => self.out.push_str(name),
+            None => write!(self.out, "{}", self.type_indices[&id]).unwrap(),
+        }
+    }
+
+    fn binding_ref(&mut self, id: Id<FunctionBinding>) {
+        match self.binding_names.get(&id) {
+            Some(name) => self.out.push_str(name),
+            None => write!(self.out, "{}", self.binding_indices[&id]).unwrap(),
+        }
+    }
+
+    /// Print a reference to a Wasm function type, followed by a space.
+    fn wasm_func_type_ref(&mut self, id: walrus::TypeId) -> Result<(), failure::Error> {
+        match self.func_type_indices.get(&id) {
+            Some(idx) => write!(self.out, "{} ", idx).unwrap(),
+            None => failure::bail!("Wasm function type is not in the original Wasm binary"),
+        }
+        Ok(())
+    }
+
+    /// Print a reference to a Wasm function, followed by a space.
+    fn wasm_func_ref(&mut self, id: walrus::FunctionId) -> Result<(), failure::Error> {
+        let import = self.module.imports.iter().find(|i| match i.kind {
+            walrus::ImportKind::Function(f) => f == id,
+            _ => false,
+        });
+        let export = self.module.exports.iter().find(|e| match e.item {
+            walrus::ExportItem::Function(f) => f == id,
+            _ => false,
+        });
+        if let Some(import) = import {
+            write!(
+                self.out,
+                "(import {} {}) ",
+                quoted(&import.module),
+                quoted(&import.name)
+            )
+            .unwrap();
+        } else if let Some(export) = export {
+            write!(self.out, "(export {}) ", quoted(&export.name)).unwrap();
+        } else if let Some(idx) = self.func_indices.get(&id) {
+            write!(self.out, "{} ", idx).unwrap();
+        } else {
+            failure::bail!("Wasm function is not in the original Wasm binary");
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn scalar_type_name(s: WebidlScalarType) -> &'static str {
+    match s {
+        WebidlScalarType::Any => "any",
+        WebidlScalarType::Boolean => "boolean",
+        WebidlScalarType::Byte => "byte",
+        WebidlScalarType::Octet => "octet",
+        WebidlScalarType::Long => "long",
+        WebidlScalarType::UnsignedLong => "unsigned long",
+        WebidlScalarType::Short => "short",
+        WebidlScalarType::UnsignedShort => "unsigned short",
+        WebidlScalarType::LongLong => "long long",
+        WebidlScalarType::UnsignedLongLong => "unsigned long long",
+        WebidlScalarType::Float => "float",
+        WebidlScalarType::UnrestrictedFloat => "unrestricted float",
+        WebidlScalarType::Double => "double",
+        WebidlScalarType::UnrestrictedDouble => "unrestricted double",
+        WebidlScalarType::DomString => "DOMString",
+        WebidlScalarType::ByteString => "ByteString",
+        WebidlScalarType::UsvString => "USVString",
+        WebidlScalarType::Object => "object",
+        WebidlScalarType::Symbol => "symbol",
+        WebidlScalarType::ArrayBuffer => "ArrayBuffer",
+        WebidlScalarType::DataView => "DataView",
+        WebidlScalarType::Int8Array => "Int8Array",
+        WebidlScalarType::Int16Array => "Int16Array",
+        WebidlScalarType::Int32Array => "Int32Array",
+        WebidlScalarType::Uint8Array => "Uint8Array",
+        WebidlScalarType::Uint16Array => "Uint16Array",
+        WebidlScalarType::Uint32Array => "Uint32Array",
+        WebidlScalarType::Uint8ClampedArray => "Uint8ClampedArray",
+        WebidlScalarType::Float32Array => "Float32Array",
+        WebidlScalarType::Float64Array => "Float64Array",
+    }
+}
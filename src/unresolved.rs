@@ -0,0 +1,1082 @@
+//! An AST for Web IDL bindings whose references to the Wasm module haven't
+//! been resolved yet.
+//!
+//! `text::parse` needs the `walrus::Module` that the bindings are for, so that
+//! it can turn references to Wasm functions and function types into walrus
+//! IDs. `text::parse_unresolved` doesn't: it keeps those references as they
+//! were written in the text, so that bindings can be parsed and checked before
+//! the Wasm module exists. Once it does, `WebidlBindings::resolve` turns the
+//! unresolved AST into an `ast::WebidlBindings`.
+//!
+//! References to Web IDL types and function bindings don't depend on the Wasm
+//! module, and are resolved while parsing, just like with `text::parse`.
+
+use crate::ast::{self, WebidlScalarType, WebidlTypeRef};
+use crate::text;
+use id_arena::Id;
+
+/// A reference to a Wasm function type, by name or by index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WasmRef {
+    Named(String),
+    Indexed(u32),
+}
+
+/// A reference to a Wasm function, by name, by index, or by the name that it
+/// is imported or exported as.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WasmFuncRef {
+    Named(String),
+    Indexed(u32),
+    Import { module: String, name: String },
+    Export(String),
+}
+
+/// Web IDL bindings that refer to Wasm functions and function types by name or
+/// by index, rather than by walrus ID.
+#[derive(Debug, Default)]
+pub struct WebidlBindings {
+    pub types: ast::WebidlTypes,
+    pub bindings: Vec<FunctionBinding>,
+    pub binds: Vec<Bind>,
+    pub extensions: ast::Extensions,
+}
+
+impl WebidlBindings {
+    /// Get the index of the last function binding with the given name.
+    pub fn binding_by_name(&self, name: &str) -> Option<u32> {
+        self.bindings
+            .iter()
+            .rposition(|b| b.name() == Some(name))
+            .map(|i| i as u32)
+    }
+
+    /// Resolve every reference to a Wasm function or function type against the
+    /// given module, producing the resolved AST.
+    ///
+    /// If any reference doesn't resolve, then an error listing all of the
+    /// references that didn't resolve is returned.
+    pub fn resolve(
+        self,
+        module: &walrus::Module,
+        indices_to_ids: &walrus::IndicesToIds,
+    ) -> Result<ast::WebidlBindings, failure::Error> {
+        let mut resolver = Resolver {
+            module,
+            ids: indices_to_ids,
+            section: ast::WebidlBindings {
+                types: self.types,
+                extensions: self.extensions,
+                ..ast::WebidlBindings::default()
+            },
+            bindings: vec![],
+            errors: vec![],
+        };
+        for binding in self.bindings {
+            let id = resolver.function_binding(binding);
+            resolver.bindings.push(id);
+        }
+        for bind in self.binds {
+            resolver.bind(bind);
+        }
+
+        if resolver.errors.is_empty() {
+            Ok(resolver.section)
+        } else {
+            Err(failure::err_msg(resolver.errors.join("\n")))
+        }
+    }
+}
+
+/// A function binding. Function bindings refer to each other by their index in
+/// `WebidlBindings::bindings`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FunctionBinding {
+    Import(ImportBinding),
+    Export(ExportBinding),
+}
+
+impl FunctionBinding {
+    /// Get this binding's name, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            FunctionBinding::Import(b) => b.name.as_deref(),
+            FunctionBinding::Export(b) => b.name.as_deref(),
+        }
+    }
+}
+
+impl From<ImportBinding> for FunctionBinding {
+    fn from(a: ImportBinding) -> Self {
+        FunctionBinding::Import(a)
+    }
+}
+
+impl From<ExportBinding> for FunctionBinding {
+    fn from(a: ExportBinding) -> Self {
+        FunctionBinding::Export(a)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportBinding {
+    pub name: Option<String>,
+    pub wasm_ty: WasmRef,
+    pub webidl_ty: WebidlTypeRef,
+    pub params: OutgoingBindingMap,
+    pub result: IncomingBindingMap,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportBinding {
+    pub name: Option<String>,
+    pub wasm_ty: WasmRef,
+    pub webidl_ty: WebidlTypeRef,
+    pub params: IncomingBindingMap,
+    pub result: OutgoingBindingMap,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bind {
+    pub func: WasmFuncRef,
+    pub binding: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingMap {
+    pub bindings: Vec<OutgoingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingMap {
+    pub bindings: Vec<IncomingBindingExpression>,
+}
+
+/// An outgoing binding expression. The expressions that don't contain any
+/// references to function bindings are the same as in the resolved AST.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutgoingBindingExpression {
+    As(ast::OutgoingBindingExpressionAs),
+    Utf8Str(ast::OutgoingBindingExpressionUtf8Str),
+    Utf8CStr(ast::OutgoingBindingExpressionUtf8CStr),
+    I32ToEnum(ast::OutgoingBindingExpressionI32ToEnum),
+    View(ast::OutgoingBindingExpressionView),
+    Copy(ast::OutgoingBindingExpressionCopy),
+    Dict(OutgoingBindingExpressionDict),
+    BindExport(OutgoingBindingExpressionBindExport),
+    Custom(ast::OutgoingBindingExpressionCustom),
+}
+
+impl From<ast::OutgoingBindingExpressionAs> for OutgoingBindingExpression {
+    fn from(a: ast::OutgoingBindingExpressionAs) -> Self {
+        OutgoingBindingExpression::As(a)
+    }
+}
+
+impl From<ast::OutgoingBindingExpressionUtf8Str> for OutgoingBindingExpression {
+    fn from(s: ast::OutgoingBindingExpressionUtf8Str) -> Self {
+        OutgoingBindingExpression::Utf8Str(s)
+    }
+}
+
+impl From<ast::OutgoingBindingExpressionUtf8CStr> for OutgoingBindingExpression {
+    fn from(s: ast::OutgoingBindingExpressionUtf8CStr) -> Self {
+        OutgoingBindingExpression::Utf8CStr(s)
+    }
+}
+
+impl From<ast::OutgoingBindingExpressionI32ToEnum> for OutgoingBindingExpression {
+    fn from(s: ast::OutgoingBindingExpressionI32ToEnum) -> Self {
+        OutgoingBindingExpression::I32ToEnum(s)
+    }
+}
+
+impl From<ast::OutgoingBindingExpressionView> for OutgoingBindingExpression {
+    fn from(s: ast::OutgoingBindingExpressionView) -> Self {
+        OutgoingBindingExpression::View(s)
+    }
+}
+
+impl From<ast::OutgoingBindingExpressionCopy> for OutgoingBindingExpression {
+    fn from(s: ast::OutgoingBindingExpressionCopy) -> Self {
+        OutgoingBindingExpression::Copy(s)
+    }
+}
+
+impl From<OutgoingBindingExpressionDict> for OutgoingBindingExpression {
+    fn from(s: OutgoingBindingExpressionDict) -> Self {
+        OutgoingBindingExpression::Dict(s)
+    }
+}
+
+impl From<OutgoingBindingExpressionBindExport> for OutgoingBindingExpression {
+    fn from(s: OutgoingBindingExpressionBindExport) -> Self {
+        OutgoingBindingExpression::BindExport(s)
+    }
+}
+
+impl From<ast::OutgoingBindingExpressionCustom> for OutgoingBindingExpression {
+    fn from(s: ast::OutgoingBindingExpressionCustom) -> Self {
+        OutgoingBindingExpression::Custom(s)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionDict {
+    pub ty: WebidlTypeRef,
+    pub fields: Vec<OutgoingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutgoingBindingExpressionBindExport {
+    pub ty: WebidlTypeRef,
+    pub binding: u32,
+    pub idx: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IncomingBindingExpression {
+    Get(ast::IncomingBindingExpressionGet),
+    As(IncomingBindingExpressionAs),
+    AllocUtf8Str(IncomingBindingExpressionAllocUtf8Str),
+    AllocCopy(IncomingBindingExpressionAllocCopy),
+    EnumToI32(IncomingBindingExpressionEnumToI32),
+    Field(IncomingBindingExpressionField),
+    BindImport(IncomingBindingExpressionBindImport),
+    Custom(ast::IncomingBindingExpressionCustom),
+}
+
+impl From<ast::IncomingBindingExpressionGet> for IncomingBindingExpression {
+    fn from(a: ast::IncomingBindingExpressionGet) -> Self {
+        IncomingBindingExpression::Get(a)
+    }
+}
+
+impl From<IncomingBindingExpressionAs> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionAs) -> Self {
+        IncomingBindingExpression::As(a)
+    }
+}
+
+impl From<IncomingBindingExpressionAllocUtf8Str> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionAllocUtf8Str) -> Self {
+        IncomingBindingExpression::AllocUtf8Str(a)
+    }
+}
+
+impl From<IncomingBindingExpressionAllocCopy> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionAllocCopy) -> Self {
+        IncomingBindingExpression::AllocCopy(a)
+    }
+}
+
+impl From<IncomingBindingExpressionEnumToI32> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionEnumToI32) -> Self {
+        IncomingBindingExpression::EnumToI32(a)
+    }
+}
+
+impl From<IncomingBindingExpressionField> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionField) -> Self {
+        IncomingBindingExpression::Field(a)
+    }
+}
+
+impl From<IncomingBindingExpressionBindImport> for IncomingBindingExpression {
+    fn from(a: IncomingBindingExpressionBindImport) -> Self {
+        IncomingBindingExpression::BindImport(a)
+    }
+}
+
+impl From<ast::IncomingBindingExpressionCustom> for IncomingBindingExpression {
+    fn from(a: ast::IncomingBindingExpressionCustom) -> Self {
+        IncomingBindingExpression::Custom(a)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionAs {
+    pub ty: walrus::ValType,
+    pub expr: Box<IncomingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionAllocUtf8Str {
+    pub alloc_func_name: String,
+    pub expr: Box<IncomingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionAllocCopy {
+    pub alloc_func_name: String,
+    pub expr: Box<IncomingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionEnumToI32 {
+    pub ty: WebidlTypeRef,
+    pub expr: Box<IncomingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionField {
+    pub idx: u32,
+    pub expr: Box<IncomingBindingExpression>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingBindingExpressionBindImport {
+    pub ty: WasmRef,
+    pub binding: u32,
+    pub expr: Box<IncomingBindingExpression>,
+}
+
+/// Turns the unresolved AST into a resolved AST, collecting an error for each
+/// Wasm reference that doesn't resolve.
+struct Resolver<'a> {
+    module: &'a walrus::Module,
+    ids: &'a walrus::IndicesToIds,
+    section: ast::WebidlBindings,
+    /// The resolved ID of each function binding, or `None` if it referenced
+    /// something that didn't resolve.
+    bindings: Vec<Option<Id<ast::FunctionBinding>>>,
+    errors: Vec<String>,
+}
+
+impl Resolver<'_> {
+    fn wasm_ty(&mut self, ty: &WasmRef) -> Option<walrus::TypeId> {
+        let id = match ty {
+            WasmRef::Named(name) => self
+                .module
+                .types
+                .by_name(name)
+                .ok_or_else(|| format!("unknown Wasm function type name: '{}'", name)),
+            WasmRef::Indexed(idx) => self
+                .ids
+                .get_type(*idx)
+                .map_err(|_| format!("unknown Wasm function type index: {}", idx)),
+        };
+        id.map_err(|e| self.errors.push(e)).ok()
+    }
+
+    fn wasm_func(&mut self, func: &WasmFuncRef) -> Option<walrus::FunctionId> {
+        let id = match func {
+            WasmFuncRef::Named(name) => self
+                .module
+                .funcs
+                .by_name(name)
+                .ok_or_else(|| format!("unknown Wasm function name: '{}'", name)),
+            WasmFuncRef::Indexed(idx) => self
+                .ids
+                .get_func(*idx)
+                .map_err(|_| format!("unknown Wasm function index: {}", idx)),
+            WasmFuncRef::Import { module, name } => ast::imported_func(self.module, module, name),
+            WasmFuncRef::Export(name) => ast::exported_func(self.module, name),
+        };
+        id.map_err(|e| self.errors.push(e)).ok()
+    }
+
+    /// Get the resolved ID of the function binding at the given index.
+    ///
+    /// A binding that didn't resolve has already had its errors reported, so
+    /// references to it don't report any more.
+    fn binding(&mut self, idx: u32) -> Option<Id<ast::FunctionBinding>> {
+        match self.bindings.get(idx as usize) {
+            Some(id) => *id,
+            None => {
+                self.errors
+                    .push(format!("unknown function binding index: {}", idx));
+                None
+            }
+        }
+    }
+
+    fn function_binding(&mut self, binding: FunctionBinding) -> Option<Id<ast::FunctionBinding>> {
+        let (name, id) = match binding {
+            FunctionBinding::Import(b) => {
+                let wasm_ty = self.wasm_ty(&b.wasm_ty);
+                let params = self.outgoing_binding_map(b.params);
+                let result = self.incoming_binding_map(b.result);
+                let binding = ast::ImportBinding {
+                    wasm_ty: wasm_ty?,
+                    webidl_ty: b.webidl_ty,
+                    params: params?,
+                    result: result?,
+                };
+                let id: ast::ImportBindingId = self.section.bindings.insert(binding);
+                (b.name, id.into())
+            }
+            FunctionBinding::Export(b) => {
+                let wasm_ty = self.wasm_ty(&b.wasm_ty);
+                let params = self.incoming_binding_map(b.params);
+                let result = self.outgoing_binding_map(b.result);
+                let binding = ast::ExportBinding {
+                    wasm_ty: wasm_ty?,
+                    webidl_ty: b.webidl_ty,
+                    params: params?,
+                    result: result?,
+                };
+                let id: ast::ExportBindingId = self.section.bindings.insert(binding);
+                (b.name, id.into())
+            }
+        };
+        if let Some(name) = name {
+            self.section.bindings.names.insert(name, id);
+        }
+        Some(id)
+    }
+
+    fn bind(&mut self, bind: Bind) {
+        let func = self.wasm_func(&bind.func);
+        let binding = self.binding(bind.binding);
+        if let (Some(func), Some(binding)) = (func, binding) {
+            self.section.binds.insert(ast::Bind { func, binding });
+        }
+    }
+
+    fn outgoing_binding_map(&mut self, map: OutgoingBindingMap) -> Option<ast::OutgoingBindingMap> {
+        let bindings = self.outgoing_binding_expressions(map.bindings)?;
+        Some(ast::OutgoingBindingMap { bindings })
+    }
+
+    fn incoming_binding_map(&mut self, map: IncomingBindingMap) -> Option<ast::IncomingBindingMap> {
+        // Resolve every expression before giving up, so that all of their
+        // errors are reported.
+        let bindings: Vec<_> = map
+            .bindings
+            .into_iter()
+            .map(|e| self.incoming_binding_expression(e))
+            .collect();
+        let bindings = bindings.into_iter().collect::<Option<_>>()?;
+        Some(ast::IncomingBindingMap { bindings })
+    }
+
+    fn outgoing_binding_expressions(
+        &mut self,
+        exprs: Vec<OutgoingBindingExpression>,
+    ) -> Option<Vec<ast::OutgoingBindingExpression>> {
+        let exprs: Vec<_> = exprs
+            .into_iter()
+            .map(|e| self.outgoing_binding_expression(e))
+            .collect();
+        exprs.into_iter().collect()
+    }
+
+    fn outgoing_binding_expression(
+        &mut self,
+        expr: OutgoingBindingExpression,
+    ) -> Option<ast::OutgoingBindingExpression> {
+        Some(match expr {
+            OutgoingBindingExpression::As(e) => e.into(),
+            OutgoingBindingExpression::Utf8Str(e) => e.into(),
+            OutgoingBindingExpression::Utf8CStr(e) => e.into(),
+            OutgoingBindingExpression::I32ToEnum(e) => e.into(),
+            OutgoingBindingExpression::View(e) => e.into(),
+            OutgoingBindingExpression::Copy(e) => e.into(),
+            OutgoingBindingExpression::Dict(e) => {
+                let fields = self.outgoing_binding_expressions(e.fields)?;
+                ast::OutgoingBindingExpressionDict { ty: e.ty, fields }.into()
+            }
+            OutgoingBindingExpression::BindExport(e) => {
+                let binding = self.binding(e.binding)?;
+                ast::OutgoingBindingExpressionBindExport {
+                    ty: e.ty,
+                    binding,
+                    idx: e.idx,
+                }
+                .into()
+            }
+            OutgoingBindingExpression::Custom(e) => e.into(),
+        })
+    }
+
+    fn incoming_binding_expression(
+        &mut self,
+        expr: IncomingBindingExpression,
+    ) -> Option<ast::IncomingBindingExpression> {
+        Some(match expr {
+            IncomingBindingExpression::Get(e) => e.into(),
+            IncomingBindingExpression::As(e) => {
+                let expr = Box::new(self.incoming_binding_expression(*e.expr)?);
+                ast::IncomingBindingExpressionAs { ty: e.ty, expr }.into()
+            }
+            IncomingBindingExpression::AllocUtf8Str(e) => {
+                let expr = Box::new(self.incoming_binding_expression(*e.expr)?);
+                ast::IncomingBindingExpressionAllocUtf8Str {
+                    alloc_func_name: e.alloc_func_name,
+                    expr,
+                }
+                .into()
+            }
+            IncomingBindingExpression::AllocCopy(e) => {
+                let expr = Box::new(self.incoming_binding_expression(*e.expr)?);
+                ast::IncomingBindingExpressionAllocCopy {
+                    alloc_func_name: e.alloc_func_name,
+                    expr,
+                }
+                .into()
+            }
+            IncomingBindingExpression::EnumToI32(e) => {
+                let expr = Box::new(self.incoming_binding_expression(*e.expr)?);
+                ast::IncomingBindingExpressionEnumToI32 { ty: e.ty, expr }.into()
+            }
+            IncomingBindingExpression::Field(e) => {
+                let expr = Box::new(self.incoming_binding_expression(*e.expr)?);
+                ast::IncomingBindingExpressionField { idx: e.idx, expr }.into()
+            }
+            IncomingBindingExpression::BindImport(e) => {
+                let ty = self.wasm_ty(&e.ty);
+                let binding = self.binding(e.binding);
+                let expr = self.incoming_binding_expression(*e.expr);
+                ast::IncomingBindingExpressionBindImport {
+                    ty: ty?,
+                    binding: binding?,
+                    expr: Box::new(expr?),
+                }
+                .into()
+            }
+            IncomingBindingExpression::Custom(e) => e.into(),
+        })
+    }
+}
+
+/// Text format parser actions that build an unresolved AST.
+#[derive(Debug)]
+pub struct BuildUnresolvedAstActions<'a> {
+    section: &'a mut WebidlBindings,
+}
+
+impl<'a> BuildUnresolvedAstActions<'a> {
+    pub fn new(section: &'a mut WebidlBindings) -> Self {
+        BuildUnresolvedAstActions { section }
+    }
+
+    fn push_binding(&mut self, binding: FunctionBinding) -> u32 {
+        self.section.bindings.push(binding);
+        self.section.bindings.len() as u32 - 1
+    }
+}
+
+impl<'a> text::Actions for BuildUnresolvedAstActions<'a> {
+    type WebidlBindingsSection = ();
+    fn webidl_bindings_section(&mut self, _types: (), _bindings: ()) {}
+
+    type WebidlTypeSubsection = ();
+    fn webidl_type_subsection(&mut self, _types: Vec<()>) {}
+
+    type WebidlType = ();
+    fn webidl_type(
+        &mut self,
+        name: Option<&str>,
+        ty: Id<ast::WebidlCompoundType>,
+    ) -> Result<(), String> {
+        if let Some(name) = name {
+            self.section
+                .types
+                .set_name(name.to_string(), ty)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn webidl_type_alias(&mut self, name: &str, ty: WebidlTypeRef) -> Result<(), String> {
+        self.section
+            .types
+            .alias(name.to_string(), ty)
+            .map_err(|e| e.to_string())
+    }
+
+    type WebidlCompoundType = Id<ast::WebidlCompoundType>;
+
+    type WebidlFunction = ast::WebidlFunctionId;
+    fn webidl_function(
+        &mut self,
+        kind: Option<ast::WebidlFunctionKind>,
+        params: Option<Vec<WebidlTypeRef>>,
+        result: Option<WebidlTypeRef>,
+    ) -> ast::WebidlFunctionId {
+        let kind = kind.unwrap_or(ast::WebidlFunctionKind::Static);
+        let params = params.unwrap_or(vec![]);
+        self.section.types.insert(ast::WebidlFunction {
+            kind,
+            params,
+            result,
+        })
+    }
+
+    type WebidlFunctionKind = ast::WebidlFunctionKind;
+
+    type WebidlFunctionKindMethod = ast::WebidlFunctionKindMethod;
+    fn webidl_function_kind_method(&mut self, ty: WebidlTypeRef) -> ast::WebidlFunctionKindMethod {
+        ast::WebidlFunctionKindMethod { ty }
+    }
+
+    type WebidlFunctionKindConstructor = ast::WebidlFunctionKind;
+    fn webidl_function_kind_constructor_default_new_target(&mut self) -> ast::WebidlFunctionKind {
+        ast::WebidlFunctionKind::Constructor
+    }
+
+    type WebidlFunctionParams = Vec<WebidlTypeRef>;
+    fn webidl_function_params(&mut self, tys: Vec<WebidlTypeRef>) -> Vec<WebidlTypeRef> {
+        tys
+    }
+
+    type WebidlFunctionResult = WebidlTypeRef;
+    fn webidl_function_result(&mut self, ty: WebidlTypeRef) -> WebidlTypeRef {
+        ty
+    }
+
+    type WebidlDictionary = ast::WebidlDictionaryId;
+    fn webidl_dictionary(
+        &mut self,
+        fields: Vec<ast::WebidlDictionaryField>,
+    ) -> ast::WebidlDictionaryId {
+        self.section.types.insert(ast::WebidlDictionary { fields })
+    }
+
+    type WebidlDictionaryField = ast::WebidlDictionaryField;
+    fn webidl_dictionary_field(
+        &mut self,
+        name: String,
+        ty: WebidlTypeRef,
+    ) -> ast::WebidlDictionaryField {
+        ast::WebidlDictionaryField { name, ty }
+    }
+
+    type WebidlDictionaryFieldName = String;
+    fn webidl_dictionary_field_name(&mut self, name: &str) -> String {
+        name.into()
+    }
+
+    type WebidlEnumeration = ast::WebidlEnumerationId;
+    fn webidl_enumeration(&mut self, values: Vec<String>) -> ast::WebidlEnumerationId {
+        self.section.types.insert(ast::WebidlEnumeration { values })
+    }
+
+    type WebidlEnumerationValue = String;
+    fn webidl_enumeration_value(&mut self, value: &str) -> String {
+        value.into()
+    }
+
+    type WebidlUnion = ast::WebidlUnionId;
+    fn webidl_union(&mut self, members: Vec<WebidlTypeRef>) -> ast::WebidlUnionId {
+        self.section.types.insert(ast::WebidlUnion { members })
+    }
+
+    type WebidlFunctionBindingsSubsection = ();
+    fn webidl_function_bindings_subsection(&mut self, _bindings: Vec<u32>, _binds: Vec<()>) {}
+
+    type FunctionBinding = u32;
+
+    type ImportBinding = u32;
+    fn import_binding(
+        &mut self,
+        name: Option<&str>,
+        wasm_ty: WasmRef,
+        webidl_ty: WebidlTypeRef,
+        params: OutgoingBindingMap,
+        result: IncomingBindingMap,
+    ) -> u32 {
+        self.push_binding(
+            ImportBinding {
+                name: name.map(|s| s.to_string()),
+                wasm_ty,
+                webidl_ty,
+                params,
+                result,
+            }
+            .into(),
+        )
+    }
+
+    type ExportBinding = u32;
+    fn export_binding(
+        &mut self,
+        name: Option<&str>,
+        wasm_ty: WasmRef,
+        webidl_ty: WebidlTypeRef,
+        params: IncomingBindingMap,
+        result: OutgoingBindingMap,
+    ) -> u32 {
+        self.push_binding(
+            ExportBinding {
+                name: name.map(|s| s.to_string()),
+                wasm_ty,
+                webidl_ty,
+                params,
+                result,
+            }
+            .into(),
+        )
+    }
+
+    type Bind = ();
+    fn bind(&mut self, func: WasmFuncRef, binding: u32) {
+        self.section.binds.push(Bind { func, binding });
+    }
+
+    type OutgoingBindingMap = OutgoingBindingMap;
+    fn outgoing_binding_map(
+        &mut self,
+        bindings: Vec<OutgoingBindingExpression>,
+    ) -> OutgoingBindingMap {
+        OutgoingBindingMap { bindings }
+    }
+
+    type IncomingBindingMap = IncomingBindingMap;
+    fn incoming_binding_map(
+        &mut self,
+        bindings: Vec<IncomingBindingExpression>,
+    ) -> IncomingBindingMap {
+        IncomingBindingMap { bindings }
+    }
+
+    type OutgoingBindingExpression = OutgoingBindingExpression;
+
+    type OutgoingBindingExpressionAs = ast::OutgoingBindingExpressionAs;
+    fn outgoing_binding_expression_as(
+        &mut self,
+        ty: WebidlTypeRef,
+        idx: u32,
+    ) -> ast::OutgoingBindingExpressionAs {
+        ast::OutgoingBindingExpressionAs { ty, idx }
+    }
+
+    type OutgoingBindingExpressionUtf8Str = ast::OutgoingBindingExpressionUtf8Str;
+    fn outgoing_binding_expression_utf8_str(
+        &mut self,
+        ty: WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> ast::OutgoingBindingExpressionUtf8Str {
+        ast::OutgoingBindingExpressionUtf8Str { ty, offset, length }
+    }
+
+    type OutgoingBindingExpressionUtf8CStr = ast::OutgoingBindingExpressionUtf8CStr;
+    fn outgoing_binding_expression_utf8_c_str(
+        &mut self,
+        ty: WebidlTypeRef,
+        offset: u32,
+    ) -> ast::OutgoingBindingExpressionUtf8CStr {
+        ast::OutgoingBindingExpressionUtf8CStr { ty, offset }
+    }
+
+    type OutgoingBindingExpressionI32ToEnum = ast::OutgoingBindingExpressionI32ToEnum;
+    fn outgoing_binding_expression_i32_to_enum(
+        &mut self,
+        ty: WebidlTypeRef,
+        idx: u32,
+    ) -> ast::OutgoingBindingExpressionI32ToEnum {
+        ast::OutgoingBindingExpressionI32ToEnum { ty, idx }
+    }
+
+    type OutgoingBindingExpressionView = ast::OutgoingBindingExpressionView;
+    fn outgoing_binding_expression_view(
+        &mut self,
+        ty: WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> ast::OutgoingBindingExpressionView {
+        ast::OutgoingBindingExpressionView { ty, offset, length }
+    }
+
+    type OutgoingBindingExpressionCopy = ast::OutgoingBindingExpressionCopy;
+    fn outgoing_binding_expression_copy(
+        &mut self,
+        ty: WebidlTypeRef,
+        offset: u32,
+        length: u32,
+    ) -> ast::OutgoingBindingExpressionCopy {
+        ast::OutgoingBindingExpressionCopy { ty, offset, length }
+    }
+
+    type OutgoingBindingExpressionDict = OutgoingBindingExpressionDict;
+    fn outgoing_binding_expression_dict(
+        &mut self,
+        ty: WebidlTypeRef,
+        fields: Vec<OutgoingBindingExpression>,
+    ) -> OutgoingBindingExpressionDict {
+        OutgoingBindingExpressionDict { ty, fields }
+    }
+
+    type OutgoingBindingExpressionBindExport = OutgoingBindingExpressionBindExport;
+    fn outgoing_binding_expression_bind_export(
+        &mut self,
+        ty: WebidlTypeRef,
+        binding: u32,
+        idx: u32,
+    ) -> OutgoingBindingExpressionBindExport {
+        OutgoingBindingExpressionBindExport { ty, binding, idx }
+    }
+
+    type OutgoingBindingExpressionCustom = ast::OutgoingBindingExpressionCustom;
+    fn outgoing_binding_expression_custom(
+        &mut self,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> ast::OutgoingBindingExpressionCustom {
+        ast::OutgoingBindingExpressionCustom { opcode, payload }
+    }
+
+    type IncomingBindingExpression = IncomingBindingExpression;
+
+    type IncomingBindingExpressionGet = ast::IncomingBindingExpressionGet;
+    fn incoming_binding_expression_get(&mut self, idx: u32) -> ast::IncomingBindingExpressionGet {
+        ast::IncomingBindingExpressionGet { idx }
+    }
+
+    type IncomingBindingExpressionAs = IncomingBindingExpressionAs;
+    fn incoming_binding_expression_as(
+        &mut self,
+        ty: walrus::ValType,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionAs {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionAs { ty, expr }
+    }
+
+    type IncomingBindingExpressionAllocUtf8Str = IncomingBindingExpressionAllocUtf8Str;
+    fn incoming_binding_expression_alloc_utf8_str(
+        &mut self,
+        alloc_func_name: &str,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionAllocUtf8Str {
+        let alloc_func_name = alloc_func_name.into();
+        let expr = Box::new(expr);
+        IncomingBindingExpressionAllocUtf8Str {
+            alloc_func_name,
+            expr,
+        }
+    }
+
+    type IncomingBindingExpressionAllocCopy = IncomingBindingExpressionAllocCopy;
+    fn incoming_binding_expression_alloc_copy(
+        &mut self,
+        alloc_func_name: &str,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionAllocCopy {
+        let alloc_func_name = alloc_func_name.into();
+        let expr = Box::new(expr);
+        IncomingBindingExpressionAllocCopy {
+            alloc_func_name,
+            expr,
+        }
+    }
+
+    type IncomingBindingExpressionEnumToI32 = IncomingBindingExpressionEnumToI32;
+    fn incoming_binding_expression_enum_to_i32(
+        &mut self,
+        ty: WebidlTypeRef,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionEnumToI32 {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionEnumToI32 { ty, expr }
+    }
+
+    type IncomingBindingExpressionField = IncomingBindingExpressionField;
+    fn incoming_binding_expression_field(
+        &mut self,
+        idx: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionField {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionField { idx, expr }
+    }
+
+    type IncomingBindingExpressionBindImport = IncomingBindingExpressionBindImport;
+    fn incoming_binding_expression_bind_import(
+        &mut self,
+        ty: WasmRef,
+        binding: u32,
+        expr: IncomingBindingExpression,
+    ) -> IncomingBindingExpressionBindImport {
+        let expr = Box::new(expr);
+        IncomingBindingExpressionBindImport { ty, binding, expr }
+    }
+
+    type IncomingBindingExpressionCustom = ast::IncomingBindingExpressionCustom;
+    fn incoming_binding_expression_custom(
+        &mut self,
+        opcode: u8,
+        payload: Vec<u8>,
+    ) -> ast::IncomingBindingExpressionCustom {
+        ast::IncomingBindingExpressionCustom { opcode, payload }
+    }
+
+    type WebidlTypeRef = WebidlTypeRef;
+
+    type WebidlTypeRefNamed = WebidlTypeRef;
+    fn webidl_type_ref_named(&mut self, name: &str) -> Option<WebidlTypeRef> {
+        self.section.types.resolve(name)
+    }
+
+    type WebidlTypeRefIndexed = WebidlTypeRef;
+    fn webidl_type_ref_indexed(&mut self, idx: u32) -> Option<WebidlTypeRef> {
+        self.section.types.by_index(idx).map(Into::into)
+    }
+
+    type WebidlScalarType = WebidlScalarType;
+    fn webidl_scalar_type_any(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Any
+    }
+    fn webidl_scalar_type_boolean(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Boolean
+    }
+    fn webidl_scalar_type_byte(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Byte
+    }
+    fn webidl_scalar_type_octet(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Octet
+    }
+    fn webidl_scalar_type_long(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Long
+    }
+    fn webidl_scalar_type_unsigned_long(&mut self) -> WebidlScalarType {
+        WebidlScalarType::UnsignedLong
+    }
+    fn webidl_scalar_type_short(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Short
+    }
+    fn webidl_scalar_type_unsigned_short(&mut self) -> WebidlScalarType {
+        WebidlScalarType::UnsignedShort
+    }
+    fn webidl_scalar_type_long_long(&mut self) -> WebidlScalarType {
+        WebidlScalarType::LongLong
+    }
+    fn webidl_scalar_type_unsigned_long_long(&mut self) -> WebidlScalarType {
+        WebidlScalarType::UnsignedLongLong
+    }
+    fn webidl_scalar_type_float(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Float
+    }
+    fn webidl_scalar_type_unrestricted_float(&mut self) -> WebidlScalarType {
+        WebidlScalarType::UnrestrictedFloat
+    }
+    fn webidl_scalar_type_double(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Double
+    }
+    fn webidl_scalar_type_unrestricted_double(&mut self) -> WebidlScalarType {
+        WebidlScalarType::UnrestrictedDouble
+    }
+    fn webidl_scalar_type_dom_string(&mut self) -> WebidlScalarType {
+        WebidlScalarType::DomString
+    }
+    fn webidl_scalar_type_byte_string(&mut self) -> WebidlScalarType {
+        WebidlScalarType::ByteString
+    }
+    fn webidl_scalar_type_usv_string(&mut self) -> WebidlScalarType {
+        WebidlScalarType::UsvString
+    }
+    fn webidl_scalar_type_object(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Object
+    }
+    fn webidl_scalar_type_symbol(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Symbol
+    }
+    fn webidl_scalar_type_array_buffer(&mut self) -> WebidlScalarType {
+        WebidlScalarType::ArrayBuffer
+    }
+    fn webidl_scalar_type_data_view(&mut self) -> WebidlScalarType {
+        WebidlScalarType::DataView
+    }
+    fn webidl_scalar_type_int8_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Int8Array
+    }
+    fn webidl_scalar_type_int16_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Int16Array
+    }
+    fn webidl_scalar_type_int32_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Int32Array
+    }
+    fn webidl_scalar_type_uint8_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Uint8Array
+    }
+    fn webidl_scalar_type_uint16_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Uint16Array
+    }
+    fn webidl_scalar_type_uint32_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Uint32Array
+    }
+    fn webidl_scalar_type_uint8_clamped_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Uint8ClampedArray
+    }
+    fn webidl_scalar_type_float32_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Float32Array
+    }
+    fn webidl_scalar_type_float64_array(&mut self) -> WebidlScalarType {
+        WebidlScalarType::Float64Array
+    }
+
+    type WasmValType = walrus::ValType;
+    fn wasm_val_type_i32(&mut self) -> walrus::ValType {
+        walrus::ValType::I32
+    }
+    fn wasm_val_type_i64(&mut self) -> walrus::ValType {
+        walrus::ValType::I64
+    }
+    fn wasm_val_type_f32(&mut self) -> walrus::ValType {
+        walrus::ValType::F32
+    }
+    fn wasm_val_type_f64(&mut self) -> walrus::ValType {
+        walrus::ValType::F64
+    }
+    fn wasm_val_type_v128(&mut self) -> walrus::ValType {
+        walrus::ValType::V128
+    }
+    fn wasm_val_type_anyref(&mut self) -> walrus::ValType {
+        walrus::ValType::Anyref
+    }
+
+    type WasmFuncTypeRef = WasmRef;
+
+    type WasmFuncTypeRefNamed = WasmRef;
+    fn wasm_func_type_ref_named(&mut self, name: &str) -> Option<WasmRef> {
+        Some(WasmRef::Named(name.to_string()))
+    }
+
+    type WasmFuncTypeRefIndexed = WasmRef;
+    fn wasm_func_type_ref_indexed(&mut self, idx: u32) -> Option<WasmRef> {
+        Some(WasmRef::Indexed(idx))
+    }
+
+    type WasmFuncRef = WasmFuncRef;
+
+    type WasmFuncRefNamed = WasmFuncRef;
+    fn wasm_func_ref_named(&mut self, name: &str) -> Option<WasmFuncRef> {
+        Some(WasmFuncRef::Named(name.to_string()))
+    }
+
+    type WasmFuncRefIndexed = WasmFuncRef;
+    fn wasm_func_ref_indexed(&mut self, idx: u32) -> Option<WasmFuncRef> {
+        Some(WasmFuncRef::Indexed(idx))
+    }
+
+    type WasmFuncRefImport = WasmFuncRef;
+    fn wasm_func_ref_import(&mut self, module: &str, name: &str) -> Result<WasmFuncRef, String> {
+        Ok(WasmFuncRef::Import {
+            module: module.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    type WasmFuncRefExport = WasmFuncRef;
+    fn wasm_func_ref_export(&mut self, name: &str) -> Result<WasmFuncRef, String> {
+        Ok(WasmFuncRef::Export(name.to_string()))
+    }
+
+    type BindingRef = u32;
+
+    type BindingRefNamed = u32;
+    fn binding_ref_named(&mut self, name: &str) -> Option<u32> {
+        self.section.binding_by_name(name)
+    }
+
+    type BindingRefIndexed = u32;
+    fn binding_ref_indexed(&mut self, idx: u32) -> Option<u32> {
+        if (idx as usize) < self.section.bindings.len() {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,230 @@
+#![cfg(feature = "serde")]
+
+use wasm_webidl_bindings::ast::SubsectionPosition;
+use wasm_webidl_bindings::{interchange, text};
+
+const INPUT: &str = r#"
+    type $Contact (dict (field "name" DOMString) (field "age" long))
+    type $Color (enum "red" "green")
+    type $Either (union $Contact $Color)
+    type $Bytes = Uint8Array
+    type $F (func (method any) (param $Either $Bytes) (result USVString))
+    type $G (func (param long))
+    func-binding $e export 0 $G (param) (result)
+    func-binding $b import 0 $F
+        (param
+            (utf8-str DOMString 0 1)
+            (utf8-cstr DOMString 2)
+            (i32-to-enum $Color 0)
+            (view Uint8Array 0 1)
+            (copy Uint8Array 0 1)
+            (dict $Contact (as long 0))
+            (bind-export $G $e 0)
+            (custom 128 1 2 3))
+        (result
+            (custom 200)
+            (as i32
+                (field 0
+                    (enum-to-i32 $Color
+                        (alloc-utf8-str $malloc
+                            (alloc-copy $malloc
+                                (bind-import 0 $e (get 0))))))))
+    bind (import "env" "f") $b
+"#;
+
+/// Call `f` with a module that has one imported function of one type.
+fn with_module<F>(f: F)
+where
+    F: Fn(&mut walrus::Module, &walrus::IndicesToIds) + Send + Sync + 'static,
+{
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[walrus::ValType::I32], &[]);
+    module.add_import_func("env", "f", ty);
+    let wasm = module.emit_wasm().unwrap();
+
+    let mut config = walrus::ModuleConfig::default();
+    config.on_parse(move |module, indices_to_ids| {
+        f(module, indices_to_ids);
+        Ok(())
+    });
+    config.parse(&wasm).unwrap();
+}
+
+#[test]
+fn json_round_trip() {
+    with_module(|module, indices_to_ids| {
+        let section = text::parse(module, indices_to_ids, INPUT).unwrap();
+        let json = interchange::to_json(&section, indices_to_ids).unwrap();
+        let round_tripped = interchange::from_json(indices_to_ids, &json).unwrap();
+
+        assert_eq!(
+            text::print(&round_tripped, module, indices_to_ids).unwrap(),
+            text::print(&section, module, indices_to_ids).unwrap(),
+        );
+        assert_eq!(
+            interchange::to_json(&round_tripped, indices_to_ids).unwrap(),
+            json
+        );
+    });
+}
+
+#[test]
+fn json_format() {
+    with_module(|module, indices_to_ids| {
+        let section = text::parse(
+            module,
+            indices_to_ids,
+            r#"
+            type $U (union long)
+            type $T (func (param $U USVString))
+            type $L = long
+            func-binding $b import 0 $T (param (as $U 0) (as USVString 1)) (result)
+            bind (import "env" "f") $b
+            "#,
+        )
+        .unwrap();
+        let json = interchange::to_json(&section, indices_to_ids).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "types": [
+                    { "type": "union", "members": ["long"] },
+                    {
+                        "type": "function",
+                        "kind": { "kind": "static" },
+                        "params": [0, "USVString"],
+                        "result": null,
+                    },
+                ],
+                "type_names": { "$T": 1, "$U": 0 },
+                "type_aliases": { "$L": "long" },
+                "bindings": [
+                    {
+                        "kind": "import",
+                        "wasm_ty": 0,
+                        "webidl_ty": 1,
+                        "params": {
+                            "bindings": [
+                                { "kind": "as", "ty": 0, "idx": 0 },
+                                { "kind": "as", "ty": "USVString", "idx": 1 },
+                            ],
+                        },
+                        "result": { "bindings": [] },
+                    },
+                ],
+                "binding_names": { "$b": 0 },
+                "binds": [{ "func": 0, "binding": 0 }],
+            })
+        );
+    });
+}
+
+#[test]
+fn json_keeps_extensions() {
+    with_module(|_module, indices_to_ids| {
+        let json = r#"{
+            "types": [],
+            "bindings": [],
+            "binds": [],
+            "extensions": {
+                "features": 131072,
+                "subsections": [
+                    { "id": 7, "data": [1, 2, 3] },
+                    { "id": 8, "data": [], "position": "after-types" }
+                ]
+            }
+        }"#;
+        let section = interchange::from_json(indices_to_ids, json).unwrap();
+        assert_eq!(section.extensions.features, 0x20000);
+        assert_eq!(section.extensions.subsections[0].data, [1, 2, 3]);
+        assert_eq!(
+            section.extensions.subsections[0].position,
+            SubsectionPosition::AfterNames
+        );
+        assert_eq!(
+            section.extensions.subsections[1].position,
+            SubsectionPosition::AfterTypes
+        );
+
+        let json = interchange::to_json(&section, indices_to_ids).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json["extensions"],
+            serde_json::json!({
+                "features": 131072,
+                "subsections": [
+                    { "id": 7, "data": [1, 2, 3], "position": "after-names" },
+                    { "id": 8, "data": [], "position": "after-types" },
+                ],
+            })
+        );
+    });
+}
+
+#[test]
+fn deserializing_checks_references() {
+    let section = |types: &str, bindings: &str, binds: &str| {
+        format!(
+            r#"{{ "types": [{}], "bindings": [{}], "binds": [{}] }}"#,
+            types, bindings, binds
+        )
+    };
+    let errors = vec![
+        (
+            section(r#"{ "type": "union", "members": [1] }"#, "", ""),
+            "unknown Web IDL type index 1",
+        ),
+        (
+            section(r#"{ "type": "union", "members": ["float128"] }"#, "", ""),
+            "unknown Web IDL scalar type `float128`",
+        ),
+        (
+            section("", "", r#"{ "func": 0, "binding": 0 }"#),
+            "unknown function binding index 0",
+        ),
+        (
+            section(
+                "",
+                r#"{ "kind": "export", "wasm_ty": 1, "webidl_ty": "any",
+                     "params": { "bindings": [] }, "result": { "bindings": [] } }"#,
+                "",
+            ),
+            "unknown Wasm function type index 1",
+        ),
+    ];
+
+    with_module(move |_module, indices_to_ids| {
+        for (json, expected) in &errors {
+            let error = interchange::from_json(indices_to_ids, json)
+                .unwrap_err()
+                .to_string();
+            assert!(error.starts_with(expected), "{}: {}", json, error);
+        }
+
+        // Wasm function indices are checked against the module.
+        let json = r#"{ "types": [], "bindings": [], "binds": [{ "func": 1, "binding": 0 }] }"#;
+        let error = interchange::from_json(indices_to_ids, json)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("unknown Wasm function index 1"),
+            "{}",
+            error
+        );
+    });
+}
+
+#[test]
+fn wasm_references_need_a_module() {
+    with_module(|module, indices_to_ids| {
+        let section = text::parse(module, indices_to_ids, INPUT).unwrap();
+        let error = serde_json::to_string(&section).unwrap_err().to_string();
+        assert!(error.contains("interchange::with_module"), "{}", error);
+
+        let json = interchange::to_json(&section, indices_to_ids).unwrap();
+        let section: wasm_webidl_bindings::ast::WebidlBindings =
+            interchange::with_module(indices_to_ids, || serde_json::from_str(&json)).unwrap();
+        assert_eq!(section.binds.iter().count(), 1);
+    });
+}
@@ -1 +1,1465 @@
+use wasm_webidl_bindings::ast::*;
+use wasm_webidl_bindings::spans::*;
+use wasm_webidl_bindings::{binary, embed, gc, merge, split, text, unresolved};
 
+fn parse(input: &str) -> Result<WebidlBindings, failure::Error> {
+    let module = walrus::Module::default();
+    let indices_to_ids = walrus::IndicesToIds::default();
+    text::parse(&module, &indices_to_ids, input)
+}
+
+#[test]
+fn type_aliases_resolve_to_their_targets() {
+    let bindings = parse(
+        r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Bytes = Uint8Array
+        type $Person = $Contact
+        type $Human = $Person
+        "#,
+    )
+    .unwrap();
+
+    let contact = bindings.types.by_name("$Contact").unwrap();
+    assert_eq!(bindings.types.resolve("$Contact"), Some(contact.into()));
+    assert_eq!(
+        bindings.types.resolve("$Bytes"),
+        Some(WebidlScalarType::Uint8Array.into())
+    );
+    assert_eq!(bindings.types.resolve("$Person"), Some(contact.into()));
+    assert_eq!(bindings.types.resolve("$Human"), Some(contact.into()));
+
+    // `by_name` follows aliases of compound types, but not of scalar types.
+    assert_eq!(bindings.types.by_name("$Person"), Some(contact));
+    assert_eq!(bindings.types.by_name("$Human"), Some(contact));
+    assert_eq!(bindings.types.by_name("$Bytes"), None);
+
+    let mut aliases: Vec<_> = bindings.types.aliases().map(|(name, _)| name).collect();
+    aliases.sort();
+    assert_eq!(aliases, ["$Bytes", "$Human", "$Person"]);
+}
+
+#[test]
+fn type_alias_can_be_used_in_compound_types() {
+    let bindings = parse(
+        r#"
+        type $Bytes = Uint8Array
+        type $F (func (param $Bytes) (result $Bytes))
+        type $G = $F
+        "#,
+    )
+    .unwrap();
+
+    assert!(bindings.types.by_name("$F").is_some());
+    assert_eq!(bindings.types.by_name("$G"), bindings.types.by_name("$F"));
+    assert_eq!(bindings.types.resolve("$G"), bindings.types.resolve("$F"));
+}
+
+#[test]
+fn type_alias_name_collisions_are_errors() {
+    for input in &[
+        "type $A (dict) type $A = long",
+        "type $A = long type $A = any",
+        "type $A = long type $A (dict)",
+    ] {
+        let error = parse(input).unwrap_err().to_string();
+        assert!(
+            error.contains("Web IDL type name already defined: '$A'"),
+            "{}",
+            error
+        );
+    }
+
+    let mut bindings = parse("type $A (dict) type $B = long").unwrap();
+    assert!(bindings
+        .types
+        .alias("$A".into(), WebidlScalarType::Any)
+        .is_err());
+    assert!(bindings
+        .types
+        .alias("$B".into(), WebidlScalarType::Any)
+        .is_err());
+    assert_eq!(
+        bindings.types.resolve("$B"),
+        Some(WebidlScalarType::Long.into())
+    );
+    assert!(bindings
+        .types
+        .alias("$C".into(), WebidlScalarType::Any)
+        .is_ok());
+
+    let dict = bindings.types.by_name("$A").unwrap();
+    assert!(bindings.types.set_name("$B".into(), dict).is_err());
+    assert_eq!(
+        bindings.types.resolve("$B"),
+        Some(WebidlScalarType::Long.into())
+    );
+    assert!(bindings.types.set_name("$D".into(), dict).is_ok());
+}
+
+#[test]
+fn unknown_alias_target_is_an_error() {
+    assert!(parse("type $Bytes = $Nope").is_err());
+}
+
+#[test]
+fn parse_recovering_reports_all_errors_and_keeps_the_rest() {
+    let input = r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Broken (dict (field "age" $Nope))
+        type $AlsoBroken (func (param $Contact) (result $Nope2))
+        type $Person = $Contact
+        bind 0 $noSuchBinding
+    "#;
+    let module = walrus::Module::default();
+    let indices_to_ids = walrus::IndicesToIds::default();
+    let (bindings, errors) = text::parse_recovering(&module, &indices_to_ids, input);
+
+    let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "unknown Web IDL type name: '$Nope'",
+            "unknown Web IDL type name: '$Nope2'",
+            "unknown Wasm function index: 0",
+        ]
+    );
+    assert_eq!(&input[errors[0].start..errors[0].end], "$Nope");
+    assert_eq!(&input[errors[1].start..errors[1].end], "$Nope2");
+    assert_eq!(&input[errors[2].start..errors[2].end], "0");
+
+    assert!(bindings.types.by_name("$Contact").is_some());
+    assert!(bindings.types.by_name("$Broken").is_none());
+    assert_eq!(
+        bindings.types.resolve("$Person"),
+        bindings.types.resolve("$Contact")
+    );
+}
+
+#[test]
+fn parse_recovering_keeps_the_indices_of_broken_items() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$fnTy".into());
+    let f = module.add_import_func("env", "f", ty);
+    module.funcs.get_mut(f).name = Some("$f".into());
+    let indices_to_ids = walrus::IndicesToIds::default();
+
+    let input = r#"
+        type $Broken (dict (field "age" $Nope))
+        type $Contact (dict (field "name" DOMString))
+        type $Alias = $Nope2
+        type $Same = 1
+        type $Bad = 0
+        type $Tight=$Nope4
+        type $Last (enum "last")
+        type $Other=2
+        func-binding $broken import $fnTy $Nope3 (param) (result)
+        func-binding $b import $fnTy $Contact (param) (result)
+        bind $f 1
+        bind $f 0
+    "#;
+    let (bindings, errors) = text::parse_recovering(&module, &indices_to_ids, input);
+
+    let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "unknown Web IDL type name: '$Nope'",
+            "unknown Web IDL type name: '$Nope2'",
+            "Web IDL type index 0 refers to a `type` that failed to parse",
+            "unknown Web IDL type name: '$Nope4'",
+            "unknown Web IDL type name: '$Nope3'",
+            "function binding index 0 refers to a `func-binding` that failed to parse",
+        ]
+    );
+
+    // Index 1 is still `$Contact`, even though `$Broken` at index 0 is gone.
+    assert!(bindings.types.by_name("$Contact").is_some());
+    assert_eq!(
+        bindings.types.resolve("$Same"),
+        bindings.types.resolve("$Contact")
+    );
+    assert!(bindings.types.resolve("$Bad").is_none());
+    // An alias without spaces around its `=` doesn't take up an index either.
+    assert_eq!(
+        bindings.types.resolve("$Other"),
+        bindings.types.resolve("$Last")
+    );
+
+    // Likewise, function binding index 1 is still `$b`.
+    let b = bindings.bindings.by_name("$b").unwrap();
+    let binds: Vec<_> = bindings
+        .binds
+        .iter()
+        .map(|(_, bind)| bind.binding)
+        .collect();
+    assert_eq!(binds, [b]);
+}
+
+#[test]
+fn parse_with_spans_records_spans() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$fnTy".into());
+    let f = module.add_import_func("env", "f", ty);
+    module.funcs.get_mut(f).name = Some("$f".into());
+    let indices_to_ids = walrus::IndicesToIds::default();
+
+    let input = r#"
+        type $Contact (dict (field "name" DOMString))
+        func-binding $b import $fnTy $Contact
+            (param (dict $Contact (as any 0)) (as long 1))
+            (result (as i32 (field 0 (get 0))))
+        bind $f $b
+    "#;
+    let (bindings, spans) = text::parse_with_spans(&module, &indices_to_ids, input).unwrap();
+    let text = |span: Option<Span>| {
+        let span = span.unwrap();
+        &input[span.start..span.end]
+    };
+
+    let contact = bindings.types.by_name("$Contact").unwrap();
+    assert_eq!(
+        text(spans.webidl_type(contact)),
+        r#"(dict (field "name" DOMString))"#
+    );
+
+    let b = bindings.bindings.by_name("$b").unwrap();
+    assert!(text(spans.binding(b)).starts_with("func-binding $b import"));
+    assert!(text(spans.binding(b)).ends_with("(get 0))))"));
+
+    let (bind, _) = bindings.binds.iter().next().unwrap();
+    assert_eq!(text(spans.bind(bind)), "bind $f $b");
+
+    let expr = |map, path: &[u32]| {
+        text(spans.expression(&ExpressionId {
+            binding: b,
+            map,
+            path: path.to_vec(),
+        }))
+    };
+    assert_eq!(
+        expr(BindingMapKind::Params, &[0]),
+        "(dict $Contact (as any 0))"
+    );
+    assert_eq!(expr(BindingMapKind::Params, &[0, 0]), "(as any 0)");
+    assert_eq!(expr(BindingMapKind::Params, &[1]), "(as long 1)");
+    assert_eq!(
+        expr(BindingMapKind::Result, &[0]),
+        "(as i32 (field 0 (get 0)))"
+    );
+    assert_eq!(expr(BindingMapKind::Result, &[0, 0]), "(field 0 (get 0))");
+    assert_eq!(expr(BindingMapKind::Result, &[0, 0, 0]), "(get 0)");
+}
+
+#[test]
+fn render_snippet_points_at_the_span() {
+    let input = "type $A (dict)\ntype $B (dict (field \"a\" $Nope))\n";
+    let module = walrus::Module::default();
+    let indices_to_ids = walrus::IndicesToIds::default();
+    let (_, errors) = text::parse_recovering(&module, &indices_to_ids, input);
+    let span = Span {
+        start: errors[0].start,
+        end: errors[0].end,
+    };
+    assert_eq!(
+        render_snippet(input, span, &errors[0].message),
+        "error: unknown Web IDL type name: '$Nope'\n \
+         --> 2:26\n  \
+         |\n\
+         2 | type $B (dict (field \"a\" $Nope))\n  \
+         |                          ^^^^^"
+    );
+}
+
+#[test]
+fn render_snippet_clamps_offsets_to_char_boundaries() {
+    // `é` and `ü` are two bytes each, so byte offsets 8 and 25 are in the
+    // middle of them.
+    let input = "type $Aé (dict (field \"ü\" long))\n";
+    let render = |start, end| render_snippet(input, Span { start, end }, "oops");
+    assert_eq!(
+        render(8, 8),
+        "error: oops\n \
+         --> 1:8\n  \
+         |\n\
+         1 | type $Aé (dict (field \"ü\" long))\n  \
+         |        ^"
+    );
+    assert_eq!(render(5, 8), render(5, 7));
+    assert!(render(5, 8).ends_with("|      ^^"));
+    assert_eq!(render(25, 30), render(24, 30));
+
+    // Offsets past the end of the input point just after its last character.
+    assert_eq!(render(100, 200), render(input.len(), input.len()));
+    assert!(render(100, 200).contains(" --> 2:1\n"));
+}
+
+const UNRESOLVED_INPUT: &str = r#"
+    type $Contact (dict (field "name" DOMString))
+    func-binding $b import $fnTy $Contact
+        (param (as any 0))
+        (result (as i32 (get 0)))
+    func-binding export 0 $Contact
+        (param (bind-import $fnTy $b (get 0)))
+        (result (bind-export any $b 0))
+    bind $f $b
+    bind 0 1
+"#;
+
+#[test]
+fn parse_unresolved_keeps_wasm_references_symbolic() {
+    let bindings = text::parse_unresolved(UNRESOLVED_INPUT).unwrap();
+
+    assert!(bindings.types.by_name("$Contact").is_some());
+    assert_eq!(bindings.binding_by_name("$b"), Some(0));
+    match &bindings.bindings[0] {
+        unresolved::FunctionBinding::Import(b) => {
+            assert_eq!(b.wasm_ty, unresolved::WasmRef::Named("$fnTy".into()));
+        }
+        b => panic!("expected an import binding, found {:?}", b),
+    }
+    assert_eq!(
+        bindings.binds,
+        vec![
+            unresolved::Bind {
+                func: unresolved::WasmFuncRef::Named("$f".into()),
+                binding: 0,
+            },
+            unresolved::Bind {
+                func: unresolved::WasmFuncRef::Indexed(0),
+                binding: 1,
+            },
+        ]
+    );
+
+    // Web IDL type and binding references are still checked while parsing.
+    assert!(text::parse_unresolved("bind $f $nope").is_err());
+}
+
+#[test]
+fn resolve_unresolved_ast() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.add_import_func("env", "f", ty);
+    let wasm = module.emit_wasm().unwrap();
+
+    let mut config = walrus::ModuleConfig::default();
+    config.on_parse(|module, indices_to_ids| {
+        let ty = indices_to_ids.get_type(0)?;
+        module.types.get_mut(ty).name = Some("$fnTy".into());
+        let f = indices_to_ids.get_func(0)?;
+        module.funcs.get_mut(f).name = Some("$f".into());
+
+        let bindings = text::parse_unresolved(UNRESOLVED_INPUT)?.resolve(module, indices_to_ids)?;
+
+        let b = bindings.bindings.by_name("$b").unwrap();
+        assert_eq!(bindings.bindings.by_index(0), Some(b));
+        let binds: Vec<_> = bindings
+            .binds
+            .iter()
+            .map(|(_, bind)| bind.clone())
+            .collect();
+        assert_eq!(
+            binds,
+            vec![
+                Bind {
+                    func: f,
+                    binding: b
+                },
+                Bind {
+                    func: f,
+                    binding: bindings.bindings.by_index(1).unwrap(),
+                },
+            ]
+        );
+        Ok(())
+    });
+    config.parse(&wasm).unwrap();
+}
+
+#[test]
+fn resolve_reports_every_unresolved_reference() {
+    let module = walrus::Module::default();
+    let indices_to_ids = walrus::IndicesToIds::default();
+    let error = text::parse_unresolved(UNRESOLVED_INPUT)
+        .unwrap()
+        .resolve(&module, &indices_to_ids)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unknown Wasm function type name: '$fnTy'\n\
+         unknown Wasm function type index: 0\n\
+         unknown Wasm function type name: '$fnTy'\n\
+         unknown Wasm function name: '$f'\n\
+         unknown Wasm function index: 0"
+    );
+}
+
+#[test]
+fn print_escapes_quoted_names() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.add_import_func("en\"v", "f\\\"", ty);
+    let bindings = r#"
+        type $Contact (dict (field "say \"hi\"" DOMString))
+        type $Path (enum "C:\\" "\"")
+        func-binding $b import 0 $Contact (param) (result)
+        bind (import "en\"v" "f\\\"") $b
+    "#;
+
+    let wasm = embed::embed(&module.emit_wasm().unwrap(), bindings).unwrap();
+    let printed = embed::extract(&wasm).unwrap().unwrap();
+    for quoted in &[
+        r#"(field "say \"hi\"" DOMString)"#,
+        r#"(enum "C:\\" "\"")"#,
+        r#"(import "en\"v" "f\\\"")"#,
+    ] {
+        assert!(printed.contains(quoted), "{}", printed);
+    }
+
+    let wasm = embed::embed(&wasm, &printed).unwrap();
+    assert_eq!(embed::extract(&wasm).unwrap().unwrap(), printed);
+}
+
+#[test]
+fn bind_by_import_and_export_name() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$ty".into());
+    let encode_into = module.add_import_func("TextEncoder", "encodeInto", ty);
+    let greet = module.add_import_func("env", "greet", ty);
+    module.exports.add("greet", greet);
+    let memory = module.add_import_memory("env", "memory", false, 1, None);
+    module.exports.add("memory", memory);
+    let indices_to_ids = walrus::IndicesToIds::default();
+
+    let parse = |bind: &str| {
+        let input = format!("func-binding $b import $ty any (param) (result)\n{}", bind);
+        text::parse(&module, &indices_to_ids, &input)
+    };
+    let bound_func = |bind: &str| {
+        let bindings = parse(bind).unwrap();
+        let (_, bind) = bindings.binds.iter().next().unwrap();
+        bind.func
+    };
+
+    assert_eq!(
+        bound_func(r#"bind (import "TextEncoder" "encodeInto") $b"#),
+        encode_into
+    );
+    assert_eq!(bound_func(r#"bind (export "greet") $b"#), greet);
+
+    let error = |bind: &str| parse(bind).unwrap_err().to_string();
+    assert_eq!(
+        error(r#"bind (import "TextEncoder" "nope") $b"#),
+        r#"unknown Wasm import: "TextEncoder" "nope""#
+    );
+    assert_eq!(
+        error(r#"bind (import "env" "memory") $b"#),
+        r#"Wasm import "env" "memory" is not a function"#
+    );
+    assert_eq!(
+        error(r#"bind (export "nope") $b"#),
+        r#"unknown Wasm export: "nope""#
+    );
+    assert_eq!(
+        error(r#"bind (export "memory") $b"#),
+        r#"Wasm export "memory" is not a function"#
+    );
+
+    let unresolved = text::parse_unresolved(
+        r#"
+        func-binding $b import $ty any (param) (result)
+        bind (import "TextEncoder" "encodeInto") $b
+        bind (export "memory") $b
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        unresolved.binds[0].func,
+        unresolved::WasmFuncRef::Import {
+            module: "TextEncoder".into(),
+            name: "encodeInto".into(),
+        }
+    );
+    assert_eq!(
+        unresolved
+            .resolve(&module, &indices_to_ids)
+            .unwrap_err()
+            .to_string(),
+        r#"Wasm export "memory" is not a function"#
+    );
+}
+
+#[test]
+fn parse_wat_attaches_annotated_bindings() {
+    let module = text::parse_wat(
+        r#"
+        (module
+          (type $t (func (param i32)))
+          (import "env" "f" (func $f (type $t)))
+          (func $g (export "g") (type $t))
+          (@webidl type $Contact (dict (field "name" DOMString)))
+          (@webidl
+            func-binding $b import $t $Contact (param (as any 0)) (result)
+            func-binding $c export 0 $Contact (param (get 0)) (result))
+          (@webidl bind $f $b) ;; (@webidl bind $g $c)
+          (@webidl bind (export "g") $c))
+        "#,
+    )
+    .unwrap();
+
+    assert!(module
+        .customs
+        .iter()
+        .any(|(_, c)| c.name() == "webidl-bindings"));
+    module.emit_wasm().unwrap();
+
+    // The bindings don't change the debug names of the functions.
+    let f = module.funcs.by_name("f").unwrap();
+    assert_eq!(module.funcs.get(f).name.as_deref(), Some("f"));
+}
+
+#[test]
+fn parse_wat_reports_binding_errors_against_the_wat() {
+    let error =
+        text::parse_wat("(module\n  (type $t (func))\n  (@webidl bind $nope 0))").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "error: unknown Wasm function name: '$nope'\n \
+         --> 3:17\n  \
+         |\n\
+         3 |   (@webidl bind $nope 0))\n  \
+         |                 ^^^^^"
+    );
+}
+
+#[test]
+fn check_reports_dangling_references() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$ty".into());
+    let f = module.add_import_func("env", "f", ty);
+    let indices_to_ids = walrus::IndicesToIds::default();
+    let mut section = text::parse(
+        &module,
+        &indices_to_ids,
+        r#"
+        type $T (func (param long))
+        func-binding $b import $ty $T (param (as long 0)) (result)
+        bind (import "env" "f") $b
+        "#,
+    )
+    .unwrap();
+    binary::check(&section, &module).unwrap();
+
+    // A type that refers to a type from a different section.
+    let mut other = WebidlBindings::default();
+    let foreign = other.types.insert(WebidlEnumeration { values: vec![] });
+    let dict = section.types.insert(WebidlDictionary {
+        fields: vec![WebidlDictionaryField {
+            name: "a".into(),
+            ty: foreign.into(),
+        }],
+    });
+    section.types.set_name("$Dict".into(), dict).unwrap();
+
+    // The bound function is deleted from the module.
+    let import = module.imports.iter().next().unwrap().id();
+    module.imports.delete(import);
+    module.funcs.delete(f);
+
+    let errors = binary::check(&section, &module).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Web IDL type 1 ($Dict) refers to a Web IDL type that isn't in the section",
+            "bind 0 refers to a Wasm function that isn't in the module",
+        ]
+    );
+    let (bind, _) = section.binds.iter().next().unwrap();
+    assert_eq!(
+        errors[1].item,
+        binary::EncodeErrorItem::Bind { index: 0, id: bind }
+    );
+    assert_eq!(errors[1].dangling, binary::DanglingRef::WasmFunction(f));
+
+    // Encoding finds the dangling Web IDL type on its own, but needs the module
+    // to find the deleted function.
+    let mut bytes = vec![];
+    let error = binary::encode(&section, &walrus::IdsToIndices::default(), &mut bytes).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    let error = error
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<binary::EncodeError>();
+    assert_eq!(error, Some(&errors[0]));
+    assert!(bytes.is_empty());
+}
+
+#[test]
+fn check_errors_render_with_spans() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$ty".into());
+    let f = module.add_import_func("env", "f", ty);
+    let indices_to_ids = walrus::IndicesToIds::default();
+    let input = "func-binding $b import $ty any (param) (result)\n\
+                 bind (import \"env\" \"f\") $b\n";
+    let (section, spans) = text::parse_with_spans(&module, &indices_to_ids, input).unwrap();
+
+    let import = module.imports.iter().next().unwrap().id();
+    module.imports.delete(import);
+    module.funcs.delete(f);
+
+    let errors = binary::check(&section, &module).unwrap_err();
+    assert_eq!(
+        errors[0].render(input, &spans),
+        "error: bind 0 refers to a Wasm function that isn't in the module\n \
+         --> 2:1\n  \
+         |\n\
+         2 | bind (import \"env\" \"f\") $b\n  \
+         | ^^^^^^^^^^^^^^^^^^^^^^^^^^"
+    );
+
+    // Without a span for the item, only the message is rendered.
+    let no_spans = Spans::default();
+    assert_eq!(
+        errors[0].render(input, &no_spans),
+        "error: bind 0 refers to a Wasm function that isn't in the module"
+    );
+}
+
+fn module_with_bound_import() -> walrus::Module {
+    let mut module = walrus::Module::default();
+    let f_ty = module.types.add(&[walrus::ValType::I32], &[]);
+    let binding_ty = module.types.add(&[], &[walrus::ValType::I64]);
+    module.types.get_mut(binding_ty).name = Some("$t".into());
+    module.add_import_func("env", "f", f_ty);
+    module.add_import_func("env", "unused", f_ty);
+    let section = text::parse(
+        &module,
+        &walrus::IndicesToIds::default(),
+        r#"
+        func-binding $b import $t long (param) (result)
+        bind (import "env" "f") $b
+        "#,
+    )
+    .unwrap();
+    module.customs.add(section);
+    module
+}
+
+#[test]
+fn gc_keeps_what_bindings_refer_to() {
+    // walrus' own pass deletes the bound function and the binding's type.
+    let mut module = module_with_bound_import();
+    walrus::passes::gc::run(&mut module);
+    let section = module.customs.get_typed::<WebidlBindings>().unwrap();
+    let errors = binary::check(section, &module).unwrap_err();
+    assert_eq!(errors.len(), 2);
+
+    let mut module = module_with_bound_import();
+    gc::run(&mut module);
+    let section = module.customs.get_typed::<WebidlBindings>().unwrap();
+    binary::check(section, &module).unwrap();
+    assert_eq!(module.imports.iter().count(), 1);
+    assert!(module.exports.iter().next().is_none());
+    assert_eq!(gc::roots(section).types.len(), 1);
+
+    let wasm = module.emit_wasm().unwrap();
+    assert_eq!(
+        embed::extract(&wasm).unwrap().unwrap(),
+        "func-binding $b import 1 long\n    (param)\n    (result)\n\n\
+         bind\n    (import \"env\" \"f\")\n    $b\n"
+    );
+}
+
+#[test]
+fn gc_keeps_exports_with_the_names_of_its_roots() {
+    let mut module = module_with_bound_import();
+    let unused = module
+        .imports
+        .iter()
+        .find(|i| i.name == "unused")
+        .map(|i| match i.kind {
+            walrus::ImportKind::Function(f) => f,
+            _ => unreachable!(),
+        })
+        .unwrap();
+    module.exports.add("__webidl_bindings_gc_root0", unused);
+    gc::run(&mut module);
+
+    let section = module.customs.get_typed::<WebidlBindings>().unwrap();
+    binary::check(section, &module).unwrap();
+    assert_eq!(module.imports.iter().count(), 2);
+    let exports: Vec<_> = module.exports.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(exports, ["__webidl_bindings_gc_root0"]);
+}
+
+#[test]
+fn remap_moves_a_section_to_another_module() {
+    let mut old = walrus::Module::default();
+    let old_ty = old.types.add(&[walrus::ValType::I32], &[]);
+    old.types.get_mut(old_ty).name = Some("$t".into());
+    let old_f = old.add_import_func("env", "f", old_ty);
+    let mut section = text::parse(
+        &old,
+        &walrus::IndicesToIds::default(),
+        r#"
+        func-binding $b import $t long (param) (result)
+        func-binding $c export $t long
+            (param (as i32 (bind-import $t $b (get 0))))
+            (result)
+        bind (import "env" "f") $b
+        "#,
+    )
+    .unwrap();
+
+    // The new module has other types and functions before the ones that the
+    // section refers to.
+    let mut new = walrus::Module::default();
+    new.types.add(&[], &[]);
+    let new_ty = new.types.add(&[walrus::ValType::I32], &[]);
+    new.add_import_func("env", "g", new_ty);
+    let new_f = new.add_import_func("env", "f", new_ty);
+
+    section.remap(
+        |f| {
+            assert_eq!(f, old_f);
+            new_f
+        },
+        |ty| {
+            assert_eq!(ty, old_ty);
+            new_ty
+        },
+    );
+    binary::check(&section, &new).unwrap();
+    new.customs.add(section);
+    let wasm = new.emit_wasm().unwrap();
+    assert_eq!(
+        embed::extract(&wasm).unwrap().unwrap(),
+        "func-binding $b import 1 long\n    (param)\n    (result)\n\n\
+         func-binding $c export 1 long\n    \
+         (param (as i32 (bind-import 1 $b (get 0))))\n    (result)\n\n\
+         bind\n    (import \"env\" \"f\")\n    $b\n"
+    );
+}
+
+#[test]
+fn merge_shares_types_and_renames_clashes() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[walrus::ValType::I32], &[]);
+    module.types.get_mut(ty).name = Some("$t".into());
+    module.add_import_func("env", "f", ty);
+    module.add_import_func("env", "g", ty);
+    let parse = |input| text::parse(&module, &walrus::IndicesToIds::default(), input).unwrap();
+    let a = parse(
+        r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Color (enum "red" "green")
+        func-binding $b import $t $Contact
+            (param (as $Contact 0))
+            (result (enum-to-i32 $Color (get 0)))
+        bind (import "env" "f") $b
+        "#,
+    );
+    let b = parse(
+        r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Color (enum "cyan")
+        func-binding $b import $t $Contact
+            (param (as $Contact 0))
+            (result (enum-to-i32 $Color (get 0)))
+        func-binding $c export $t $Contact
+            (param (as i32 (bind-import $t $b (get 0))))
+            (result)
+        bind (import "env" "g") $c
+        "#,
+    );
+
+    let section = merge::merge(vec![a, b]);
+    binary::check(&section, &module).unwrap();
+
+    module.customs.add(section);
+    let wasm = module.emit_wasm().unwrap();
+    assert_eq!(
+        embed::extract(&wasm).unwrap().unwrap(),
+        "type $Contact\n    (dict (field \"name\" DOMString))\n\n\
+         type $Color\n    (enum \"red\" \"green\")\n\n\
+         type $Color_1\n    (enum \"cyan\")\n\n\
+         func-binding $b import 0 $Contact\n    \
+         (param (as $Contact 0))\n    \
+         (result (enum-to-i32 $Color (get 0)))\n\n\
+         func-binding $b_1 import 0 $Contact\n    \
+         (param (as $Contact 0))\n    \
+         (result (enum-to-i32 $Color_1 (get 0)))\n\n\
+         func-binding $c export 0 $Contact\n    \
+         (param (as i32 (bind-import 0 $b_1 (get 0))))\n    (result)\n\n\
+         bind\n    (import \"env\" \"f\")\n    $b\n\n\
+         bind\n    (import \"env\" \"g\")\n    $c\n"
+    );
+}
+
+#[test]
+fn merge_drops_dangling_binds_and_duplicate_subsections() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$t".into());
+    module.add_import_func("env", "f", ty);
+    let parse = |input| text::parse(&module, &walrus::IndicesToIds::default(), input).unwrap();
+    let unknown = |id, data: &[u8]| UnknownSubsection {
+        id,
+        data: data.to_vec(),
+        position: SubsectionPosition::AfterNames,
+    };
+
+    let mut a = parse(
+        r#"
+        func-binding $b import $t long (param) (result)
+        bind (import "env" "f") $b
+        "#,
+    );
+    a.extensions.subsections.push(unknown(7, &[1]));
+
+    // `b` binds a function to a binding that is only in `a`.
+    let mut b = WebidlBindings::default();
+    let (_, bind) = a.binds.iter().next().unwrap();
+    b.binds.insert(bind.clone());
+    b.extensions.subsections.push(unknown(7, &[1]));
+    b.extensions.subsections.push(unknown(7, &[2]));
+
+    let section = merge::merge(vec![a, b]);
+    assert_eq!(section.binds.iter().count(), 1);
+    assert_eq!(
+        section.extensions.subsections,
+        [unknown(7, &[1]), unknown(7, &[2])]
+    );
+}
+
+#[test]
+fn merge_drops_items_that_refer_to_other_sections() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$t".into());
+    let f = module.add_import_func("env", "f", ty);
+    let a = text::parse(
+        &module,
+        &walrus::IndicesToIds::default(),
+        r#"type $Contact (dict (field "name" DOMString))"#,
+    )
+    .unwrap();
+    let contact = a.types.by_name("$Contact").unwrap();
+
+    // Everything in `b` but `$Kept` refers to `a`'s `$Contact`, either directly
+    // or through another item that does.
+    let mut b = WebidlBindings::default();
+    let u: WebidlUnionId = b.types.insert(WebidlUnion {
+        members: vec![contact.into()],
+    });
+    let d: WebidlDictionaryId = b.types.insert(WebidlDictionary {
+        fields: vec![WebidlDictionaryField {
+            name: "u".into(),
+            ty: u.into(),
+        }],
+    });
+    let kept: WebidlEnumerationId = b.types.insert(WebidlEnumeration {
+        values: vec!["x".into()],
+    });
+    b.types.set_name("$U".into(), u).unwrap();
+    b.types.set_name("$D".into(), d).unwrap();
+    b.types.set_name("$Kept".into(), kept).unwrap();
+    b.types.alias("$Alias".into(), contact).unwrap();
+    let binding: ImportBindingId = b.bindings.insert(ImportBinding {
+        wasm_ty: ty,
+        webidl_ty: d.into(),
+        params: OutgoingBindingMap { bindings: vec![] },
+        result: IncomingBindingMap { bindings: vec![] },
+    });
+    b.binds.insert(Bind {
+        func: f,
+        binding: binding.into(),
+    });
+
+    let section = merge::merge(vec![a, b]);
+    binary::check(&section, &module).unwrap();
+    assert!(section.types.by_name("$U").is_none());
+    assert!(section.types.by_name("$D").is_none());
+    assert!(section.types.resolve("$Alias").is_none());
+    assert_eq!(section.binds.iter().count(), 0);
+
+    module.customs.add(section);
+    let wasm = module.emit_wasm().unwrap();
+    assert_eq!(
+        embed::extract(&wasm).unwrap().unwrap(),
+        "type $Contact\n    (dict (field \"name\" DOMString))\n\n\
+         type $Kept\n    (enum \"x\")\n"
+    );
+}
+
+#[test]
+fn split_gives_each_part_what_it_needs() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[walrus::ValType::I32], &[]);
+    module.types.get_mut(ty).name = Some("$t".into());
+    let f = module.add_import_func("env", "f", ty);
+    let g = module.add_import_func("env", "g", ty);
+    let section = text::parse(
+        &module,
+        &walrus::IndicesToIds::default(),
+        r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Color (enum "red" "green")
+        type $Unused (enum "nope")
+        func-binding $b import $t $Contact
+            (param (as $Contact 0))
+            (result (alloc-utf8-str $malloc (get 0)))
+        func-binding $c export $t $Contact
+            (param (enum-to-i32 $Color (get 0)))
+            (result (as $Contact 0))
+        bind (import "env" "f") $b
+        bind (import "env" "g") $c
+        "#,
+    )
+    .unwrap();
+
+    let parts = split::split(
+        &section,
+        &[vec![f].into_iter().collect(), vec![g].into_iter().collect()],
+    );
+    assert_eq!(parts[0].allocators, vec!["$malloc".to_string()]);
+    assert!(parts[1].allocators.is_empty());
+
+    // Each part's module only has the function that is split off into it.
+    let printed: Vec<_> = parts
+        .into_iter()
+        .zip(&["f", "g"])
+        .map(|(mut part, name)| {
+            let mut module = walrus::Module::default();
+            let new_ty = module.types.add(&[walrus::ValType::I32], &[]);
+            let new_f = module.add_import_func("env", name, new_ty);
+            part.section.remap(|_| new_f, |_| new_ty);
+            binary::check(&part.section, &module).unwrap();
+            module.customs.add(part.section);
+            embed::extract(&module.emit_wasm().unwrap())
+                .unwrap()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        printed,
+        vec![
+            "type $Contact\n    (dict (field \"name\" DOMString))\n\n\
+             func-binding $b import 0 $Contact\n    \
+             (param (as $Contact 0))\n    \
+             (result (alloc-utf8-str $malloc (get 0)))\n\n\
+             bind\n    (import \"env\" \"f\")\n    $b\n",
+            "type $Contact\n    (dict (field \"name\" DOMString))\n\n\
+             type $Color\n    (enum \"red\" \"green\")\n\n\
+             func-binding $c export 0 $Contact\n    \
+             (param (enum-to-i32 $Color (get 0)))\n    \
+             (result (as $Contact 0))\n\n\
+             bind\n    (import \"env\" \"g\")\n    $c\n",
+        ]
+    );
+}
+
+#[test]
+fn split_keeps_the_aliases_that_parts_need() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$t".into());
+    let f = module.add_import_func("env", "f", ty);
+    let section = text::parse(
+        &module,
+        &walrus::IndicesToIds::default(),
+        r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Color (enum "red" "green")
+        type $Name = DOMString
+        type $Person = $Contact
+        type $Hue = $Color
+        func-binding $b import $t $Person (param) (result)
+        bind (import "env" "f") $b
+        "#,
+    )
+    .unwrap();
+
+    let part = split::part(&section, &vec![f].into_iter().collect()).section;
+    let contact = part.types.by_name("$Contact").unwrap();
+    assert_eq!(part.types.by_name("$Person"), Some(contact));
+    assert_eq!(
+        part.types.resolve("$Name"),
+        Some(WebidlScalarType::DomString.into())
+    );
+    assert!(part.types.resolve("$Color").is_none());
+    assert!(part.types.resolve("$Hue").is_none());
+}
+
+#[test]
+fn split_drops_dangling_binds() {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[], &[]);
+    module.types.get_mut(ty).name = Some("$t".into());
+    let f = module.add_import_func("env", "f", ty);
+    let other = text::parse(
+        &module,
+        &walrus::IndicesToIds::default(),
+        r#"
+        func-binding $b import $t long (param) (result)
+        bind (import "env" "f") $b
+        "#,
+    )
+    .unwrap();
+
+    // `section` binds `f` to a binding that is only in `other`.
+    let mut section = WebidlBindings::default();
+    let (_, bind) = other.binds.iter().next().unwrap();
+    section.binds.insert(bind.clone());
+
+    let part = split::part(&section, &vec![f].into_iter().collect());
+    assert_eq!(part.section.binds.iter().count(), 0);
+}
+
+fn wasm_with_import() -> Vec<u8> {
+    let mut module = walrus::Module::default();
+    let ty = module.types.add(&[walrus::ValType::I32], &[]);
+    module.add_import_func("env", "f", ty);
+    module.emit_wasm().unwrap()
+}
+
+#[test]
+fn embed_replaces_existing_section() {
+    let bindings = r#"
+        type $T (func (param long))
+        func-binding $b import 0 $T (param (as long 0)) (result)
+        bind (import "env" "f") $b
+    "#;
+    let wasm = embed::embed(&wasm_with_import(), bindings).unwrap();
+    let wasm = embed::embed(&wasm, bindings).unwrap();
+
+    let module = walrus::Module::from_buffer(&wasm).unwrap();
+    let sections = module
+        .customs
+        .iter()
+        .filter(|(_, c)| c.name() == "webidl-bindings")
+        .count();
+    assert_eq!(sections, 1);
+}
+
+#[test]
+fn embed_reports_errors_at_each_stage() {
+    let error = embed::embed(b"not wasm", "").unwrap_err().to_string();
+    assert!(
+        error.starts_with("failed to parse the Wasm module"),
+        "{}",
+        error
+    );
+
+    let error = embed::embed(&wasm_with_import(), "\nbind (import \"env\" \"g\") 0")
+        .unwrap_err()
+        .to_string();
+    assert!(error.starts_with("invalid Web IDL bindings"), "{}", error);
+    assert!(
+        error.contains("unknown Wasm import: \"env\" \"g\""),
+        "{}",
+        error
+    );
+    assert!(error.contains("--> 2:"), "{}", error);
+}
+
+#[test]
+fn extract_round_trips_through_embed() {
+    let bindings = r#"
+        type $Contact (dict (field "name" DOMString) (field "age" long))
+        type $Color (enum "red" "green")
+        type $Either (union $Contact $Color)
+        type $T (func (method any) (param $Either) (result USVString))
+        func-binding $b import 0 $T
+            (param (utf8-str DOMString 0 1) (i32-to-enum $Color 0))
+            (result (as i32 (get 0)))
+        bind (import "env" "f") $b
+    "#;
+    let wasm = embed::embed(&wasm_with_import(), bindings).unwrap();
+    let extracted = embed::extract(&wasm).unwrap().unwrap();
+
+    let wasm = embed::embed(&wasm_with_import(), &extracted).unwrap();
+    assert_eq!(embed::extract(&wasm).unwrap().unwrap(), extracted);
+}
+
+#[test]
+fn extract_and_strip() {
+    assert!(embed::extract(&wasm_with_import()).unwrap().is_none());
+
+    let mut module = walrus::Module::from_buffer(&wasm_with_import()).unwrap();
+    module.customs.add(walrus::RawCustomSection {
+        name: "webidl-bindings".to_string(),
+        data: vec![1, 0, 1, 0, 1, 1],
+    });
+    let wasm = module.emit_wasm().unwrap();
+    let error = embed::extract(&wasm).unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "invalid `webidl-bindings` section: unknown Wasm function index 1 at offset 5"
+    );
+
+    let wasm = embed::strip(&wasm).unwrap();
+    assert!(embed::extract(&wasm).unwrap().is_none());
+}
+
+#[test]
+fn encode_and_decode_without_walrus() {
+    let input = r#"
+        type $Contact (dict (field "name" DOMString) (field "age" long))
+        type $T (func (method any) (param $Contact) (result USVString))
+        func-binding $b import 0 $T
+            (param (dict $Contact (utf8-str DOMString 0 1) (as long 2)))
+            (result (alloc-utf8-str $malloc (get 0)))
+        func-binding export 0 $T
+            (param (bind-import 0 $b (get 0)))
+            (result (bind-export any $b 0))
+        bind 0 $b
+    "#;
+    let section = text::parse_unresolved(input).unwrap();
+    let mut bytes = vec![];
+    binary::encode_unresolved(&section, &mut bytes).unwrap();
+
+    // Web IDL type IDs are from a different arena, so check that decoding
+    // round trips through encoding again.
+    let decoded = binary::decode_unresolved(&bytes).unwrap();
+    assert_eq!(decoded.binds, section.binds);
+    assert_eq!(decoded.bindings.len(), 2);
+    assert_eq!(decoded.bindings[0].name(), Some("$b"));
+    assert_eq!(decoded.bindings[1].name(), None);
+    assert!(decoded.types.by_name("$Contact").is_some());
+
+    let mut reencoded = vec![];
+    binary::encode_unresolved(&decoded, &mut reencoded).unwrap();
+    assert_eq!(reencoded, bytes);
+
+    // The same bytes decode against a Wasm module with walrus.
+    let mut module = walrus::Module::from_buffer(&wasm_with_import()).unwrap();
+    module.customs.add(walrus::RawCustomSection {
+        name: "webidl-bindings".to_string(),
+        data: bytes,
+    });
+    let wasm = module.emit_wasm().unwrap();
+    assert!(embed::extract(&wasm).unwrap().is_some());
+}
+
+#[test]
+fn unknown_subsections_are_preserved() {
+    // A section from a newer producer, with an unknown optional feature, an
+    // unknown subsection between the known ones, and two after them.
+    #[rustfmt::skip]
+    let bytes = vec![
+        1, 0x80, 0x80, 0x0c,
+        0, 1, 2, 1, 1, b'a',
+        8, 1, 0xcc,
+        1, 0, 0,
+        2, 7, 1, 0, 2, b'$', b'E', 0, 0,
+        7, 3, 1, 2, 3,
+        9, 0,
+    ];
+    let section = binary::decode_unresolved(&bytes).unwrap();
+    assert_eq!(
+        section.extensions,
+        Extensions {
+            features: 0x20000,
+            subsections: vec![
+                UnknownSubsection {
+                    id: 8,
+                    data: vec![0xcc],
+                    position: SubsectionPosition::AfterTypes,
+                },
+                UnknownSubsection {
+                    id: 7,
+                    data: vec![1, 2, 3],
+                    position: SubsectionPosition::AfterNames,
+                },
+                UnknownSubsection {
+                    id: 9,
+                    data: vec![],
+                    position: SubsectionPosition::AfterNames,
+                },
+            ],
+        }
+    );
+    let mut reencoded = vec![];
+    binary::encode_unresolved(&section, &mut reencoded).unwrap();
+    assert_eq!(reencoded, bytes);
+
+    // They survive decoding against a Wasm module and emitting it again.
+    let mut module = walrus::Module::from_buffer(&wasm_with_import()).unwrap();
+    module.customs.add(walrus::RawCustomSection {
+        name: "webidl-bindings".to_string(),
+        data: bytes.clone(),
+    });
+    let wasm = module.emit_wasm().unwrap();
+    let mut config = walrus::ModuleConfig::default();
+    config.on_parse(|module, indices_to_ids| {
+        let raw = module.customs.remove_raw("webidl-bindings").unwrap();
+        let section = binary::decode(indices_to_ids, &raw.data)?;
+        module.customs.add(section);
+        Ok(())
+    });
+    let wasm = config.parse(&wasm).unwrap().emit_wasm().unwrap();
+    let mut module = walrus::Module::from_buffer(&wasm).unwrap();
+    let raw = module.customs.remove_raw("webidl-bindings").unwrap();
+    assert_eq!(raw.data, bytes);
+
+    // Unknown subsections can't reuse the id of a known one.
+    let mut section = section;
+    section.extensions.subsections[1].id = 2;
+    let error = binary::encode_unresolved(&section, &mut vec![]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Nor can extensions set a known feature, like the names feature.
+    section.extensions.subsections[1].id = 7;
+    section.extensions.features |= binary::FEATURE_NAMES;
+    let error = binary::encode_unresolved(&section, &mut vec![]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn encode_unresolved_needs_indexed_references() {
+    let section = text::parse_unresolved(
+        r#"
+        func-binding $b import $fnTy long (param) (result)
+        bind 0 $b
+        "#,
+    )
+    .unwrap();
+    let error = binary::encode_unresolved(&section, &mut vec![]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(
+        error.to_string(),
+        "only references to Wasm functions and function types by index can be encoded, \
+         found Named(\"$fnTy\")"
+    );
+
+    let section = text::parse_unresolved(
+        r#"
+        func-binding $b import 0 long (param) (result)
+        bind (export "f") $b
+        "#,
+    )
+    .unwrap();
+    let error = binary::encode_unresolved(&section, &mut vec![]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(
+        error.to_string().ends_with("found Export(\"f\")"),
+        "{}",
+        error
+    );
+
+    let error = binary::decode_unresolved(&[1, 0, 1, 0, 1, 0, 0]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unknown function binding index 0 at offset 6"
+    );
+}
+
+#[test]
+fn custom_binding_expressions() {
+    let section = text::parse_unresolved(
+        r#"
+        func-binding import 0 long
+            (param (custom 128 1 2 3))
+            (result (custom 255))
+        bind 0 0
+        "#,
+    )
+    .unwrap();
+    let mut bytes = vec![];
+    binary::encode_unresolved(&section, &mut bytes).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(
+        bytes,
+        [
+            1, 0,
+            0, 0,
+            1, 1, 0, 0, 0x7b, 1, 128, 3, 1, 2, 3, 1, 255, 0,
+            1, 0, 0,
+        ]
+    );
+
+    // Their payloads are kept as is through decoding.
+    let decoded = binary::decode_unresolved(&bytes).unwrap();
+    match &decoded.bindings[0] {
+        unresolved::FunctionBinding::Import(b) => {
+            assert_eq!(
+                b.params.bindings,
+                [unresolved::OutgoingBindingExpression::Custom(
+                    OutgoingBindingExpressionCustom {
+                        opcode: 128,
+                        payload: vec![1, 2, 3],
+                    }
+                )]
+            );
+            assert_eq!(
+                b.result.bindings,
+                [unresolved::IncomingBindingExpression::Custom(
+                    IncomingBindingExpressionCustom {
+                        opcode: 255,
+                        payload: vec![],
+                    }
+                )]
+            );
+        }
+        b => panic!("expected an import binding, found {:?}", b),
+    }
+    let mut reencoded = vec![];
+    binary::encode_unresolved(&decoded, &mut reencoded).unwrap();
+    assert_eq!(reencoded, bytes);
+
+    // Opcodes below 128 belong to the proposal's expressions.
+    let mut section = section;
+    if let unresolved::FunctionBinding::Import(b) = &mut section.bindings[0] {
+        b.params.bindings[0] = OutgoingBindingExpressionCustom {
+            opcode: 7,
+            payload: vec![],
+        }
+        .into();
+    }
+    let error = binary::encode_unresolved(&section, &mut vec![]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(
+        text::parse_unresolved("func-binding import 0 long (param (custom 7)) (result)").is_err()
+    );
+}
+
+#[test]
+fn names_subsection() {
+    let bindings = r#"
+        type $Contact (dict (field "name" DOMString))
+        type $Name = DOMString
+        func-binding $b import 0 $Contact (param (as $Name 0)) (result)
+        bind 0 $b
+    "#;
+    let wasm = embed::embed(&wasm_with_import(), bindings).unwrap();
+    let extracted = embed::extract(&wasm).unwrap().unwrap();
+    assert!(extracted.contains("type $Contact"), "{}", extracted);
+    assert!(
+        extracted.contains("type $Name = DOMString"),
+        "{}",
+        extracted
+    );
+    assert!(extracted.contains("func-binding $b"), "{}", extracted);
+
+    let section = text::parse_unresolved(bindings).unwrap();
+    let mut with_names = vec![];
+    binary::encode_unresolved(&section, &mut with_names).unwrap();
+    let mut without_names = vec![];
+    binary::EncodeConfig::new()
+        .generate_names_subsection(false)
+        .encode_unresolved(&section, &mut without_names)
+        .unwrap();
+    // The header's features say whether the names subsection is there.
+    assert_eq!(with_names[..4], [1, 0x80, 0x80, 0x04]);
+    assert_eq!(without_names[..2], [1, 0]);
+    assert!(with_names[4..].starts_with(&without_names[2..]));
+    assert!(with_names.len() > without_names.len());
+
+    let decoded = binary::decode_unresolved(&with_names).unwrap();
+    assert!(decoded.types.by_name("$Contact").is_some());
+    assert_eq!(
+        decoded.types.resolve("$Name"),
+        Some(WebidlTypeRef::Scalar(WebidlScalarType::DomString))
+    );
+    assert_eq!(decoded.binding_by_name("$b"), Some(0));
+
+    let decoded = binary::decode_unresolved(&without_names).unwrap();
+    assert!(decoded.types.by_name("$Contact").is_none());
+    assert!(decoded.types.resolve("$Name").is_none());
+    assert_eq!(decoded.binding_by_name("$b"), None);
+}
+
+/// Bindings for an API surface with lots of repeated strings.
+fn repetitive_bindings() -> String {
+    let mut types = String::new();
+    let mut bindings = String::new();
+    for i in 0..20 {
+        types.push_str(&format!(
+            r#"
+            type $Dict{i} (dict (field "read" long) (field "length" long) (field "type" DOMString))
+            type $Enum{i} (enum "read" "write" "type")
+            "#,
+            i = i
+        ));
+        bindings.push_str(&format!(
+            r#"
+            func-binding $b{i} export 0 $Dict{i}
+                (param (alloc-utf8-str $__wbindgen_malloc (get 0)))
+                (result (dict $Dict{i} (as long 0) (as long 1) (utf8-str DOMString 2 3)))
+            "#,
+            i = i
+        ));
+    }
+    types + &bindings
+}
+
+fn encoded_size(bindings: &str, config: &binary::EncodeConfig) -> usize {
+    let section = text::parse_unresolved(bindings).unwrap();
+    let mut bytes = vec![];
+    config.encode_unresolved(&section, &mut bytes).unwrap();
+    bytes.len()
+}
+
+#[test]
+fn string_table_round_trips() {
+    let section = text::parse_unresolved(&repetitive_bindings()).unwrap();
+    let mut config = binary::EncodeConfig::new();
+    config.generate_string_table(true);
+    let mut bytes = vec![];
+    config.encode_unresolved(&section, &mut bytes).unwrap();
+
+    // The table has the field names and enumeration values, the allocator,
+    // and the names of the types and bindings. The most used strings get the
+    // smallest indices.
+    assert_eq!(
+        bytes[..10],
+        [1, 0x82, 0x80, 0x04, 3, 0xb1, 0x03, 65, 4, b'r']
+    );
+
+    let decoded = binary::decode_unresolved(&bytes).unwrap();
+    assert_eq!(decoded.binding_by_name("$b19"), Some(19));
+    let mut reencoded = vec![];
+    config.encode_unresolved(&decoded, &mut reencoded).unwrap();
+    assert_eq!(reencoded, bytes);
+
+    // A section without any strings doesn't get an empty string table.
+    let section = text::parse_unresolved("func-binding import 0 long (param) (result)").unwrap();
+    let mut table = vec![];
+    binary::EncodeConfig::new()
+        .generate_names_subsection(false)
+        .generate_string_table(true)
+        .encode_unresolved(&section, &mut table)
+        .unwrap();
+    let mut inline = vec![];
+    binary::encode_unresolved(&section, &mut inline).unwrap();
+    assert_eq!(table, inline);
+}
+
+#[test]
+fn string_table_sizes() {
+    // These sizes guard against regressions in the encoding's size. Update
+    // them when the encoding gets smaller.
+    let bindings = repetitive_bindings();
+    let mut config = binary::EncodeConfig::new();
+    config.generate_names_subsection(false);
+    assert_eq!(encoded_size(&bindings, &config), 1607);
+    config.generate_string_table(true);
+    assert_eq!(encoded_size(&bindings, &config), 752);
+
+    // Without repeated strings, the string table only costs a few bytes.
+    let bindings = r#"type $E (enum "a" "b" "c")"#;
+    let mut config = binary::EncodeConfig::new();
+    config.generate_names_subsection(false);
+    assert_eq!(encoded_size(bindings, &config), 15);
+    config.generate_string_table(true);
+    assert_eq!(encoded_size(bindings, &config), 21);
+}